@@ -0,0 +1,189 @@
+use crate::lexer::Span;
+use crate::parse::ParseError;
+use crate::scope::Scope;
+use crate::untyped_instruction::Instruction;
+use crate::untyped_instructions::Instructions;
+
+use std::collections::BTreeMap;
+
+/// A parsed TERM, before names have been lowered to Restack's and calls to
+/// define's have been expanded
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Term {
+    /// A built-in Instruction
+    Instruction {
+        instruction: Instruction,
+        span: Span,
+    },
+
+    /// A use of a let-bound name or param, or else a call to a define
+    Call {
+        name: String,
+        span: Span,
+    },
+
+    /// "let name", binding the top of the stack to the name
+    Let {
+        name: String,
+        span: Span,
+    },
+}
+
+impl Term {
+    /// Location of the Term
+    pub(crate) fn span(&self) -> Span {
+        match self {
+            Self::Instruction { span, .. } => *span,
+            Self::Call { span, .. } => *span,
+            Self::Let { span, .. } => *span,
+        }
+    }
+
+    /// Is the name used before it's rebound in the given Term's?
+    fn is_used_in(name: &str, terms: &[Term]) -> bool {
+        for term in terms {
+            match term {
+                Self::Call { name: called, .. } if called == name => return true,
+                Self::Let { name: bound, .. } if bound == name => return false,
+                _ => (),
+            }
+        }
+        false
+    }
+}
+
+/// A parsed "define name(params..) { body }"
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Define {
+    /// Name of the define
+    pub(crate) name: String,
+
+    /// Location of the name
+    pub(crate) span: Span,
+
+    /// Names of the inputs that the body consumes, the last being the top of
+    /// the stack
+    pub(crate) params: Vec<String>,
+
+    /// Unexpanded body
+    pub(crate) body: Vec<Term>,
+}
+
+/// All of the Define's in a program, by name
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Defines {
+    defines: BTreeMap<String, Define>,
+}
+
+impl Defines {
+    /// No Define's
+    pub(crate) fn new() -> Self {
+        Defines {
+            defines: BTreeMap::new(),
+        }
+    }
+
+    /// Add a Define, failing if its name is already defined
+    pub(crate) fn insert(&mut self, define: Define) -> Result<(), ParseError> {
+        if let Some(previous) = self.defines.get(&define.name) {
+            return Err(ParseError::DuplicateDefine {
+                name: define.name,
+                previous: previous.span,
+                span: define.span,
+            });
+        }
+        self.defines.insert(define.name.clone(), define);
+        Ok(())
+    }
+
+    /// Check that every Define is non-recursive, well-typed, and consumes
+    /// exactly as many inputs as it has params.
+    ///
+    /// Definitions may refer to each other in any order, but no definition
+    /// may (directly or indirectly) call itself.
+    pub(crate) fn check(&self) -> Result<(), ParseError> {
+        for define in self.defines.values() {
+            let instructions = self.lower(&define.body, &define.params, &mut vec![define.name.clone()])?;
+            let instrs = Instructions {
+                docs: vec![],
+                instructions: instructions,
+            }.to_instrs().map_err(|e| ParseError::DefineInstructionError {
+                name: define.name.clone(),
+                error: e,
+                span: define.span,
+            })?;
+            let body_type = instrs.type_of().map_err(|e| ParseError::DefineTypeError {
+                name: define.name.clone(),
+                error: e,
+                span: define.span,
+            })?;
+            if body_type.i_type.len() != define.params.len() {
+                return Err(ParseError::DefineArity {
+                    name: define.name.clone(),
+                    expected: define.params.len(),
+                    found: body_type.i_type.len(),
+                    span: define.span,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Lower a program to a flat list of Instruction's
+    pub(crate) fn expand(&self, terms: &[Term]) -> Result<Vec<Instruction>, ParseError> {
+        self.lower(terms, &[], &mut vec![])
+    }
+
+    /// Lower the Term's in a new Scope with the given params, inlining every
+    /// call to a Define and replacing every use of a name with Restack's.
+    ///
+    /// "expanding" holds the names of the Define's currently being expanded,
+    /// outermost first
+    fn lower(&self, terms: &[Term], params: &[String], expanding: &mut Vec<String>) -> Result<Vec<Instruction>, ParseError> {
+        let mut scope = Scope::new(params);
+        let mut instructions = vec![];
+        for (term_index, term) in terms.iter().enumerate() {
+            match term {
+                Term::Instruction { instruction, span } => {
+                    scope.run(std::slice::from_ref(instruction), *span)?;
+                    instructions.push(instruction.clone());
+                },
+                Term::Let { name, span } => scope.bind(name, *span),
+                Term::Call { name, span } => {
+                    let used_later = Term::is_used_in(name, &terms[term_index + 1..]);
+                    match scope.use_name(name, used_later, *span)? {
+                        Some(restacks) => instructions.extend(restacks.into_iter().map(Instruction::Restack)),
+                        None => {
+                            let body = self.lower_call(name, *span, expanding)?;
+                            scope.run(&body, *span)?;
+                            instructions.extend(body);
+                        },
+                    }
+                },
+            }
+        }
+        instructions.extend(scope.finish().into_iter().map(Instruction::Restack));
+        Ok(instructions)
+    }
+
+    /// Lower the body of the named Define, failing if it's unknown or
+    /// already being expanded
+    fn lower_call(&self, name: &str, span: Span, expanding: &mut Vec<String>) -> Result<Vec<Instruction>, ParseError> {
+        if let Some(position) = expanding.iter().position(|x| x == name) {
+            let mut cycle = expanding[position..].to_vec();
+            cycle.push(name.to_string());
+            return Err(ParseError::RecursiveDefine {
+                cycle: cycle.join(" -> "),
+                span: span,
+            });
+        }
+        let define = self.defines.get(name).ok_or_else(|| ParseError::UnsupportedInstruction {
+            instruction: name.to_string(),
+            span: span,
+        })?;
+        expanding.push(name.to_string());
+        let body = self.lower(&define.body, &define.params, expanding)?;
+        expanding.pop();
+        Ok(body)
+    }
+}
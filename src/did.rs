@@ -0,0 +1,493 @@
+use crate::json_template::TValue;
+use crate::key_type::{bls12_381_public_key, KeyType, KeyTypeError};
+use crate::multiformats::{decode_multibase, split_multicodec, MultiformatsError};
+use crate::query::{QueryTemplate, QueryType};
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use serde_json::{json, Map, Value};
+use thiserror::Error;
+
+/// The base JSON-LD context of DID documents
+pub const DID_CONTEXT_V1: &str = "https://www.w3.org/ns/did/v1";
+
+/// The JSON-LD context of Multikey verification methods
+pub const MULTIKEY_CONTEXT_V1: &str = "https://w3id.org/security/multikey/v1";
+
+/// Verification relationships, which may embed verification methods. A
+/// locally-resolved DID grants all of them to its only verification method
+const VERIFICATION_RELATIONSHIPS: [&str; 4] = ["authentication", "assertionMethod", "capabilityInvocation", "capabilityDelegation"];
+
+/// Resolve a did:key or did:pkh to its DID document, deriving its only
+/// verification method from the identifier.
+///
+/// did:web can't be resolved locally: see did_web_query_template.
+pub fn resolve_did(did: &str) -> Result<Map<String, Value>, DidError> {
+    match did.strip_prefix("did:").and_then(|method_specific| method_specific.split(':').next()) {
+        Some("key") => resolve_did_key(did),
+        Some("pkh") => resolve_did_pkh(did),
+        Some("web") => Err(DidError::DidWebRequiresQuery {
+            did: did.to_string(),
+        }),
+        _ => Err(DidError::UnsupportedMethod {
+            did: did.to_string(),
+        }),
+    }
+}
+
+/// Resolve a did:key, i.e. "did:key:" followed by a multibase (base58btc)
+/// multicodec-prefixed public key, as a Multikey verification method.
+///
+/// The method also includes a publicKeyJwk, e.g. for verify_jws, unless the
+/// KeyType is Bls12381.
+pub fn resolve_did_key(did: &str) -> Result<Map<String, Value>, DidError> {
+    let invalid_did = || DidError::InvalidDid {
+        did: did.to_string(),
+        expected: "did:key:z{base58btc multicodec public key}",
+    };
+    let multibase_key = did.strip_prefix("did:key:").ok_or_else(invalid_did)?;
+    if !multibase_key.starts_with('z') {
+        return Err(invalid_did())
+    }
+    let decoded = decode_multibase(multibase_key)?;
+    let (code, public_key) = split_multicodec(&decoded)?;
+    let key_type = KeyType::from_multicodec_code(code)
+        .ok_or(DidError::UnsupportedMulticodec {
+            code: code,
+        })?;
+    let mut method = json!({
+        "id": format!("{}#{}", did, multibase_key),
+        "type": "Multikey",
+        "controller": did,
+        "publicKeyMultibase": multibase_key,
+    });
+    if let Some(jwk) = public_key_jwk(key_type, public_key)? {
+        method["publicKeyJwk"] = jwk;
+    }
+    Ok(document(did, MULTIKEY_CONTEXT_V1, method))
+}
+
+/// Resolve a did:pkh for an Ethereum account, i.e.
+/// "did:pkh:eip155:{chain_id}:{address}", as an
+/// EcdsaSecp256k1RecoveryMethod2020 verification method (see ecrecover)
+pub fn resolve_did_pkh(did: &str) -> Result<Map<String, Value>, DidError> {
+    let account_id = did.strip_prefix("did:pkh:")
+        .ok_or_else(|| DidError::InvalidDid {
+            did: did.to_string(),
+            expected: "did:pkh:{namespace}:{reference}:{address}",
+        })?;
+    let parts: Vec<&str> = account_id.split(':').collect();
+    match parts.as_slice() {
+        ["eip155", chain_id, address] => {
+            let is_chain_id = !chain_id.is_empty() && chain_id.chars().all(|c| c.is_ascii_digit());
+            let is_address = address.len() == 42 && address.starts_with("0x") &&
+                address[2..].chars().all(|c| c.is_ascii_hexdigit());
+            if !is_chain_id || !is_address {
+                return Err(DidError::InvalidDid {
+                    did: did.to_string(),
+                    expected: "did:pkh:eip155:{decimal chain id}:0x{40 hex digits}",
+                })
+            }
+            let method = json!({
+                "id": format!("{}#blockchainAccountId", did),
+                "type": "EcdsaSecp256k1RecoveryMethod2020",
+                "controller": did,
+                "blockchainAccountId": account_id,
+            });
+            Ok(document(did, "https://w3id.org/security/suites/secp256k1recovery-2020/v2", method))
+        },
+        [namespace, ..] => Err(DidError::UnsupportedPkhNamespace {
+            namespace: namespace.to_string(),
+        }),
+        [] => unreachable!("split always returns at least one part"),
+    }
+}
+
+/// The HTTPS URL of a did:web's DID document, e.g.
+/// - did:web:example.com -> https://example.com/.well-known/did.json
+/// - did:web:example.com%3A3000:user:alice -> https://example.com:3000/user/alice/did.json
+pub fn did_web_url(did: &str) -> Result<String, DidError> {
+    let invalid_did = || DidError::InvalidDid {
+        did: did.to_string(),
+        expected: "did:web:{domain}(:{path})*",
+    };
+    let mut parts = did.strip_prefix("did:web:").ok_or_else(invalid_did)?.split(':');
+    let domain = parts.next().unwrap_or("").replace("%3A", ":").replace("%3a", ":");
+    let path: Vec<&str> = parts.collect();
+    if domain.is_empty() || domain.contains('/') || path.iter().any(|part| part.is_empty() || part.contains('/')) {
+        return Err(invalid_did())
+    }
+    if path.is_empty() {
+        Ok(format!("https://{}/.well-known/did.json", domain))
+    } else {
+        Ok(format!("https://{}/{}/did.json", domain, path.join("/")))
+    }
+}
+
+/// A cached GET QueryTemplate for a did:web's DID document, named after the
+/// DID. Its url may be replaced, e.g. to be served by rest-api.
+///
+/// The query's result should be checked with check_did_document.
+pub fn did_web_query_template(did: &str) -> Result<QueryTemplate, DidError> {
+    Ok(QueryTemplate {
+        name: did.to_string(),
+        url: did_web_url(did)?,
+        template: TValue::Null,
+        cached: true,
+        query_type: QueryType::Get,
+    })
+}
+
+/// Check that the document is a DID document for the given DID, i.e. an
+/// Object with the DID as its "id"
+pub fn check_did_document(did: &str, document: &Map<String, Value>) -> Result<(), DidError> {
+    match document.get("id") {
+        Some(Value::String(id)) if id == did => Ok(()),
+        id => Err(DidError::DocumentIdMismatch {
+            did: did.to_string(),
+            id: id.cloned(),
+        }),
+    }
+}
+
+/// Select a verification method from a DID document by its id, which may be
+/// relative to the document's id (e.g. "#key-1").
+///
+/// Methods embedded in verification relationships (e.g. authentication) are
+/// also searched.
+pub fn select_verification_method(document: &Map<String, Value>, id: &str) -> Result<Map<String, Value>, DidError> {
+    let expected_id = absolute_method_id(document, id);
+    ["verificationMethod"].iter().chain(VERIFICATION_RELATIONSHIPS.iter())
+        .filter_map(|member| document.get(*member).and_then(Value::as_array))
+        .flatten()
+        .filter_map(Value::as_object)
+        .find(|method| method.get("id").and_then(Value::as_str).map(|id| absolute_method_id(document, id)) == Some(expected_id.clone()))
+        .cloned()
+        .ok_or_else(|| DidError::UnknownVerificationMethod {
+            id: id.to_string(),
+            document: document.clone(),
+        })
+}
+
+/// The verification methods of a DID document in the given verification
+/// relationship (e.g. "assertionMethod"), whether embedded or referenced by
+/// id
+pub fn relationship_verification_methods(document: &Map<String, Value>, relationship: &str) -> Result<Vec<Map<String, Value>>, DidError> {
+    document.get(relationship).and_then(Value::as_array).into_iter()
+        .flatten()
+        .filter_map(|method| match method {
+            Value::String(id) => Some(select_verification_method(document, id)),
+            Value::Object(method) => Some(Ok(method.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A verification method id, made absolute if it's relative to the
+/// document's id (e.g. "#key-1")
+pub(crate) fn absolute_method_id(document: &Map<String, Value>, id: &str) -> String {
+    if id.starts_with('#') {
+        format!("{}{}", document.get("id").and_then(Value::as_str).unwrap_or(""), id)
+    } else {
+        id.to_string()
+    }
+}
+
+fn document(did: &str, method_context: &str, method: Value) -> Map<String, Value> {
+    let method_id = method["id"].clone();
+    let mut document = Map::new();
+    document.insert("@context".to_string(), json!([DID_CONTEXT_V1, method_context]));
+    document.insert("id".to_string(), Value::String(did.to_string()));
+    document.insert("verificationMethod".to_string(), json!([method]));
+    for relationship in VERIFICATION_RELATIONSHIPS.iter() {
+        document.insert(relationship.to_string(), json!([method_id]));
+    }
+    document
+}
+
+/// Validate a public key, returning its JWK (RFC 7517), if any
+fn public_key_jwk(key_type: KeyType, public_key: &[u8]) -> Result<Option<Value>, DidError> {
+    let malformed_public_key = || KeyTypeError::MalformedPublicKey {
+        key_type: key_type,
+        public_key: public_key.to_vec(),
+    };
+    let ec_jwk = |crv: &str, point: &[u8]| json!({
+        "kty": "EC",
+        "crv": crv,
+        "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+        "y": URL_SAFE_NO_PAD.encode(&point[33..65]),
+    });
+    match key_type {
+        KeyType::Secp256k1 => {
+            let point = k256::PublicKey::from_sec1_bytes(public_key)
+                .map_err(|_| malformed_public_key())?
+                .to_encoded_point(false);
+            Ok(Some(ec_jwk("secp256k1", point.as_bytes())))
+        },
+        KeyType::P256 => {
+            let point = p256::PublicKey::from_sec1_bytes(public_key)
+                .map_err(|_| malformed_public_key())?
+                .to_encoded_point(false);
+            Ok(Some(ec_jwk("P-256", point.as_bytes())))
+        },
+        KeyType::Ed25519 => {
+            ed25519_dalek::PublicKey::from_bytes(public_key)
+                .map_err(|_| malformed_public_key())?;
+            Ok(Some(json!({
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "x": URL_SAFE_NO_PAD.encode(public_key),
+            })))
+        },
+        KeyType::Bls12381 => {
+            bls12_381_public_key(public_key)?;
+            Ok(None)
+        },
+    }
+}
+
+/// DID resolution errors
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum DidError {
+    /// Only did:key, did:pkh and did:web are supported
+    #[error("unsupported DID method, expected did:key, did:pkh or did:web: {did}")]
+    UnsupportedMethod {
+        /// The DID
+        did: String,
+    },
+
+    /// The DID is malformed
+    #[error("invalid DID, expected {expected}: {did}")]
+    InvalidDid {
+        /// The DID
+        did: String,
+        /// The expected format
+        expected: &'static str,
+    },
+
+    /// did:web must be resolved with a QueryTemplate
+    #[error("{did} must be resolved with a query: see did_web_query_template")]
+    DidWebRequiresQuery {
+        /// The DID
+        did: String,
+    },
+
+    /// The did:key's multicodec isn't a supported KeyType
+    #[error("unsupported did:key multicodec: 0x{code:x}")]
+    UnsupportedMulticodec {
+        /// The multicodec code
+        code: u64,
+    },
+
+    /// Only Ethereum (eip155) accounts are supported by did:pkh
+    #[error("unsupported did:pkh namespace, expected eip155: {namespace}")]
+    UnsupportedPkhNamespace {
+        /// The CAIP-2 namespace
+        namespace: String,
+    },
+
+    /// The DID document's id isn't the DID
+    #[error("expected a DID document for {did}, but its id is {id:?}")]
+    DocumentIdMismatch {
+        /// The DID
+        did: String,
+        /// The document's id, if any
+        id: Option<Value>,
+    },
+
+    /// The DID document has no verification method with the given id
+    #[error("no verification method {id:?} in the DID document: {document:?}")]
+    UnknownVerificationMethod {
+        /// The verification method id
+        id: String,
+        /// The DID document
+        document: Map<String, Value>,
+    },
+
+    /// The did:key's multibase or multicodec is malformed
+    #[error("{0}")]
+    MultiformatsError(#[from] MultiformatsError),
+
+    /// The did:key's public key is malformed
+    #[error("{0}")]
+    KeyTypeError(#[from] KeyTypeError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::QueryTemplates;
+
+    use std::fs;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+
+    const LOCAL_DID_WEB_QUERY: &str = include_str!("../examples/local_did_web_query.json");
+
+    #[test]
+    fn test_verification_methods() {
+        let did = "did:web:credentials.corp.com";
+        let method = json!({
+            "id": "#key-1",
+            "type": "JsonWebKey2020",
+            "controller": did,
+            "publicKeyJwk": {"kty": "OKP", "crv": "Ed25519", "x": "Lm_M42cB3HkUiODQsXRcweM6TByfzEHGO9ND274JcOY"},
+        });
+        let embedded_method = json!({
+            "id": format!("{}#key-2", did),
+            "type": "JsonWebKey2020",
+            "controller": did,
+        });
+        let document = json!({
+            "@context": [DID_CONTEXT_V1],
+            "id": did,
+            "verificationMethod": [method],
+            "assertionMethod": [format!("{}#key-1", did)],
+            "authentication": ["#key-1", embedded_method],
+        });
+        let document = document.as_object().expect("not an Object");
+        assert_eq!(Ok(()), check_did_document(did, document));
+        assert!(matches!(check_did_document("did:web:evil.com", document), Err(DidError::DocumentIdMismatch { .. })));
+
+        let method = method.as_object().expect("not an Object").clone();
+        let embedded_method = embedded_method.as_object().expect("not an Object").clone();
+        assert_eq!(Ok(method.clone()), select_verification_method(document, &format!("{}#key-1", did)));
+        assert_eq!(Ok(embedded_method.clone()), select_verification_method(document, "#key-2"));
+        assert!(matches!(select_verification_method(document, "#key-3"), Err(DidError::UnknownVerificationMethod { .. })));
+
+        assert_eq!(Ok(vec![method.clone()]), relationship_verification_methods(document, "assertionMethod"));
+        assert_eq!(Ok(vec![method, embedded_method]), relationship_verification_methods(document, "authentication"));
+        assert_eq!(Ok(vec![]), relationship_verification_methods(document, "keyAgreement"));
+    }
+
+    #[test]
+    fn test_resolve_did_key() {
+        let did = "did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK";
+        let document = resolve_did(did).expect("failed to resolve did:key");
+        let method_id = format!("{}#z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK", did);
+        assert_eq!(Some(&json!([method_id])), document.get("authentication"));
+        let method = select_verification_method(&document, "#z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK")
+            .expect("missing verification method");
+        assert_eq!(Some(&json!({"kty": "OKP", "crv": "Ed25519", "x": "Lm_M42cB3HkUiODQsXRcweM6TByfzEHGO9ND274JcOY"})),
+                   method.get("publicKeyJwk"));
+        assert_eq!(Ok(vec![method.clone()]), relationship_verification_methods(&document, "assertionMethod"));
+        assert_eq!(Ok(vec![]), relationship_verification_methods(&document, "keyAgreement"));
+        assert_eq!(Ok(method), select_verification_method(&document, &method_id));
+
+        let signing_key = k256::ecdsa::SigningKey::from_bytes(&[0x11; 32]).expect("invalid secp256k1 key");
+        let public_key = signing_key.verifying_key().to_encoded_point(true);
+        let secp256k1_did = format!("did:key:{}", multibase::encode(multibase::Base::Base58Btc,
+                                                                   [&[0xe7, 0x01], public_key.as_bytes()].concat()));
+        assert!(secp256k1_did.starts_with("did:key:zQ3s"), "{}", secp256k1_did);
+        let document = resolve_did(&secp256k1_did).expect("failed to resolve did:key");
+        let jwk = document["verificationMethod"][0]["publicKeyJwk"].as_object().expect("missing publicKeyJwk");
+        assert_eq!(Ok((KeyType::Secp256k1, signing_key.verifying_key().to_encoded_point(false).as_bytes().to_vec())),
+                   crate::jws::jwk_public_key(jwk));
+
+        assert!(matches!(resolve_did("did:key:z6LSbysY2xFMRpGMhb7tFTLMpeuPRaqaWM1yECx2AtzE3KCc"),
+                         Err(DidError::UnsupportedMulticodec { code: 0xec })));
+        assert!(matches!(resolve_did("did:key:6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK"),
+                         Err(DidError::InvalidDid { .. })));
+        assert!(matches!(resolve_did("did:ion:EiD3DIbDgBCajj2zCkE48x74FKTV9_Dcu1u_imzZddDKfg"),
+                         Err(DidError::UnsupportedMethod { .. })));
+        assert!(matches!(resolve_did("did:web:credentials.corp.com"), Err(DidError::DidWebRequiresQuery { .. })));
+    }
+
+    #[test]
+    fn test_resolve_did_pkh() {
+        let did = "did:pkh:eip155:1:0xb9c5714089478a327f09197987f16f9e5d936e8a";
+        let document = resolve_did(did).expect("failed to resolve did:pkh");
+        assert_eq!(json!({
+            "id": format!("{}#blockchainAccountId", did),
+            "type": "EcdsaSecp256k1RecoveryMethod2020",
+            "controller": did,
+            "blockchainAccountId": "eip155:1:0xb9c5714089478a327f09197987f16f9e5d936e8a",
+        }).as_object(), select_verification_method(&document, "#blockchainAccountId").ok().as_ref());
+
+        assert!(matches!(resolve_did("did:pkh:eip155:0xb9c5714089478a327f09197987f16f9e5d936e8a"),
+                         Err(DidError::UnsupportedPkhNamespace { .. })));
+        assert!(matches!(resolve_did("did:pkh:eip155:1:0xb9c5"), Err(DidError::InvalidDid { .. })));
+        assert!(matches!(resolve_did("did:pkh:bip122:000000000019d6689c085ae165831e93:128Lkh3S7CkDTBZ8W7BbpsN3YYizJMp8p6"),
+                         Err(DidError::UnsupportedPkhNamespace { .. })));
+    }
+
+    #[test]
+    fn test_did_web_url() {
+        assert_eq!(Ok("https://w3c-ccg.github.io/.well-known/did.json".to_string()),
+                   did_web_url("did:web:w3c-ccg.github.io"));
+        assert_eq!(Ok("https://w3c-ccg.github.io/user/alice/did.json".to_string()),
+                   did_web_url("did:web:w3c-ccg.github.io:user:alice"));
+        assert_eq!(Ok("https://example.com:3000/.well-known/did.json".to_string()),
+                   did_web_url("did:web:example.com%3A3000"));
+        assert!(did_web_url("did:web:").is_err());
+        assert!(did_web_url("did:web:example.com::alice").is_err());
+        assert!(did_web_url("did:key:example.com").is_err());
+    }
+
+    // Serve a single HTTP response on a local port, sending the request line
+    // (e.g. "GET /apis/did_web HTTP/1.1") back over the channel
+    fn serve_once(body: String) -> (u16, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        let port = listener.local_addr().expect("no local address").port();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().expect("failed to accept");
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).expect("failed to read request");
+            let mut content_length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).expect("failed to read header");
+                if header.trim().is_empty() {
+                    break
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().expect("invalid content-length");
+                    }
+                }
+            }
+            let mut request_body = vec![0; content_length];
+            reader.read_exact(&mut request_body).expect("failed to read request body");
+            write!(reader.get_mut(), "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                   body.len(), body).expect("failed to respond");
+            sender.send(request_line.trim_end().to_string()).expect("failed to send request line");
+        });
+        (port, receiver)
+    }
+
+    #[tokio::test]
+    async fn test_did_web_query() {
+        let did = "did:web:credentials.corp.com";
+        let queries: QueryTemplates = serde_json::from_str(LOCAL_DID_WEB_QUERY).expect("invalid example queries");
+        let mut query_template = did_web_query_template(did).expect("invalid did:web");
+        query_template.url = "http://127.0.0.1:8080/apis/did_web".to_string();
+        let query_json = query_template.to_json().expect("failed to serialize QueryTemplate");
+        let example_json = serde_json::to_value(&queries).expect("failed to serialize QueryTemplates");
+        assert_eq!(Some(&query_json), example_json["queries"].as_array().and_then(|queries| queries.last()));
+
+        // Serve the document that the example registers with rest-api
+        let setup = &example_json["queries"][0];
+        let expected_document = setup["template"]["Object"]["response"].clone();
+        let expected_document: Value = serde_json::from_value::<TValue>(expected_document).expect("invalid TValue")
+            .run(Map::new()).expect("failed to run TValue");
+        let (port, request_line) = serve_once(expected_document.to_string());
+        query_template.url = format!("http://127.0.0.1:{}/apis/did_web", port);
+
+        let cache_location = std::env::temp_dir().join(format!("cryptoscript_did_web_{}.json", std::process::id()));
+        let _ = fs::remove_file(&cache_location);
+        let result = query_template.to_query(Arc::new(Map::new()), Arc::new(cache_location.clone())).run().await;
+        let _ = fs::remove_file(&cache_location);
+        let document = result.expect("failed to run query").as_object().expect("not an Object").clone();
+        assert_eq!(Ok("GET /apis/did_web HTTP/1.1".to_string()), request_line.recv());
+        assert_eq!(expected_document, Value::Object(document.clone()));
+        assert_eq!(Ok(()), check_did_document(did, &document));
+        assert!(matches!(check_did_document("did:web:evil.com", &document), Err(DidError::DocumentIdMismatch { .. })));
+        let method = select_verification_method(&document, "#key-1").expect("missing verification method");
+        assert_eq!(Some("JsonWebKey2020"), method.get("type").and_then(Value::as_str));
+    }
+}
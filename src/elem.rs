@@ -44,7 +44,7 @@ impl PartialOrd for Elem {
             (Self::Unit, Self::Unit) => Some(cmp::Ordering::Equal),
             (Self::Bool(x), Self::Bool(y)) => x.partial_cmp(y),
             (Self::Bytes(x), Self::Bytes(y)) => x.partial_cmp(y),
-            (Self::Number(x), Self::Number(y)) => x.to_string().partial_cmp(&y.to_string()),
+            (Self::Number(x), Self::Number(y)) => Self::number_partial_cmp(x, y),
             (Self::String(x), Self::String(y)) => x.partial_cmp(y),
             (Self::Array(x), Self::Array(y)) => if x == y { Some(cmp::Ordering::Equal) } else { None },
             (Self::Object(x), Self::Object(y)) => if x == y { Some(cmp::Ordering::Equal) } else { None }
@@ -53,6 +53,19 @@ impl PartialOrd for Elem {
     }
 }
 
+impl Elem {
+    /// Order Numbers by value: exactly for integers, and otherwise as f64's
+    fn number_partial_cmp(x: &Number, y: &Number) -> Option<cmp::Ordering> {
+        match (x.as_i64(), y.as_i64(), x.as_u64(), y.as_u64()) {
+            (Some(x), Some(y), _, _) => Some(x.cmp(&y)),
+            (_, _, Some(x), Some(y)) => Some(x.cmp(&y)),
+            (Some(_), None, _, Some(_)) => Some(cmp::Ordering::Less),
+            (None, Some(_), Some(_), _) => Some(cmp::Ordering::Greater),
+            _ => x.as_f64()?.partial_cmp(&y.as_f64()?),
+        }
+    }
+}
+
 impl Display for Elem {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
         match self {
@@ -240,6 +253,18 @@ mod elem_symbol_tests {
             assert_eq!(default_elem, default_elem.symbol().default_elem())
         }
     }
+
+    #[test]
+    fn test_number_partial_cmp() {
+        let number = |x: &str| Elem::Number(x.parse().expect("invalid Number"));
+        assert!(number("9") < number("10"));
+        assert!(number("-10") < number("-9"));
+        assert!(number("-1") < number("18446744073709551615"));
+        assert!(number("18446744073709551615") > number("2"));
+        assert!(number("1.5") < number("2"));
+        assert!(number("1e3") > number("999"));
+        assert_eq!(Some(cmp::Ordering::Equal), number("2").partial_cmp(&number("2")));
+    }
 }
 
 impl Elem {
@@ -252,5 +277,21 @@ impl Elem {
     pub fn symbol_str(&self) -> &'static str {
       From::from(self.symbol())
     }
+
+    /// Format as a cryptoscript literal, i.e. the argument of "push"
+    ///
+    /// Unlike Display, the result can be parsed back into the same Elem
+    pub fn literal(&self) -> String {
+        match self {
+            Self::Unit => "()".to_string(),
+            Self::Bool(x) => x.to_string(),
+            Self::Number(x) => x.to_string(),
+            Self::Bytes(x) => format!("0x{}", hex::encode(x.as_slice())),
+            Self::String(x) => Value::String(x.clone()).to_string(),
+            Self::Array(x) => Value::Array(x.clone()).to_string(),
+            Self::Object(x) => Value::Object(x.clone()).to_string(),
+            Self::Json(x) => format!("json {}", x),
+        }
+    }
 }
 
@@ -99,7 +99,7 @@ where
         });
         Ok(Type {
             context: context,
-            i_type: (1..num_inputs).into_iter().map(|_| type_id).collect(),
+            i_type: (0..num_inputs).into_iter().map(|_| type_id).collect(),
             o_type: vec![type_id],
         })
     }
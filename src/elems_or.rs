@@ -111,9 +111,12 @@ where
     where
         Self: Sized,
     {
+        // restore any Elem's popped by a failed Singleton::pop before trying U
+        let original_stack = stack.clone();
         match <Singleton<T, N> as Elems>::pop(PhantomData, stack) {
             Ok(Singleton { array }) => Ok(Self::Left(array)),
             Err(hd_error) => {
+                *stack = original_stack;
                 Elems::pop(PhantomData::<U>, stack)
                     .map(|x| Self::Right(x))
                     .map_err(|tl_errors| {
@@ -137,3 +140,23 @@ where
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Number;
+    use generic_array::typenum::U1;
+
+    // Singleton::pop consumes the stack before failing, which used to leave U
+    // to pop the wrong Elem's (or none at all)
+    #[test]
+    fn test_or_pop_restores_stack() {
+        let mut stack = Stack::new();
+        stack.push(Elem::Bool(true));
+        stack.push(Elem::Number(From::from(1u8)));
+        let popped = Elems::pop(PhantomData::<Or<String, U1, Singleton<Number, U1>>>, &mut stack)
+            .expect("failed to pop a Number");
+        assert_eq!(Or::Right(Singleton { array: GenericArray::from([Number::from(1u8)]) }), popped);
+        assert_eq!(vec![Elem::Bool(true)], stack.stack);
+    }
+}
@@ -0,0 +1,185 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use blake2::VarBlake2b;
+use blake2::digest::{Update, VariableOutput};
+use quickcheck::{Arbitrary, Gen};
+use ripemd160::Ripemd160;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use sha3::{Keccak256, Sha3_256};
+use thiserror::Error;
+
+/// A supported hash function
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum HashFunction {
+    /// SHA-256
+    Sha256,
+
+    /// SHA3-256 (FIPS 202)
+    Sha3_256,
+
+    /// Keccak-256, as used by Ethereum (i.e. SHA3-256 before standardization)
+    Keccak256,
+
+    /// SHA-512
+    Sha512,
+
+    /// BLAKE2b, with a 256-bit output
+    Blake2b256,
+
+    /// BLAKE3, with its default 256-bit output
+    Blake3,
+
+    /// RIPEMD-160, as used (after SHA-256) by Bitcoin addresses
+    Ripemd160,
+}
+
+impl HashFunction {
+    /// All HashFunction's
+    pub fn all() -> [Self; 7] {
+        [
+            Self::Sha256,
+            Self::Sha3_256,
+            Self::Keccak256,
+            Self::Sha512,
+            Self::Blake2b256,
+            Self::Blake3,
+            Self::Ripemd160,
+        ]
+    }
+
+    /// The HashFunction with the given name (as formatted by Display)
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().iter().copied().find(|hash_function| hash_function.to_string() == name)
+    }
+
+    /// The multihash code of the HashFunction, from the multicodec table
+    pub fn multihash_code(&self) -> u64 {
+        match self {
+            Self::Sha256 => 0x12,
+            Self::Sha3_256 => 0x16,
+            Self::Keccak256 => 0x1b,
+            Self::Sha512 => 0x13,
+            Self::Blake2b256 => 0xb220,
+            Self::Blake3 => 0x1e,
+            Self::Ripemd160 => 0x1053,
+        }
+    }
+
+    /// The HashFunction with the given multihash code
+    pub fn from_multihash_code(code: u64) -> Option<Self> {
+        Self::all().iter().copied().find(|hash_function| hash_function.multihash_code() == code)
+    }
+
+    /// Length of the digest in bytes
+    pub fn output_len(&self) -> usize {
+        match self {
+            Self::Sha256 => 32,
+            Self::Sha3_256 => 32,
+            Self::Keccak256 => 32,
+            Self::Sha512 => 64,
+            Self::Blake2b256 => 32,
+            Self::Blake3 => 32,
+            Self::Ripemd160 => 20,
+        }
+    }
+
+    /// Check that a digest (e.g. a Merkle node) is output_len() bytes
+    pub fn check_output_len(&self, digest: &[u8]) -> Result<(), DigestLengthError> {
+        if digest.len() == self.output_len() {
+            Ok(())
+        } else {
+            Err(DigestLengthError {
+                hash_function: *self,
+                digest: digest.to_vec(),
+            })
+        }
+    }
+
+    /// Hash the input, returning output_len() bytes
+    pub fn digest(&self, input: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Sha256 => super::sha256(&input.to_vec()),
+            Self::Sha3_256 => Sha3_256::digest(input).to_vec(),
+            Self::Keccak256 => Keccak256::digest(input).to_vec(),
+            Self::Sha512 => Sha512::digest(input).to_vec(),
+            Self::Blake2b256 => {
+                let mut hasher = VarBlake2b::new(32).expect("BLAKE2b supports 32-byte outputs");
+                hasher.update(input);
+                hasher.finalize_boxed().to_vec()
+            },
+            Self::Blake3 => blake3::hash(input).as_bytes().to_vec(),
+            Self::Ripemd160 => Ripemd160::digest(input).to_vec(),
+        }
+    }
+}
+
+// Formatting uses the names accepted by from_name, e.g. "sha3_256"
+impl Display for HashFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        let name = match self {
+            Self::Sha256 => "sha256",
+            Self::Sha3_256 => "sha3_256",
+            Self::Keccak256 => "keccak256",
+            Self::Sha512 => "sha512",
+            Self::Blake2b256 => "blake2b_256",
+            Self::Blake3 => "blake3",
+            Self::Ripemd160 => "ripemd160",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A digest isn't HashFunction::output_len() bytes
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+#[error("expected a {} byte {hash_function} digest, but found {} bytes: 0x{}", .hash_function.output_len(), .digest.len(), hex::encode(.digest))]
+pub struct DigestLengthError {
+    /// The HashFunction
+    pub hash_function: HashFunction,
+    /// The digest
+    pub digest: Vec<u8>,
+}
+
+impl Arbitrary for HashFunction {
+    fn arbitrary(g: &mut Gen) -> Self {
+        *g.choose(&Self::all()).unwrap_or(&Self::Sha256)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn test_digest() {
+        for (hash_function, input, expected) in [
+            (HashFunction::Sha256, b"abc".as_slice(), hex!("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad").to_vec()),
+            (HashFunction::Sha3_256, b"abc", hex!("3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532").to_vec()),
+            (HashFunction::Keccak256, b"", hex!("c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470").to_vec()),
+            (HashFunction::Sha512, b"abc", hex!("ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f").to_vec()),
+            (HashFunction::Blake2b256, b"abc", hex!("bddd813c634239723171ef3fee98579b94964e3bb1cb3e427262c8c068d52319").to_vec()),
+            (HashFunction::Blake3, b"", hex!("af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262").to_vec()),
+            (HashFunction::Ripemd160, b"abc", hex!("8eb208f7e05d987a9b044a8e98c6b087f15a0bfc").to_vec()),
+        ] {
+            assert_eq!(expected, hash_function.digest(input), "{}", hash_function);
+            assert_eq!(hash_function.output_len(), expected.len(), "{}", hash_function);
+            assert_eq!(Some(hash_function), HashFunction::from_name(&hash_function.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_check_output_len() {
+        for hash_function in HashFunction::all() {
+            let digest = hash_function.digest(b"abc");
+            assert_eq!(Ok(()), hash_function.check_output_len(&digest), "{}", hash_function);
+            for invalid in [&[][..], &digest[1..], &[digest.as_slice(), &[0]].concat()] {
+                assert_eq!(Err(DigestLengthError { hash_function: hash_function, digest: invalid.to_vec() }),
+                           hash_function.check_output_len(invalid), "{}", hash_function);
+            }
+        }
+        let error = HashFunction::Ripemd160.check_output_len(&[0xab]).expect_err("too short");
+        assert_eq!("expected a 20 byte ripemd160 digest, but found 1 bytes: 0xab", error.to_string());
+    }
+}
@@ -0,0 +1,296 @@
+use crate::key_type::{KeyType, KeyTypeError};
+
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use thiserror::Error;
+
+/// A supported JWS "alg" (RFC 7518, RFC 8037 and RFC 8812)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum JwsAlgorithm {
+    /// ECDSA over P-256 of SHA-256(signing input)
+    Es256,
+
+    /// ECDSA over secp256k1 of SHA-256(signing input)
+    Es256k,
+
+    /// Ed25519 (the only "EdDSA" curve supported)
+    EdDsa,
+}
+
+impl JwsAlgorithm {
+    /// All JwsAlgorithm's
+    pub fn all() -> [Self; 3] {
+        [Self::Es256, Self::Es256k, Self::EdDsa]
+    }
+
+    /// The JwsAlgorithm with the given "alg" (as formatted by Display)
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().iter().copied().find(|algorithm| algorithm.to_string() == name)
+    }
+
+    /// The KeyType used to verify signatures
+    pub fn key_type(&self) -> KeyType {
+        match self {
+            Self::Es256 => KeyType::P256,
+            Self::Es256k => KeyType::Secp256k1,
+            Self::EdDsa => KeyType::Ed25519,
+        }
+    }
+}
+
+impl Display for JwsAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Self::Es256 => write!(f, "ES256"),
+            Self::Es256k => write!(f, "ES256K"),
+            Self::EdDsa => write!(f, "EdDSA"),
+        }
+    }
+}
+
+/// A compact-serialized JWS (RFC 7515), i.e. "header.payload.signature",
+/// where the header and payload are JSON Objects (as in a JWT)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Jws {
+    /// The decoded protected header
+    pub header: Map<String, Value>,
+
+    /// The decoded payload, e.g. JWT claims
+    pub payload: Map<String, Value>,
+
+    /// The signature
+    pub signature: Vec<u8>,
+
+    /// "header.payload", exactly as it was signed
+    pub signing_input: String,
+}
+
+impl Jws {
+    /// Split and base64url-decode a compact JWS
+    pub fn parse(jws: &str) -> Result<Self, JwsError> {
+        let parts: Vec<&str> = jws.split('.').collect();
+        if parts.len() != 3 {
+            return Err(JwsError::NotCompact {
+                parts: parts.len(),
+            })
+        }
+        Ok(Jws {
+            header: decode_object("header", parts[0])?,
+            payload: decode_object("payload", parts[1])?,
+            signature: decode_base64url("signature", parts[2])?,
+            signing_input: format!("{}.{}", parts[0], parts[1]),
+        })
+    }
+
+    /// The header's "alg"
+    pub fn algorithm(&self) -> Result<JwsAlgorithm, JwsError> {
+        let alg = self.header.get("alg").cloned().unwrap_or(Value::Null);
+        alg.as_str()
+            .and_then(JwsAlgorithm::from_name)
+            .ok_or(JwsError::UnsupportedAlgorithm {
+                alg: alg,
+            })
+    }
+
+    /// Verify the signature with the given JWK, which must match the header's
+    /// "alg" (including the JWK's own "alg", if any).
+    ///
+    /// Fails if the header or JWK is unsupported or malformed, otherwise
+    /// returns whether the signature is valid.
+    pub fn verify(&self, jwk: &Map<String, Value>) -> Result<bool, JwsError> {
+        let algorithm = self.algorithm()?;
+        match jwk.get("alg") {
+            None => (),
+            Some(jwk_alg) if jwk_alg.as_str() == Some(&algorithm.to_string()) => (),
+            Some(jwk_alg) => return Err(JwsError::JwkAlgorithmMismatch {
+                algorithm: algorithm,
+                jwk_alg: jwk_alg.clone(),
+            }),
+        }
+        let (key_type, public_key) = jwk_public_key(jwk)?;
+        if key_type != algorithm.key_type() {
+            return Err(JwsError::JwkKeyTypeMismatch {
+                algorithm: algorithm,
+                key_type: key_type,
+            })
+        }
+        // JWS ECDSA signatures are always r || s, never DER
+        if self.signature.len() != 64 {
+            return Err(JwsError::SignatureLength {
+                algorithm: algorithm,
+                length: self.signature.len(),
+            })
+        }
+        Ok(key_type.verify(&public_key, &self.signature, self.signing_input.as_bytes())?)
+    }
+}
+
+/// The KeyType and public key (as accepted by KeyType::verify) of a JWK
+/// (RFC 7517): an EC key on P-256 or secp256k1, or an OKP Ed25519 key
+pub fn jwk_public_key(jwk: &Map<String, Value>) -> Result<(KeyType, Vec<u8>), JwsError> {
+    let member = |name: &'static str| {
+        jwk.get(name).and_then(|value| value.as_str())
+            .ok_or_else(|| JwsError::JwkMissingMember {
+                member: name.to_string(),
+                jwk: jwk.clone(),
+            })
+    };
+    match (member("kty")?, member("crv")?) {
+        ("EC", crv @ "P-256") | ("EC", crv @ "secp256k1") => {
+            let key_type = if crv == "P-256" { KeyType::P256 } else { KeyType::Secp256k1 };
+            let x = decode_base64url("x", member("x")?)?;
+            let y = decode_base64url("y", member("y")?)?;
+            if x.len() != 32 || y.len() != 32 {
+                return Err(JwsError::JwkCoordinateLength {
+                    jwk: jwk.clone(),
+                })
+            }
+            Ok((key_type, [&[0x04], x.as_slice(), &y].concat()))
+        },
+        ("OKP", "Ed25519") => Ok((KeyType::Ed25519, decode_base64url("x", member("x")?)?)),
+        (kty, crv) => Err(JwsError::UnsupportedJwk {
+            kty: kty.to_string(),
+            crv: crv.to_string(),
+        }),
+    }
+}
+
+/// A NumericDate claim, e.g. "exp" or "nbf" (RFC 7519), in seconds since
+/// the epoch
+pub fn numeric_date(claims: &Map<String, Value>, name: &str) -> Result<Number, JwsError> {
+    match claims.get(name) {
+        Some(Value::Number(date)) => Ok(date.clone()),
+        claim => Err(JwsError::NotNumericDate {
+            name: name.to_string(),
+            claim: claim.cloned(),
+        }),
+    }
+}
+
+fn decode_base64url(part: &'static str, encoded: &str) -> Result<Vec<u8>, JwsError> {
+    URL_SAFE_NO_PAD.decode(encoded)
+        .map_err(|error| JwsError::Base64Url {
+            part: part,
+            encoded: encoded.to_string(),
+            error: error.to_string(),
+        })
+}
+
+fn decode_object(part: &'static str, encoded: &str) -> Result<Map<String, Value>, JwsError> {
+    let decoded = decode_base64url(part, encoded)?;
+    match serde_json::from_slice(&decoded) {
+        Ok(Value::Object(object)) => Ok(object),
+        _ => Err(JwsError::NotJsonObject {
+            part: part,
+            decoded: String::from_utf8_lossy(&decoded).to_string(),
+        }),
+    }
+}
+
+/// JWS and JWK parsing and verification errors
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum JwsError {
+    /// A compact JWS has exactly three '.'-separated parts
+    #[error("expected a compact JWS (header.payload.signature), but found {parts} part(s)")]
+    NotCompact {
+        /// Number of parts found
+        parts: usize,
+    },
+
+    /// Invalid base64url (without padding)
+    #[error("invalid base64url ({part}): {error}: {encoded}")]
+    Base64Url {
+        /// Which part or JWK member failed to decode
+        part: &'static str,
+        /// The encoded part
+        encoded: String,
+        /// The base64 error
+        error: String,
+    },
+
+    /// The header or payload isn't a JSON Object
+    #[error("JWS {part} is not a JSON Object: {decoded}")]
+    NotJsonObject {
+        /// "header" or "payload"
+        part: &'static str,
+        /// The decoded part
+        decoded: String,
+    },
+
+    /// The header's "alg" is missing or unsupported
+    #[error("unsupported JWS alg, expected ES256, ES256K or EdDSA: {alg}")]
+    UnsupportedAlgorithm {
+        /// The "alg", or null if missing
+        alg: Value,
+    },
+
+    /// The JWK's "alg" differs from the header's
+    #[error("JWS alg is {algorithm}, but the JWK's alg is {jwk_alg}")]
+    JwkAlgorithmMismatch {
+        /// The header's "alg"
+        algorithm: JwsAlgorithm,
+        /// The JWK's "alg"
+        jwk_alg: Value,
+    },
+
+    /// The JWK's curve can't be used with the header's "alg"
+    #[error("JWS alg is {algorithm}, but the JWK is a {key_type} key")]
+    JwkKeyTypeMismatch {
+        /// The header's "alg"
+        algorithm: JwsAlgorithm,
+        /// The JWK's KeyType
+        key_type: KeyType,
+    },
+
+    /// A required JWK member is missing or not a String
+    #[error("JWK member {member:?} is missing or not a String: {jwk:?}")]
+    JwkMissingMember {
+        /// The member's name
+        member: String,
+        /// The JWK
+        jwk: Map<String, Value>,
+    },
+
+    /// The JWK's kty and crv are unsupported
+    #[error("unsupported JWK, expected an EC P-256 or secp256k1 key, or an OKP Ed25519 key: kty: {kty}, crv: {crv}")]
+    UnsupportedJwk {
+        /// The JWK's "kty"
+        kty: String,
+        /// The JWK's "crv"
+        crv: String,
+    },
+
+    /// An EC JWK's x or y isn't 32 bytes
+    #[error("EC JWK coordinates must be 32 bytes: {jwk:?}")]
+    JwkCoordinateLength {
+        /// The JWK
+        jwk: Map<String, Value>,
+    },
+
+    /// JWS signatures are 64 bytes for all supported algorithms
+    #[error("{algorithm} JWS signatures are 64 bytes, but found {length}")]
+    SignatureLength {
+        /// The header's "alg"
+        algorithm: JwsAlgorithm,
+        /// The signature's length
+        length: usize,
+    },
+
+    /// The JWK's public key is malformed
+    #[error("Jws::verify: {0}")]
+    KeyTypeError(#[from] KeyTypeError),
+
+    /// A NumericDate claim is missing or not a Number
+    #[error("JWT claim {name:?} is missing or not a NumericDate: {claim:?}")]
+    NotNumericDate {
+        /// The claim's name
+        name: String,
+        /// The claim, if present
+        claim: Option<Value>,
+    },
+}
@@ -0,0 +1,189 @@
+use crate::typed_instructions_crypto::BLS12_381_DST;
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use k256::ecdsa::signature::Verifier;
+use quickcheck::{Arbitrary, Gen};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// A supported signature scheme
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum KeyType {
+    /// ECDSA over secp256k1 of SHA-256(message)
+    Secp256k1,
+
+    /// ECDSA over P-256 (secp256r1) of SHA-256(message)
+    P256,
+
+    /// Ed25519 (RFC 8032), rejecting small-order public keys and R values
+    /// (as in ed25519_dalek's verify_strict)
+    Ed25519,
+
+    /// BLS over BLS12-381, see BLS12_381_DST
+    Bls12381,
+}
+
+impl KeyType {
+    /// All KeyType's
+    pub fn all() -> [Self; 4] {
+        [Self::Secp256k1, Self::P256, Self::Ed25519, Self::Bls12381]
+    }
+
+    /// The KeyType with the given name (as formatted by Display)
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().iter().copied().find(|key_type| key_type.to_string() == name)
+    }
+
+    /// The multicodec code of the KeyType's public keys, e.g. as used by
+    /// did:key
+    pub fn multicodec_code(&self) -> u64 {
+        match self {
+            Self::Secp256k1 => 0xe7,
+            Self::P256 => 0x1200,
+            Self::Ed25519 => 0xed,
+            Self::Bls12381 => 0xea,
+        }
+    }
+
+    /// The KeyType with the given public key multicodec code
+    pub fn from_multicodec_code(code: u64) -> Option<Self> {
+        Self::all().iter().copied().find(|key_type| key_type.multicodec_code() == code)
+    }
+
+    /// Expected encoding of public keys
+    pub fn public_key_format(&self) -> &'static str {
+        match self {
+            Self::Secp256k1 => "a SEC1 point (33 or 65 bytes)",
+            Self::P256 => "a SEC1 point (33 or 65 bytes)",
+            Self::Ed25519 => "a 32-byte point",
+            Self::Bls12381 => "a compressed G1 point (48 bytes)",
+        }
+    }
+
+    /// Expected encoding of signatures
+    pub fn signature_format(&self) -> &'static str {
+        match self {
+            Self::Secp256k1 => "64 bytes (r || s) or DER",
+            Self::P256 => "64 bytes (r || s) or DER",
+            Self::Ed25519 => "64 bytes (R || S)",
+            Self::Bls12381 => "a compressed G2 point (96 bytes)",
+        }
+    }
+
+    /// Verify a signature of the message.
+    ///
+    /// Fails if public_key or signature is malformed, otherwise returns
+    /// whether the signature is valid. High-S secp256k1 signatures are
+    /// accepted, and Ed25519 signatures are verified strictly.
+    pub fn verify(&self, public_key: &[u8], signature: &[u8], message: &[u8]) -> Result<bool, KeyTypeError> {
+        let malformed_public_key = || KeyTypeError::MalformedPublicKey {
+            key_type: *self,
+            public_key: public_key.to_vec(),
+        };
+        let malformed_signature = || KeyTypeError::MalformedSignature {
+            key_type: *self,
+            signature: signature.to_vec(),
+        };
+        match self {
+            Self::Secp256k1 => {
+                let verifying_key = k256::ecdsa::VerifyingKey::from_sec1_bytes(public_key)
+                    .map_err(|_| malformed_public_key())?;
+                let parsed_signature = if signature.len() == 64 {
+                    k256::ecdsa::Signature::try_from(signature)
+                } else {
+                    k256::ecdsa::Signature::from_der(signature)
+                }.map_err(|_| malformed_signature())?;
+                // k256 only accepts low-S signatures (BIP 62)
+                let normalized_signature = parsed_signature.normalize_s().unwrap_or(parsed_signature);
+                Ok(verifying_key.verify(message, &normalized_signature).is_ok())
+            },
+            Self::P256 => {
+                let verifying_key = p256::ecdsa::VerifyingKey::from_sec1_bytes(public_key)
+                    .map_err(|_| malformed_public_key())?;
+                let parsed_signature = if signature.len() == 64 {
+                    p256::ecdsa::Signature::try_from(signature)
+                } else {
+                    p256::ecdsa::Signature::from_der(signature)
+                }.map_err(|_| malformed_signature())?;
+                Ok(verifying_key.verify(message, &parsed_signature).is_ok())
+            },
+            Self::Ed25519 => {
+                let parsed_public_key = ed25519_dalek::PublicKey::from_bytes(public_key)
+                    .map_err(|_| malformed_public_key())?;
+                let parsed_signature = ed25519_dalek::Signature::try_from(signature)
+                    .map_err(|_| malformed_signature())?;
+                Ok(parsed_public_key.verify_strict(message, &parsed_signature).is_ok())
+            },
+            Self::Bls12381 => {
+                let parsed_public_key = bls12_381_public_key(public_key)?;
+                let parsed_signature = bls12_381_signature(signature)?;
+                let result = parsed_signature.verify(false, message, BLS12_381_DST, &[], &parsed_public_key, false);
+                Ok(result == blst::BLST_ERROR::BLST_SUCCESS)
+            },
+        }
+    }
+}
+
+/// Parse a compressed BLS12-381 G1 point, failing if it's the identity or
+/// isn't in the prime-order subgroup
+pub(crate) fn bls12_381_public_key(public_key: &[u8]) -> Result<blst::min_pk::PublicKey, KeyTypeError> {
+    blst::min_pk::PublicKey::key_validate(public_key)
+        .map_err(|_| KeyTypeError::MalformedPublicKey {
+            key_type: KeyType::Bls12381,
+            public_key: public_key.to_vec(),
+        })
+}
+
+/// Parse a compressed BLS12-381 G2 point, failing if it isn't in the
+/// prime-order subgroup
+pub(crate) fn bls12_381_signature(signature: &[u8]) -> Result<blst::min_pk::Signature, KeyTypeError> {
+    blst::min_pk::Signature::sig_validate(signature, true)
+        .map_err(|_| KeyTypeError::MalformedSignature {
+            key_type: KeyType::Bls12381,
+            signature: signature.to_vec(),
+        })
+}
+
+// Formatting uses the names accepted by from_name, e.g. "secp256k1"
+impl Display for KeyType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Self::Secp256k1 => write!(f, "secp256k1"),
+            Self::P256 => write!(f, "p256"),
+            Self::Ed25519 => write!(f, "ed25519"),
+            Self::Bls12381 => write!(f, "bls12_381"),
+        }
+    }
+}
+
+impl Arbitrary for KeyType {
+    fn arbitrary(g: &mut Gen) -> Self {
+        *g.choose(&Self::all()).unwrap_or(&Self::Secp256k1)
+    }
+}
+
+/// Error from KeyType::verify, i.e. a malformed input rather than an invalid
+/// signature
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum KeyTypeError {
+    /// The public key isn't valid for the KeyType
+    #[error("malformed {key_type} public key, expected {}: 0x{}", .key_type.public_key_format(), hex::encode(.public_key))]
+    MalformedPublicKey {
+        /// The KeyType
+        key_type: KeyType,
+        /// The malformed public key
+        public_key: Vec<u8>,
+    },
+
+    /// The signature isn't valid for the KeyType
+    #[error("malformed {key_type} signature, expected {}: 0x{}", .key_type.signature_format(), hex::encode(.signature))]
+    MalformedSignature {
+        /// The KeyType
+        key_type: KeyType,
+        /// The malformed signature
+        signature: Vec<u8>,
+    },
+}
@@ -0,0 +1,593 @@
+use crate::parse::ParseError;
+
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use base64::Engine;
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use serde::{Deserialize, Serialize};
+use serde_json::{Deserializer, Value};
+
+/// A range of source code, with the line and column of its start
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Span {
+    /// Byte offset of the start of the span (inclusive)
+    pub start: usize,
+
+    /// Byte offset of the end of the span (exclusive)
+    pub end: usize,
+
+    /// Line number of the start of the span, 1-indexed
+    pub line: usize,
+
+    /// Column (in characters) of the start of the span, 1-indexed
+    pub column: usize,
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+impl Span {
+    /// Extend self to the end of the given Span
+    pub fn to(&self, other: Self) -> Self {
+        Span {
+            start: self.start,
+            end: other.end,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    /// The empty Span at the given 1-indexed line and column, where the column
+    /// is counted in bytes (as serde_json does)
+    pub fn at_line_column(source: &str, line: usize, column: usize) -> Self {
+        let line = line.max(1);
+        let line_start = source
+            .split_inclusive('\n')
+            .take(line - 1)
+            .map(|line_str| line_str.len())
+            .sum::<usize>();
+        let line_str = source[line_start..].lines().next().unwrap_or("");
+        let byte_column = column.max(1).min(line_str.len() + 1) - 1;
+        let start = line_start + byte_column;
+        Span {
+            start: start,
+            end: start,
+            line: line,
+            column: line_str.get(..byte_column).map_or(byte_column, |prefix| prefix.chars().count()) + 1,
+        }
+    }
+
+    /// Render the line of source containing the start of self, with carets
+    /// under the spanned characters (only the first line of multi-line spans
+    /// is underlined):
+    ///
+    /// ```text
+    ///   |
+    /// 2 |     hash_md5;
+    ///   |     ^^^^^^^^
+    /// ```
+    pub fn snippet(&self, source: &str) -> String {
+        let line_str = source.lines().nth(self.line.saturating_sub(1)).unwrap_or("");
+        let line_no_str = self.line.to_string();
+        let gutter = " ".repeat(line_no_str.len());
+        let prefix_len = line_str.chars().take(self.column.saturating_sub(1)).count();
+        let span_len = source
+            .get(self.start..self.end)
+            .map(|spanned| spanned.chars().take_while(|&c| c != '\n').count())
+            .unwrap_or(0);
+        let caret_len = span_len
+            .min(line_str.chars().count().saturating_sub(prefix_len))
+            .max(1);
+        format!("{gutter} |\n{line_no} | {line_str}\n{gutter} | {padding}{carets}",
+                gutter = gutter,
+                line_no = line_no_str,
+                line_str = line_str,
+                padding = " ".repeat(prefix_len),
+                carets = "^".repeat(caret_len))
+    }
+}
+
+/// Kinds of tokens produced by the Lexer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    /// An identifier, e.g. an instruction mnemonic
+    Ident(String),
+
+    /// A decimal number, e.g. 12, -3 or 1.5e-7
+    Number(String),
+
+    /// The decoded contents of a "..." literal
+    String(String),
+
+    /// The decoded contents of a b"..." or base64"..." literal
+    Bytes(Vec<u8>),
+
+    /// The digits of a 0x... literal
+    Hex(String),
+
+    /// ;
+    Semicolon,
+
+    /// ,
+    Comma,
+
+    /// =
+    Equals,
+
+    /// .
+    Dot,
+
+    /// <
+    LeftAngle,
+
+    /// >
+    RightAngle,
+
+    /// [
+    LeftBracket,
+
+    /// ]
+    RightBracket,
+
+    /// {
+    LeftBrace,
+
+    /// }
+    RightBrace,
+
+    /// (
+    LeftParen,
+
+    /// )
+    RightParen,
+
+    /// The text of a "///" doc comment, without the leading "///" and at
+    /// most one space
+    DocComment(String),
+
+    /// End of the input
+    Eof,
+}
+
+impl Display for TokenKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Self::Ident(x) => write!(f, "{}", x),
+            Self::Number(x) => write!(f, "{}", x),
+            Self::String(x) => write!(f, "{}", Value::String(x.clone())),
+            Self::Bytes(x) => write!(f, "0x{}", hex::encode(x)),
+            Self::Hex(x) => write!(f, "0x{}", x),
+            Self::Semicolon => write!(f, ";"),
+            Self::Comma => write!(f, ","),
+            Self::Equals => write!(f, "="),
+            Self::Dot => write!(f, "."),
+            Self::LeftAngle => write!(f, "<"),
+            Self::RightAngle => write!(f, ">"),
+            Self::LeftBracket => write!(f, "["),
+            Self::RightBracket => write!(f, "]"),
+            Self::LeftBrace => write!(f, "{{"),
+            Self::RightBrace => write!(f, "}}"),
+            Self::LeftParen => write!(f, "("),
+            Self::RightParen => write!(f, ")"),
+            Self::DocComment(x) => write!(f, "///{}", x),
+            Self::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+/// A TokenKind and its location in the source
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    /// The kind of token
+    pub kind: TokenKind,
+
+    /// Where the token is in the source
+    pub span: Span,
+}
+
+/// Splits source code into Token's on demand, tracking byte offsets, lines
+/// and columns
+#[derive(Clone, Debug)]
+pub(crate) struct Lexer<'a> {
+    source: &'a str,
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Lexer<'a> {
+    /// A new Lexer, positioned at the start of the source
+    pub(crate) fn new(source: &'a str) -> Self {
+        Lexer {
+            source: source,
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.source[self.offset..].chars().next()
+    }
+
+    fn peek_nth_char(&self, n: usize) -> Option<char> {
+        self.source[self.offset..].chars().nth(n)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek_char()?;
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn bump_while(&mut self, predicate: impl Fn(char) -> bool) {
+        while self.peek_char().is_some_and(&predicate) {
+            self.bump();
+        }
+    }
+
+    /// The empty Span at the current position
+    fn here(&self) -> Span {
+        Span {
+            start: self.offset,
+            end: self.offset,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    /// Extend the given Span to the current position
+    fn since(&self, start: Span) -> Span {
+        Span {
+            end: self.offset,
+            ..start
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.bump_while(char::is_whitespace)
+    }
+
+    /// Skip whitespace, "//" line comments and "/* */" block comments, but
+    /// not "///" doc comments
+    fn skip_whitespace_and_comments(&mut self) -> Result<(), ParseError> {
+        loop {
+            self.skip_whitespace();
+            let rest = &self.source[self.offset..];
+            if rest.starts_with("//") && !is_doc_comment(rest) {
+                self.bump_while(|c| c != '\n');
+            } else if rest.starts_with("/*") {
+                let start = self.here();
+                self.bump();
+                self.bump();
+                while !self.source[self.offset..].starts_with("*/") {
+                    if self.bump().is_none() {
+                        return Err(ParseError::UnterminatedComment { span: self.since(start) });
+                    }
+                }
+                self.bump();
+                self.bump();
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Reposition the Lexer at the start of the given Span, e.g. to re-lex a
+    /// Token that has already been consumed
+    pub(crate) fn seek(&mut self, span: Span) {
+        self.offset = span.start;
+        self.line = span.line;
+        self.column = span.column;
+    }
+
+    /// Lex an inline JSON array or object, using serde_json
+    pub(crate) fn lex_json(&mut self) -> Result<(Value, Span), ParseError> {
+        self.skip_whitespace();
+        let start = self.here();
+        let mut stream = Deserializer::from_str(&self.source[self.offset..]).into_iter::<Value>();
+        match stream.next() {
+            Some(Ok(value)) => {
+                let end = start.start + stream.byte_offset();
+                while self.offset < end {
+                    self.bump();
+                }
+                Ok((value, self.since(start)))
+            },
+            Some(Err(error)) => {
+                let relative_span = Span::at_line_column(&self.source[self.offset..], error.line(), error.column());
+                let error_offset = start.start + relative_span.start;
+                Err(ParseError::SerdeJsonError {
+                    error: error,
+                    span: Span {
+                        start: error_offset,
+                        end: error_offset,
+                        line: start.line + relative_span.line - 1,
+                        column: if relative_span.line == 1 {
+                            start.column + relative_span.column - 1
+                        } else {
+                            relative_span.column
+                        },
+                    },
+                })
+            },
+            None => Err(ParseError::UnsupportedElem {
+                elem: TokenKind::Eof.to_string(),
+                span: start,
+            }),
+        }
+    }
+
+    /// Lex the given number of hex digits, returning their value
+    fn lex_hex_digits(&mut self, num_digits: usize, escape_start: Span) -> Result<u32, ParseError> {
+        (0..num_digits).try_fold(0, |acc, _| {
+            self.bump()
+                .and_then(|c| c.to_digit(16))
+                .map(|digit| acc * 16 + digit)
+                .ok_or_else(|| ParseError::InvalidEscape { span: self.since(escape_start) })
+        })
+    }
+
+    /// Lex the rest of a "..." literal (after the opening quote), decoding
+    /// the escapes \n, \r, \t, \0, \b, \f, \\, \", \', \/ and \uXXXX (including
+    /// UTF-16 surrogate pairs).
+    ///
+    /// When allow_byte_escapes is true, \xHH escapes are also allowed
+    fn lex_quoted(&mut self, start: Span, allow_byte_escapes: bool) -> Result<Vec<u8>, ParseError> {
+        let mut contents = vec![];
+        loop {
+            let escape_start = self.here();
+            let c = match self.bump() {
+                None => return Err(ParseError::UnterminatedLiteral { span: self.since(start) }),
+                Some('"') => return Ok(contents),
+                Some('\\') => match self.bump() {
+                    None => return Err(ParseError::UnterminatedLiteral { span: self.since(start) }),
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some('t') => '\t',
+                    Some('0') => '\0',
+                    Some('b') => '\u{8}',
+                    Some('f') => '\u{c}',
+                    Some(c @ ('\\' | '"' | '\'' | '/')) => c,
+                    Some('x') if allow_byte_escapes => {
+                        contents.push(self.lex_hex_digits(2, escape_start)? as u8);
+                        continue
+                    },
+                    Some('u') => {
+                        let mut code_point = self.lex_hex_digits(4, escape_start)?;
+                        if (0xD800..0xDC00).contains(&code_point) && self.peek_char() == Some('\\') && self.peek_nth_char(1) == Some('u') {
+                            self.bump();
+                            self.bump();
+                            let low_surrogate = self.lex_hex_digits(4, escape_start)?;
+                            if (0xDC00..0xE000).contains(&low_surrogate) {
+                                code_point = 0x10000 + ((code_point - 0xD800) << 10) + (low_surrogate - 0xDC00);
+                            }
+                        }
+                        char::from_u32(code_point)
+                            .ok_or_else(|| ParseError::InvalidEscape { span: self.since(escape_start) })?
+                    },
+                    Some(_) => return Err(ParseError::InvalidEscape { span: self.since(escape_start) }),
+                },
+                Some(c) => c,
+            };
+            let mut utf8 = [0u8; 4];
+            contents.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+        }
+    }
+
+    /// Lex the next Token, returning TokenKind::Eof at the end of the input
+    pub(crate) fn next_token(&mut self) -> Result<Token, ParseError> {
+        self.skip_whitespace_and_comments()?;
+        let start = self.here();
+        let c = match self.bump() {
+            None => return Ok(Token { kind: TokenKind::Eof, span: start }),
+            Some(c) => c,
+        };
+        let kind = match c {
+            ';' => TokenKind::Semicolon,
+            ',' => TokenKind::Comma,
+            '=' => TokenKind::Equals,
+            '.' => TokenKind::Dot,
+            '<' => TokenKind::LeftAngle,
+            '>' => TokenKind::RightAngle,
+            '[' => TokenKind::LeftBracket,
+            ']' => TokenKind::RightBracket,
+            '{' => TokenKind::LeftBrace,
+            '}' => TokenKind::RightBrace,
+            '/' if self.source[self.offset..].starts_with("//") => {
+                self.bump();
+                self.bump();
+                self.bump_while(|c| c != '\n');
+                let doc = self.source[start.start + 3..self.offset].trim_end_matches('\r');
+                TokenKind::DocComment(doc.strip_prefix(' ').unwrap_or(doc).to_string())
+            },
+            '(' => TokenKind::LeftParen,
+            ')' => TokenKind::RightParen,
+            '"' => {
+                let contents = self.lex_quoted(start, false)?;
+                TokenKind::String(String::from_utf8(contents)
+                    .map_err(|_| ParseError::InvalidEscape { span: self.since(start) })?)
+            },
+            '0' if self.peek_char() == Some('x') => {
+                self.bump();
+                let digits_start = self.offset;
+                self.bump_while(|c| c.is_ascii_alphanumeric());
+                TokenKind::Hex(self.source[digits_start..self.offset].to_string())
+            },
+            c if c.is_ascii_digit() || (c == '-' && self.peek_char().is_some_and(|c| c.is_ascii_digit())) => {
+                self.bump_while(|c| c.is_ascii_digit());
+                if self.peek_char() == Some('.') && self.peek_nth_char(1).is_some_and(|c| c.is_ascii_digit()) {
+                    self.bump();
+                    self.bump_while(|c| c.is_ascii_digit());
+                }
+                if let Some('e' | 'E') = self.peek_char() {
+                    let exponent_digit = match self.peek_nth_char(1) {
+                        Some('+' | '-') => self.peek_nth_char(2),
+                        exponent_digit => exponent_digit,
+                    };
+                    if exponent_digit.is_some_and(|c| c.is_ascii_digit()) {
+                        self.bump();
+                        self.bump_while(|c| c == '+' || c == '-');
+                        self.bump_while(|c| c.is_ascii_digit());
+                    }
+                }
+                TokenKind::Number(self.source[start.start..self.offset].to_string())
+            },
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                self.bump_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                let ident = &self.source[start.start..self.offset];
+                match (ident, self.peek_char()) {
+                    ("b", Some('"')) => {
+                        self.bump();
+                        TokenKind::Bytes(self.lex_quoted(start, true)?)
+                    },
+                    ("base64", Some('"')) => {
+                        self.bump();
+                        let contents = self.lex_quoted(start, false)?;
+                        TokenKind::Bytes(decode_base64(&contents)
+                            .ok_or_else(|| ParseError::InvalidBase64 { span: self.since(start) })?)
+                    },
+                    _ => TokenKind::Ident(ident.to_string()),
+                }
+            },
+            c => return Err(ParseError::UnexpectedChar { found: c, span: self.since(start) }),
+        };
+        Ok(Token {
+            kind: kind,
+            span: self.since(start),
+        })
+    }
+}
+
+/// Whether the source starts with a "///" doc comment: as in Rust, "////"
+/// (or more) starts an ordinary comment
+fn is_doc_comment(source: &str) -> bool {
+    source.starts_with("///") && !source.starts_with("////")
+}
+
+/// Decode standard or URL-safe base64, with or without padding
+fn decode_base64(contents: &[u8]) -> Option<Vec<u8>> {
+    let config = GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+    GeneralPurpose::new(&alphabet::STANDARD, config).decode(contents)
+        .or_else(|_| GeneralPurpose::new(&alphabet::URL_SAFE, config).decode(contents))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(source: &str) -> Result<Vec<Token>, ParseError> {
+        let mut lexer = Lexer::new(source);
+        let mut tokens = vec![];
+        loop {
+            let token = lexer.next_token()?;
+            if token.kind == TokenKind::Eof {
+                return Ok(tokens);
+            }
+            tokens.push(token);
+        }
+    }
+
+    #[test]
+    fn test_lex_spans() {
+        let tokens = lex("push b\"a;b\";\n  dig 12;").expect("failed to lex");
+        assert_eq!(vec![
+            Token { kind: TokenKind::Ident("push".to_string()), span: Span { start: 0, end: 4, line: 1, column: 1 } },
+            Token { kind: TokenKind::Bytes(b"a;b".to_vec()), span: Span { start: 5, end: 11, line: 1, column: 6 } },
+            Token { kind: TokenKind::Semicolon, span: Span { start: 11, end: 12, line: 1, column: 12 } },
+            Token { kind: TokenKind::Ident("dig".to_string()), span: Span { start: 15, end: 18, line: 2, column: 3 } },
+            Token { kind: TokenKind::Number("12".to_string()), span: Span { start: 19, end: 21, line: 2, column: 7 } },
+            Token { kind: TokenKind::Semicolon, span: Span { start: 21, end: 22, line: 2, column: 9 } },
+        ], tokens);
+    }
+
+    #[test]
+    fn test_lex_literals() {
+        let kinds = lex(r#"b"\x00\"\\" "caf\u00e9 \ud83d\ude00\n" base64"SGk=" base64"_-8" -12 1.5e-7 3E2 ()"#)
+            .expect("failed to lex")
+            .into_iter()
+            .map(|token| token.kind)
+            .collect::<Vec<TokenKind>>();
+        assert_eq!(vec![
+            TokenKind::Bytes(b"\x00\"\\".to_vec()),
+            TokenKind::String("caf\u{e9} \u{1f600}\n".to_string()),
+            TokenKind::Bytes(b"Hi".to_vec()),
+            TokenKind::Bytes(vec![0xff, 0xef]),
+            TokenKind::Number("-12".to_string()),
+            TokenKind::Number("1.5e-7".to_string()),
+            TokenKind::Number("3E2".to_string()),
+            TokenKind::LeftParen,
+            TokenKind::RightParen,
+        ], kinds);
+    }
+
+    #[test]
+    fn test_lex_comments() {
+        let kinds = lex("// comment\n/// doc\n//// comment\n/* block */ dup")
+            .expect("failed to lex")
+            .into_iter()
+            .map(|token| token.kind)
+            .collect::<Vec<TokenKind>>();
+        assert_eq!(vec![TokenKind::DocComment("doc".to_string()), TokenKind::Ident("dup".to_string())], kinds);
+    }
+
+    #[test]
+    fn test_lex_json() {
+        let mut lexer = Lexer::new("push\n [1, {\"a;\": null}];");
+        lexer.next_token().expect("failed to lex push");
+        let (value, span) = lexer.lex_json().expect("failed to lex JSON");
+        assert_eq!(serde_json::json!([1, {"a;": null}]), value);
+        assert_eq!(Span { start: 6, end: 23, line: 2, column: 2 }, span);
+        assert_eq!(TokenKind::Semicolon, lexer.next_token().expect("failed to lex ;").kind);
+    }
+
+    #[test]
+    fn test_lex_path() {
+        let kinds = lex("vc.credentialSubject.roles[0]<str>")
+            .expect("failed to lex")
+            .into_iter()
+            .map(|token| token.kind)
+            .collect::<Vec<TokenKind>>();
+        assert_eq!(vec![
+            TokenKind::Ident("vc".to_string()),
+            TokenKind::Dot,
+            TokenKind::Ident("credentialSubject".to_string()),
+            TokenKind::Dot,
+            TokenKind::Ident("roles".to_string()),
+            TokenKind::LeftBracket,
+            TokenKind::Number("0".to_string()),
+            TokenKind::RightBracket,
+            TokenKind::LeftAngle,
+            TokenKind::Ident("str".to_string()),
+            TokenKind::RightAngle,
+        ], kinds);
+    }
+
+    #[test]
+    fn test_lex_errors() {
+        assert!(matches!(lex(r#""\q""#), Err(ParseError::InvalidEscape { .. })));
+        assert!(matches!(lex(r#""\x41""#), Err(ParseError::InvalidEscape { .. })));
+        assert!(matches!(lex(r#"base64"!!""#), Err(ParseError::InvalidBase64 { .. })));
+        assert!(matches!(lex("push b\"abc"), Err(ParseError::UnterminatedLiteral { span: Span { start: 5, .. } })));
+        assert!(matches!(lex("\n  $"), Err(ParseError::UnexpectedChar { found: '$', span: Span { line: 2, column: 3, .. } })));
+    }
+
+    #[test]
+    fn test_snippet() {
+        let source = "push b\"x\";\n    hash_md5;\n";
+        let span = Span { start: 15, end: 23, line: 2, column: 5 };
+        assert_eq!("  |\n2 |     hash_md5;\n  |     ^^^^^^^^", span.snippet(source));
+    }
+}
@@ -65,6 +65,18 @@ mod json_template;
 pub use json_template::{TMap, TValue, TValueRunError, Template};
 mod query;
 pub use query::{QueryTemplate, QueryTemplates, Query, QueryType, QueryError};
+mod hash_function;
+pub use hash_function::{HashFunction, DigestLengthError};
+mod key_type;
+pub use key_type::{KeyType, KeyTypeError};
+mod jws;
+pub use jws::{jwk_public_key, numeric_date, Jws, JwsAlgorithm, JwsError};
+mod merkle;
+pub use merkle::MerklePairing;
+mod siwe;
+pub use siwe::{parse_siwe, SiweError};
+mod multiformats;
+pub use multiformats::{decode_multibase, read_varint, split_multicodec, parse_multihash, MultiformatsError};
 mod untyped_instruction;
 pub use untyped_instruction::Instruction;
 mod untyped_instructions;
@@ -72,14 +84,45 @@ pub use untyped_instructions::Instructions;
 mod typed_instruction;
 pub use typed_instruction::IsInstructionT;
 mod typed_instructions;
-pub use typed_instructions::{AssertTrue, Concat, Push, Lookup, UnpackJson, Index, CheckEq, BytesEq, StringEq, CheckLe, CheckLt, StringToBytes, ToJson, Slice, HashSha256};
+pub use typed_instructions::{AssertTrue, Concat, Push, Lookup, UnpackJson, Index, CheckEq, BytesEq, StringEq, CheckLe, CheckLt, StringToBytes, ToJson, Slice, HashSha256,
+    AssertEq, AssertNe, AssertLt, AssertLe, AssertGt, AssertGe, AssertMember, AssertCmpError, AssertMemberError};
+mod typed_instructions_crypto;
+pub use typed_instructions_crypto::{VerifySecp256k1, VerifySecp256k1Error, EcRecover, EcRecoverError, HashEip191, VerifyEd25519, VerifyEd25519Error,
+    VerifyP256, VerifyP256Error, WebAuthnPayload, HashSha3_256, HashKeccak256, HashSha512, HashBlake2b256, HashBlake3,
+    HashRipemd160, HmacSha256, ConstantTimeBytesEq,
+    VerifyMerkleProof, VerifyMerkleProofError, BLS12_381_DST, VerifyBls12381, FastAggregateVerifyBls12381,
+    VerifyBls12381Error, CheckMultiSig, CheckMultiSigError};
+mod typed_instructions_siwe;
+pub use typed_instructions_siwe::ParseSiwe;
+mod typed_instructions_encoding;
+pub use typed_instructions_encoding::{DecodeMultibase, MulticodecCode, StripMulticodec, VerifyMultihash};
+mod typed_instructions_jws;
+pub use typed_instructions_jws::{JwsHeader, JwsPayload, JwsSignature, VerifyJws, JwtExp, JwtNbf};
+mod typed_instructions_vc;
+pub use typed_instructions_vc::{ValidateVc, ValidateVp, VerifyJwtVc, VerifyJwtVp, VpSigner};
+mod typed_instructions_did;
+pub use typed_instructions_did::{ResolveDid, CheckDidDocument, VerificationMethod};
 mod typed_instr;
 pub use typed_instr::Instr;
 mod typed_instrs;
 pub use typed_instrs::Instrs;
+mod lexer;
+pub use lexer::{Span, Token, TokenKind};
+mod define;
+mod scope;
 mod parse;
-pub use parse::{parse, parse_json};
-
+pub use parse::{parse, parse_json, parse_program, ParseError};
+mod program;
+pub use program::{Program, ProgramError};
+mod did;
+pub use did::{check_did_document, did_web_query_template, did_web_url, resolve_did, resolve_did_key, resolve_did_pkh,
+    select_verification_method, relationship_verification_methods, DidError, DID_CONTEXT_V1, MULTIKEY_CONTEXT_V1};
+mod vc;
+pub use vc::{jwt_vc_credential, jwt_vp_presentation, presentation_signer, validate_credential, validate_presentation, verify_jwt_signer, VcError,
+    CREDENTIALS_CONTEXT_V1, CREDENTIALS_CONTEXT_V2};
+
+mod ucan;
+pub use ucan::{ucan_cid, Capability, Ucan, UcanError, CRYPTOSCRIPT_CAVEAT};
 mod rest_api;
 pub use rest_api::Api;
 mod cli;
@@ -66,18 +66,7 @@ async fn main() {
               "action": "siwe",
               "version": "1.1.0",
               "data": {
-                "message": "service.org wants you to sign in with your Ethereum account:\n0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2\n\nI accept the ServiceOrg Terms of Service: https://service.org/tos\n\nURI: https://service.org/login\nVersion: 1\nChain ID: 1\nNonce: 32891757\nIssued At: 2021-09-30T16:25:24.000Z\nResources:\n- ipfs://Qme7ss3ARVgxv6rXqVPiikMJ8u2NLgmgszg13pYrDKEoiu\n- https://example.com/my-web2-claim.json",
-                "fields": {
-                    "domain": "service.org",
-                    "address": "0xe04f27eb70e025b78871a2ad7eabe85e61212761",
-                    "statement": "I accept the ServiceOrg Terms of Service: https://service.org/tos",
-                    "uri": "https://service.org/login",
-                    "version": "1",
-                    "chainId": 1,
-                    "nonce": "32891757",
-                    "issuedAt": "2021-09-30T16:25:24.000Z",
-                    "resources": ["ipfs://Qme7ss3ARVgxv6rXqVPiikMJ8u2NLgmgszg13pYrDKEoiu", "https://example.com/my-web2-claim.json"]
-                }
+                "message": "service.org wants you to sign in with your Ethereum account:\n0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2\n\nI accept the ServiceOrg Terms of Service: https://service.org/tos\n\nURI: https://service.org/login\nVersion: 1\nChain ID: 1\nNonce: 32891757\nIssued At: 2021-09-30T16:25:24.000Z\nResources:\n- ipfs://Qme7ss3ARVgxv6rXqVPiikMJ8u2NLgmgszg13pYrDKEoiu\n- https://example.com/my-web2-claim.json"
               }
             }
           ]
@@ -170,18 +159,19 @@ async fn main() {
         Instruction::AssertTrue,
         Instruction::Restack(Restack::drop()),
 
-        // x["data"]["fields"]["address"] = "0xe04f27eb70e025b78871a2ad7eabe85e61212761"
+        // parse_siwe(x["data"]["message"])["address"] = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"
         Instruction::Restack(Restack::dup()),
         Instruction::Push(Elem::String("data".to_string())),
         Instruction::Lookup,
         Instruction::UnpackJson(ElemSymbol::Object),
-        Instruction::Push(Elem::String("fields".to_string())),
+        Instruction::Push(Elem::String("message".to_string())),
         Instruction::Lookup,
-        Instruction::UnpackJson(ElemSymbol::Object),
+        Instruction::UnpackJson(ElemSymbol::String),
+        Instruction::ParseSiwe,
         Instruction::Push(Elem::String("address".to_string())),
         Instruction::Lookup,
         Instruction::UnpackJson(ElemSymbol::String),
-        Instruction::Push(Elem::String("0xe04f27eb70e025b78871a2ad7eabe85e61212761".to_string())),
+        Instruction::Push(Elem::String("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2".to_string())),
         Instruction::StringEq,
         Instruction::AssertTrue,
         Instruction::Restack(Restack::drop()),
@@ -197,30 +187,32 @@ async fn main() {
         Instruction::StringToBytes,
         Instruction::HashSha256,
 
-        // sha256(x["data"]["fields"]["address"])
+        // sha256(parse_siwe(x["data"]["message"])["address"])
         Instruction::Restack(Restack::swap()),
         Instruction::Push(Elem::String("data".to_string())),
         Instruction::Lookup,
         Instruction::UnpackJson(ElemSymbol::Object),
-        Instruction::Push(Elem::String("fields".to_string())),
+        Instruction::Push(Elem::String("message".to_string())),
         Instruction::Lookup,
-        Instruction::UnpackJson(ElemSymbol::Object),
+        Instruction::UnpackJson(ElemSymbol::String),
+        Instruction::ParseSiwe,
         Instruction::Push(Elem::String("address".to_string())),
         Instruction::Lookup,
         Instruction::UnpackJson(ElemSymbol::String),
         Instruction::StringToBytes,
         Instruction::HashSha256,
 
-        // sha256(sha256(x["data"]["message"]) ++ sha256(x["data"]["fields"]["address"])) =
-        //  [53,163,178,139,122,187,171,47,42,135,175,176,240,11,10,152,228,238,106,205,132,68,80,79,188,54,124,242,97,132,31,139]
+        // sha256(sha256(parse_siwe(x["data"]["message"])["address"]) ++ sha256(x["data"]["message"])) =
+        //  [194,151,214,44,199,228,71,84,126,59,26,15,134,220,3,164,189,137,220,66,54,247,228,217,197,63,107,220,118,244,189,45]
         Instruction::Concat,
         Instruction::HashSha256,
-        Instruction::Push(Elem::Bytes(vec![53,163,178,139,122,187,171,47,42,135,175,176,240,11,10,152,228,238,106,205,132,68,80,79,188,54,124,242,97,132,31,139])),
+        Instruction::Push(Elem::Bytes(vec![194,151,214,44,199,228,71,84,126,59,26,15,134,220,3,164,189,137,220,66,54,247,228,217,197,63,107,220,118,244,189,45])),
         Instruction::BytesEq,
         Instruction::AssertTrue,
         Instruction::Restack(Restack::drop()),
     ];
     let instructions = Instructions {
+        docs: vec![],
         instructions: instructions_vec,
     };
 
@@ -0,0 +1,69 @@
+use crate::hash_function::HashFunction;
+
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use quickcheck::{Arbitrary, Gen};
+use serde::{Deserialize, Serialize};
+
+/// How a Merkle tree hashes each node with its sibling
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum MerklePairing {
+    /// hash(min(node, sibling) ++ max(node, sibling)), ordering by bytes, as
+    /// in OpenZeppelin's MerkleProof, so that no index is needed
+    Sorted,
+
+    /// hash(left ++ right), where the bits of the index (least significant
+    /// first) give whether each node is the right child of its parent
+    Positional,
+}
+
+impl MerklePairing {
+    /// All MerklePairing's
+    pub fn all() -> [Self; 2] {
+        [Self::Sorted, Self::Positional]
+    }
+
+    /// The MerklePairing with the given name (as formatted by Display)
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().iter().copied().find(|pairing| pairing.to_string() == name)
+    }
+
+    /// Compute the Merkle root from a leaf hash and its sibling hashes, from
+    /// the bottom of the tree up. The index is ignored when Sorted.
+    ///
+    /// There's no domain separation between leaves and inner nodes, and
+    /// lengths aren't checked: with no siblings the root is the leaf itself,
+    /// and an inner node gives the same root as a leaf with a shorter proof.
+    pub fn root(&self, hash_function: HashFunction, leaf: &[u8], siblings: &[Vec<u8>], index: u64) -> Vec<u8> {
+        let mut node = leaf.to_vec();
+        for (depth, sibling) in siblings.iter().enumerate() {
+            let is_right = match self {
+                Self::Sorted => *sibling < node,
+                Self::Positional => index.checked_shr(depth as u32).unwrap_or(0) & 1 == 1,
+            };
+            let pair = if is_right {
+                [sibling.as_slice(), &node].concat()
+            } else {
+                [node.as_slice(), sibling].concat()
+            };
+            node = hash_function.digest(&pair);
+        }
+        node
+    }
+}
+
+impl Display for MerklePairing {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Self::Sorted => write!(f, "sorted"),
+            Self::Positional => write!(f, "positional"),
+        }
+    }
+}
+
+impl Arbitrary for MerklePairing {
+    fn arbitrary(g: &mut Gen) -> Self {
+        *g.choose(&Self::all()).unwrap_or(&Self::Sorted)
+    }
+}
@@ -0,0 +1,149 @@
+use crate::hash_function::HashFunction;
+
+use serde_json::Number;
+use thiserror::Error;
+
+/// Decode a multibase String, i.e. a base prefix (e.g. "z" for base58btc)
+/// followed by the encoded bytes
+pub fn decode_multibase(input: &str) -> Result<Vec<u8>, MultiformatsError> {
+    multibase::decode(input)
+        .map(|(_base, bytes)| bytes)
+        .map_err(|e| MultiformatsError::Multibase {
+            input: input.to_string(),
+            error: e.to_string(),
+        })
+}
+
+/// Read an unsigned varint (LEB128, at most 9 bytes and minimally encoded)
+/// from the start of the input, returning its value and the rest of the input
+pub fn read_varint(input: &[u8]) -> Result<(u64, &[u8]), MultiformatsError> {
+    let mut value: u64 = 0;
+    for (i, byte) in input.iter().enumerate().take(9) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            if *byte == 0 && i > 0 {
+                return Err(MultiformatsError::VarintNotMinimal {
+                    input: input[..=i].to_vec(),
+                })
+            }
+            return Ok((value, &input[i + 1..]))
+        }
+    }
+    if input.len() < 9 {
+        Err(MultiformatsError::VarintTruncated {
+            input: input.to_vec(),
+        })
+    } else {
+        Err(MultiformatsError::VarintTooLong {
+            input: input[..9].to_vec(),
+        })
+    }
+}
+
+/// Split a multicodec-prefixed value into its codec and payload
+pub fn split_multicodec(input: &[u8]) -> Result<(u64, &[u8]), MultiformatsError> {
+    read_varint(input)
+}
+
+/// Parse a multihash into its HashFunction and digest, which must be the
+/// HashFunction's full output (truncated multihashes aren't supported)
+pub fn parse_multihash(multihash: &[u8]) -> Result<(HashFunction, &[u8]), MultiformatsError> {
+    let (code, rest) = read_varint(multihash)?;
+    let hash_function = HashFunction::from_multihash_code(code)
+        .ok_or(MultiformatsError::UnsupportedMultihash {
+            code: code,
+        })?;
+    let (length, digest) = read_varint(rest)?;
+    if length != digest.len() as u64 || hash_function.check_output_len(digest).is_err() {
+        return Err(MultiformatsError::MultihashLength {
+            hash_function: hash_function,
+            length: length,
+            digest: digest.to_vec(),
+        })
+    }
+    Ok((hash_function, digest))
+}
+
+/// Errors from decoding multiformats
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum MultiformatsError {
+    /// The multibase prefix is unknown, or the rest isn't valid in that base
+    #[error("invalid multibase string {input:?}: {error}")]
+    Multibase {
+        /// The invalid input
+        input: String,
+        /// Error from decoding
+        error: String,
+    },
+
+    /// The input ended before the end of a varint
+    #[error("truncated varint: 0x{}", hex::encode(.input))]
+    VarintTruncated {
+        /// The truncated varint
+        input: Vec<u8>,
+    },
+
+    /// A varint is longer than 9 bytes
+    #[error("varint is longer than 9 bytes: 0x{}..", hex::encode(.input))]
+    VarintTooLong {
+        /// The first 9 bytes of the varint
+        input: Vec<u8>,
+    },
+
+    /// A varint has trailing zero bytes
+    #[error("varint isn't minimally encoded: 0x{}", hex::encode(.input))]
+    VarintNotMinimal {
+        /// The varint
+        input: Vec<u8>,
+    },
+
+    /// A multicodec code isn't a u64
+    #[error("expected a u64 multicodec code, but found {code}")]
+    CodeNotU64 {
+        /// The invalid code
+        code: Number,
+    },
+
+    /// The multicodec prefix isn't the expected one
+    #[error("expected multicodec 0x{expected:x}, but found 0x{found:x}")]
+    UnexpectedMulticodec {
+        /// The expected code
+        expected: u64,
+        /// The code found
+        found: u64,
+    },
+
+    /// The multihash code isn't a supported HashFunction
+    #[error("unsupported multihash code: 0x{code:x}")]
+    UnsupportedMultihash {
+        /// The multihash code
+        code: u64,
+    },
+
+    /// The multihash length doesn't match its digest, or isn't the
+    /// HashFunction's output_len()
+    #[error("invalid {hash_function} multihash: length {length}, digest 0x{}", hex::encode(.digest))]
+    MultihashLength {
+        /// The HashFunction
+        hash_function: HashFunction,
+        /// The encoded length
+        length: u64,
+        /// The digest
+        digest: Vec<u8>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_varint() {
+        assert_eq!(Ok((1, &[][..])), read_varint(&[0x01]));
+        assert_eq!(Ok((0xed, &[0xff][..])), read_varint(&[0xed, 0x01, 0xff]));
+        assert_eq!(Ok((0xb220, &[][..])), read_varint(&[0xa0, 0xe4, 0x02]));
+        assert!(matches!(read_varint(&[0x80]), Err(MultiformatsError::VarintTruncated { .. })));
+        assert!(matches!(read_varint(&[0x81, 0x00]), Err(MultiformatsError::VarintNotMinimal { .. })));
+        assert!(matches!(read_varint(&[0xff; 10]), Err(MultiformatsError::VarintTooLong { .. })));
+    }
+}
@@ -1,112 +1,1622 @@
 /// Parser for the cryptoscript language.
 ///
-/// Partial definition to cover the terms in the example in lib.rs.
-///   TERM -> push PUSH_VALUE | FUNCTION
+///   PROGRAM -> DOC_COMMENT* ITEMS
+///   SETUP_CHALLENGE -> DOC_COMMENT* DEFINE* setup { PUSHES } DEFINE* challenge { TERMS } DEFINE*
+///   PUSHES -> push PUSH_VALUE ; PUSHES | push PUSH_VALUE ;
+///   ITEMS -> DEFINE ITEMS | TERM ; ITEMS | TERM ;
+///   DEFINE -> define NAME(NAME, ..) { TERMS }
 ///   TERMS -> TERM ; TERMS | TERM ;
-///   FUNCTION -> hash_sha256 | check_equal | assert_true
-///   PUSH_VALUE -> b"CHARS" | 0xHEX
+///   TERM -> push PUSH_VALUE | RESTACK | unpack_json<ELEM_SYMBOL> | FUNCTION | NAME PATH | LET
+///         | verify_merkle_proof<HASH_FUNCTION, MERKLE_PAIRING> | check_multisig<KEY_TYPE>
+///   LET -> let NAME | let NAME = TERM
+///   PATH -> ACCESSOR* | ACCESSOR* <ELEM_SYMBOL>
+///   ACCESSOR -> .KEY | ."CHARS" | ["CHARS"] | [N]
+///   RESTACK -> dup | dup N | swap | drop | drop N | dig N | dug N | restack N [N, ..]
+///   FUNCTION -> hash_sha256 | check_le | check_lt | check_equal | string_eq | bytes_eq
+///             | concat | slice | index | lookup | assert_true | to_json | string_to_bytes
+///             | assert_equal | assert_not_equal | assert_lt | assert_le | assert_gt | assert_ge
+///             | assert_member | verify_secp256k1 | ecrecover | hash_eip191
+///             | verify_ed25519 | verify_p256 | webauthn_payload | hash_sha3_256
+///             | hash_keccak256 | hash_sha512 | hash_blake2b_256 | hash_blake3
+///             | hash_ripemd160 | hmac_sha256 | constant_time_bytes_eq | verify_bls12_381
+///             | fast_aggregate_verify_bls12_381 | decode_multibase | multicodec_code
+///             | strip_multicodec | verify_multihash | jws_header | jws_payload
+///             | jws_signature | verify_jws | jwt_exp | jwt_nbf | validate_vc | validate_vp
+///             | verify_jwt_vc | verify_jwt_vp | vp_signer | parse_siwe | resolve_did
+///             | check_did_document | verification_method
+///   ELEM_SYMBOL -> Unit | Bool | Number | Bytes | String | str | Array | Object | JSON
+///   HASH_FUNCTION -> sha256 | sha3_256 | keccak256 | sha512 | blake2b_256 | blake3 | ripemd160
+///   MERKLE_PAIRING -> sorted | positional
+///   KEY_TYPE -> secp256k1 | p256 | ed25519 | bls12_381
+///   PUSH_VALUE -> () | true | false | NUMBER | "CHARS" | b"CHARS" | 0xHEX | base64"BASE64"
+///               | [JSON, ..] | {"KEY": JSON, ..} | json JSON
 ///
-/// Where CHARS is any number of characters which aren't escaped double-quotes (\") and HEX is a 64
-/// digit hexadecimal number.
+/// Where:
+/// - CHARS is any number of characters, with the escapes \n, \r, \t, \0, \b, \f, \\, \",
+///   \', \/ and \uXXXX (b"CHARS" also allows \xHH for arbitrary bytes)
+/// - HEX is an even number of hexadecimal digits
+/// - BASE64 is standard or URL-safe base64, with or without padding
+/// - NUMBER is a JSON number, e.g. 12, -3, 1.5 or 1.5e-7
+/// - JSON is an inline JSON value
+/// - N is a non-negative decimal integer
+/// - DOC_COMMENT is a "///" line comment, documenting the whole program
+/// - NAME is an identifier other than a built-in mnemonic or keyword (define, let, setup or
+///   challenge)
+/// - KEY is any identifier
+///
+/// check_eq, assert_lte, assert_gte and assert_elem are accepted as aliases of check_equal,
+/// assert_le, assert_ge and assert_member, respectively.
+///
+/// A DEFINE names its body, which is inlined wherever the NAME is used as a TERM, so that
+/// parse always produces a flat list of Instructions. DEFINE's may appear in any order,
+/// but may not be nested or (mutually) recursive. The params name the inputs that the body
+/// consumes, the last being the top of the stack, and must match the number of inputs in
+/// the body's Type.
+///
+/// "let NAME" binds NAME to the value on top of the stack ("let NAME = TERM" runs TERM
+/// first). Using NAME as a TERM then brings a copy of that value to the top of the stack
+/// (with "dup N"), or moves it there (with "dig N") if NAME isn't used again. Params are
+/// bound in the same way. It's an error to use NAME after its value has been consumed by
+/// an instruction.
+///
+/// Any value that's still bound at the end of a program or define body is dropped, so it
+/// isn't part of the final stack (or the define's outputs): e.g. "push 1; let x; push 2;"
+/// leaves only 2 on the stack, and "define f(x) {}" drops its input. To keep a bound value,
+/// use NAME as the last TERM.
+///
+/// A PATH accesses the value produced by NAME, e.g. "vc.credentialSubject.roles[0]<str>"
+/// expands to:
+///
+///   vc; push "credentialSubject"; lookup;
+///   unpack_json<Object>; push "roles"; lookup;
+///   unpack_json<Array>; push 0; index;
+///   unpack_json<String>;
+///
+/// The value produced by NAME must already be an Object (for .KEY or ["CHARS"]) or Array
+/// (for [N]), while each accessed JSON value is unpacked as needed by the next ACCESSOR.
+/// The final value is JSON, unless it's unpacked by an ELEM_SYMBOL annotation.
+///
+/// Literals map to Elem's as follows:
+/// - () -> Elem::Unit
+/// - true, false -> Elem::Bool
+/// - NUMBER -> Elem::Number
+/// - "CHARS" -> Elem::String
+/// - b"CHARS", 0xHEX, base64"BASE64" -> Elem::Bytes
+/// - [JSON, ..] -> Elem::Array
+/// - {"KEY": JSON, ..} -> Elem::Object
+/// - json JSON -> Elem::Json
+///
+/// Whitespace, "//" line comments and "/* */" block comments between tokens are ignored, as are
+/// empty TERM's, and the final ";" may be omitted.
+///
+/// parse accepts a PROGRAM, while parse_program accepts a SETUP_CHALLENGE, i.e. a Program.
+/// Definitions are shared between the setup and challenge blocks, but only literals may be
+/// pushed by setup.
+///
+/// Instructions and Program implement Display, producing canonical source accepted by parse
+/// and parse_program, respectively.
 
-use crate::elem::Elem;
+use crate::define::{Define, Defines, Term};
+use crate::elem::{Elem, ElemSymbol};
+use crate::hash_function::HashFunction;
+use crate::key_type::KeyType;
+use crate::merkle::MerklePairing;
+use crate::restack::{Restack, StackIx};
+use crate::lexer::{Lexer, Span, Token, TokenKind};
+use crate::program::Program;
 use crate::untyped_instruction::Instruction;
 use crate::untyped_instructions::Instructions;
+use crate::typed_instruction::StackInstructionError;
+use crate::untyped_instruction::InstructionError;
 
 use std::str::FromStr;
 
+use serde_json::{Number, Value};
 use thiserror::Error;
 
+/// Keywords, which can't be used as NAME's
+const KEYWORDS: [&str; 4] = ["define", "let", "setup", "challenge"];
+
+/// Mnemonics of the built-in TERM's, which can't be used as NAME's
+const MNEMONICS: [&str; 69] = [
+    "push", "unpack_json", "dup", "swap", "drop", "dig", "dug", "restack",
+    "hash_sha256", "check_le", "check_lt", "check_equal", "check_eq", "string_eq", "bytes_eq",
+    "concat", "slice", "index", "lookup", "assert_true", "to_json", "string_to_bytes",
+    "assert_equal", "assert_not_equal", "assert_lt", "assert_le", "assert_lte", "assert_gt",
+    "assert_ge", "assert_gte", "assert_member", "assert_elem", "verify_secp256k1",
+    "ecrecover", "hash_eip191", "verify_ed25519", "verify_p256", "webauthn_payload",
+    "hash_sha3_256", "hash_keccak256", "hash_sha512", "hash_blake2b_256", "hash_blake3",
+    "hash_ripemd160", "hmac_sha256", "constant_time_bytes_eq", "verify_merkle_proof",
+    "verify_bls12_381", "fast_aggregate_verify_bls12_381", "check_multisig",
+    "decode_multibase", "multicodec_code", "strip_multicodec", "verify_multihash",
+    "jws_header", "jws_payload", "jws_signature", "verify_jws", "jwt_exp", "jwt_nbf",
+    "validate_vc", "validate_vp", "verify_jwt_vc", "verify_jwt_vp", "vp_signer",
+    "parse_siwe", "resolve_did", "check_did_document", "verification_method",
+];
+
 /// Parse a list of Instruction's using serde_json::from_str
 pub fn parse_json(input: &str) -> Result<Instructions, ParseError> {
     match serde_json::from_str(&input) {
-        Err(serde_error) => Err(ParseError::SerdeJsonError(serde_error)),
+        Err(serde_error) => Err(ParseError::SerdeJsonError {
+            span: Span::at_line_column(input, serde_error.line(), serde_error.column()),
+            error: serde_error,
+        }),
         Ok(instructions) => Ok(instructions),
     }
 }
 
-/// Parse a ";"-separated list of instructions, where individual Instruction's
-/// are parsed with parse_instruction
+/// Parse a ";"-separated list of instructions
 pub fn parse(input: &str) -> Result<Instructions, ParseError> {
-    Ok(Instructions {
-        instructions:
-            input
-            .split(';')
-            .map(|term| term.trim())
-            .filter(|&term| !term.is_empty())
-            .map(|term| parse_instruction(term))
-            .collect::<Result<Vec<Instruction>, ParseError>>()?,
-    })
+    let mut parser = Parser::new(input);
+    let instructions = parser.parse_instructions()?;
+    parser.expect_eof()?;
+    Ok(instructions)
+}
+
+/// Parse a Program, i.e. a "setup { .. }" block followed by a "challenge { .. }" block
+pub fn parse_program(input: &str) -> Result<Program, ParseError> {
+    let mut parser = Parser::new(input);
+    let program = parser.parse_program()?;
+    parser.expect_eof()?;
+    Ok(program)
 }
 
-/// Parse an individual Instruction
-fn parse_instruction(term: &str) -> Result<Instruction, ParseError> {
-    if let Some(rest) = term.strip_prefix("push") {
-        return Ok(Instruction::Push(rest.trim().parse()?));
+/// Recursive-descent parser over the Token's produced by a Lexer
+#[derive(Clone, Debug)]
+pub(crate) struct Parser<'a> {
+    lexer: Lexer<'a>,
+    peeked: Option<Token>,
+}
+
+impl<'a> Parser<'a> {
+    /// A new Parser, positioned at the start of the source
+    pub(crate) fn new(source: &'a str) -> Self {
+        Parser {
+            lexer: Lexer::new(source),
+            peeked: None,
+        }
     }
-    match term {
-        "assert_true" => Ok(Instruction::AssertTrue),
-        "check_equal" => Ok(Instruction::CheckEq),
-        "hash_sha256" => Ok(Instruction::HashSha256),
-        _ => Err(ParseError::UnsupportedInstruction(term.to_string())),
+
+    /// The next Token, without consuming it
+    fn peek(&mut self) -> Result<&Token, ParseError> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.lexer.next_token()?);
+        }
+        Ok(self.peeked.as_ref().expect("peeked was just set"))
     }
-}
 
-impl FromStr for Elem {
-    type Err = ParseError;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.as_bytes() {
-            [b'b', b'"', inner @ .., b'"'] => {
-                return Ok(Elem::Bytes(inner.to_vec()));
+    /// Consume the next Token
+    fn next(&mut self) -> Result<Token, ParseError> {
+        match self.peeked.take() {
+            Some(token) => Ok(token),
+            None => self.lexer.next_token(),
+        }
+    }
+
+    /// Consume the next Token if it has the given TokenKind
+    fn eat(&mut self, kind: &TokenKind) -> Result<Option<Token>, ParseError> {
+        if self.peek()?.kind == *kind {
+            Ok(Some(self.next()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Consume the next Token, failing unless it has the given TokenKind
+    fn expect(&mut self, kind: &TokenKind) -> Result<Token, ParseError> {
+        let token = self.next()?;
+        if token.kind == *kind {
+            Ok(token)
+        } else {
+            Err(ParseError::UnexpectedToken {
+                expected: kind.to_string(),
+                found: token.kind.to_string(),
+                span: token.span,
+            })
+        }
+    }
+
+    /// Fail unless all of the input has been consumed
+    pub(crate) fn expect_eof(&mut self) -> Result<(), ParseError> {
+        self.expect(&TokenKind::Eof).map(|_| ())
+    }
+
+    /// Parse leading "///" doc comments
+    pub(crate) fn parse_docs(&mut self) -> Result<Vec<String>, ParseError> {
+        let mut docs = vec![];
+        while let TokenKind::DocComment(_) = self.peek()?.kind {
+            if let TokenKind::DocComment(doc) = self.next()?.kind {
+                docs.push(doc);
+            }
+        }
+        Ok(docs)
+    }
+
+    /// Parse Instruction's until the end of the input, expanding any define's
+    pub(crate) fn parse_instructions(&mut self) -> Result<Instructions, ParseError> {
+        let docs = self.parse_docs()?;
+        let mut defines = Defines::new();
+        let terms = self.parse_terms(&TokenKind::Eof, Some(&mut defines))?;
+        defines.check()?;
+        Ok(Instructions {
+            docs: docs,
+            instructions: defines.expand(&terms)?,
+        })
+    }
+
+    /// Parse a setup block and a challenge block, expanding any define's
+    pub(crate) fn parse_program(&mut self) -> Result<Program, ParseError> {
+        let docs = self.parse_docs()?;
+        let mut defines = Defines::new();
+        self.parse_defines(&mut defines)?;
+        let setup = self.parse_block("setup")?;
+        self.parse_defines(&mut defines)?;
+        let challenge = self.parse_block("challenge")?;
+        self.parse_defines(&mut defines)?;
+        defines.check()?;
+        for term in &setup {
+            match term {
+                Term::Instruction { instruction: Instruction::Push(_), .. } => (),
+                _ => return Err(ParseError::SetupNotLiteral { span: term.span() }),
+            }
+        }
+        Ok(Program {
+            docs: docs,
+            setup: Instructions {
+                docs: vec![],
+                instructions: defines.expand(&setup)?,
+            },
+            challenge: Instructions {
+                docs: vec![],
+                instructions: defines.expand(&challenge)?,
+            },
+        })
+    }
+
+    /// Parse any number of DEFINE's
+    fn parse_defines(&mut self, defines: &mut Defines) -> Result<(), ParseError> {
+        loop {
+            if self.eat(&TokenKind::Semicolon)?.is_some() {
+                continue;
+            }
+            if self.peek()?.kind != TokenKind::Ident("define".to_string()) {
+                return Ok(());
+            }
+            let define = self.parse_define()?;
+            defines.insert(define)?;
+        }
+    }
+
+    /// Parse a block of the form "keyword { TERMS }"
+    fn parse_block(&mut self, keyword: &str) -> Result<Vec<Term>, ParseError> {
+        self.expect(&TokenKind::Ident(keyword.to_string()))?;
+        self.expect(&TokenKind::LeftBrace)?;
+        let terms = self.parse_terms(&TokenKind::RightBrace, None)?;
+        self.expect(&TokenKind::RightBrace)?;
+        Ok(terms)
+    }
+
+    /// Parse TERM's until the given closing TokenKind, without consuming it.
+    ///
+    /// DEFINE's are only allowed when "defines" is provided
+    fn parse_terms(&mut self, end: &TokenKind, mut defines: Option<&mut Defines>) -> Result<Vec<Term>, ParseError> {
+        let mut terms = vec![];
+        loop {
+            if self.eat(&TokenKind::Semicolon)?.is_some() {
+                continue;
+            }
+            if let TokenKind::DocComment(_) = self.peek()?.kind {
+                let token = self.next()?;
+                return Err(ParseError::MisplacedDocComment { span: token.span });
+            }
+            if self.peek()?.kind == *end {
+                break;
+            }
+            if self.peek()?.kind == TokenKind::Eof {
+                self.expect(end)?;
             }
-            [b'0', b'x', hex_digits @ ..] => {
-                if hex_digits.len() != 64 {
-                    return Err(ParseError::HexElemWrongLength(hex_digits.len()));
+            if self.peek()?.kind == TokenKind::Ident("define".to_string()) {
+                match defines {
+                    Some(ref mut defines) => {
+                        let define = self.parse_define()?;
+                        defines.insert(define)?;
+                        continue;
+                    },
+                    None => {
+                        let token = self.next()?;
+                        return Err(ParseError::UnsupportedInstruction {
+                            instruction: "define".to_string(),
+                            span: token.span,
+                        });
+                    },
                 }
-                // Second value can be ignored since there is a check above for evenness.
-                let (bytes, _) = hex_digits
-                    .iter()
-                    // convert the hex digits to their decimal value
-                    .map(|byte| match byte {
-                        // convert digits
-                        digit @ 48..=57 => Ok(digit - 48),
-                        // convert uppercase A-F
-                        upper @ 65..=70 => Ok(upper - 55),
-                        // convert lowercase a-f
-                        lower @ 97..=102 => Ok(lower - 87),
-                        invalid => Err(ParseError::HexElemInvalid(*invalid as char)),
-                    })
-                    // pair up the hex digits to make bytes
-                    .try_fold(
-                        (vec![], None),
-                        |(mut acc, previous), digit| match previous {
-                            None => Ok((acc, Some(digit?))),
-                            Some(top) => {
-                                acc.push(top * 16 + digit?);
-                                Ok((acc, None))
-                            }
-                        },
-                    )?;
+            }
+            if self.peek()?.kind == TokenKind::Ident("let".to_string()) {
+                terms.extend(self.parse_let()?);
+            } else {
+                terms.extend(self.parse_term()?);
+            }
+            if self.peek()?.kind != *end {
+                self.expect(&TokenKind::Semicolon)?;
+            }
+        }
+        Ok(terms)
+    }
+
+    /// Parse a DEFINE, i.e. "define name(param, ..) { TERMS }"
+    fn parse_define(&mut self) -> Result<Define, ParseError> {
+        self.expect(&TokenKind::Ident("define".to_string()))?;
+        let (name, span) = self.parse_name()?;
+        self.expect(&TokenKind::LeftParen)?;
+        let mut params: Vec<String> = vec![];
+        while self.peek()?.kind != TokenKind::RightParen {
+            let (param, param_span) = self.parse_name()?;
+            if params.contains(&param) {
+                return Err(ParseError::DuplicateParam {
+                    name: param,
+                    span: param_span,
+                });
+            }
+            params.push(param);
+            if self.eat(&TokenKind::Comma)?.is_none() {
+                break;
+            }
+        }
+        self.expect(&TokenKind::RightParen)?;
+        self.expect(&TokenKind::LeftBrace)?;
+        let body = self.parse_terms(&TokenKind::RightBrace, None)?;
+        self.expect(&TokenKind::RightBrace)?;
+        Ok(Define {
+            name: name,
+            span: span,
+            params: params,
+            body: body,
+        })
+    }
 
-                return Ok(Elem::Bytes(bytes))
+    /// Parse a NAME, i.e. an identifier that's not reserved
+    fn parse_name(&mut self) -> Result<(String, Span), ParseError> {
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::Ident(name) if is_reserved(&name) => Err(ParseError::ReservedName {
+                name: name,
+                span: token.span,
+            }),
+            TokenKind::Ident(name) => Ok((name, token.span)),
+            kind => Err(ParseError::UnexpectedToken {
+                expected: "a name".to_string(),
+                found: kind.to_string(),
+                span: token.span,
+            }),
+        }
+    }
+
+    /// Parse a LET, i.e. "let NAME" or "let NAME = TERM"
+    fn parse_let(&mut self) -> Result<Vec<Term>, ParseError> {
+        self.expect(&TokenKind::Ident("let".to_string()))?;
+        let (name, span) = self.parse_name()?;
+        let mut terms = vec![];
+        if self.eat(&TokenKind::Equals)?.is_some() {
+            terms.extend(self.parse_term()?);
+        }
+        terms.push(Term::Let {
+            name: name,
+            span: span,
+        });
+        Ok(terms)
+    }
+
+    /// Parse a TERM other than a LET: any NAME is either bound by a LET or
+    /// param, or else a call to a define, and may be followed by a PATH
+    fn parse_term(&mut self) -> Result<Vec<Term>, ParseError> {
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::Ident(ref name) if !is_reserved(name) => {
+                let mut terms = vec![Term::Call {
+                    name: name.clone(),
+                    span: token.span,
+                }];
+                terms.extend(self.parse_path()?);
+                Ok(terms)
+            },
+            _ => {
+                let span = token.span;
+                Ok(vec![Term::Instruction {
+                    instruction: self.parse_instruction(token)?,
+                    span: span,
+                }])
+            },
+        }
+    }
+
+    /// Parse a (possibly empty) PATH into the Instruction's that access it,
+    /// unpacking each intermediate JSON value as an Object (for a KEY) or
+    /// Array (for an index) and the final value as the given ELEM_SYMBOL
+    fn parse_path(&mut self) -> Result<Vec<Term>, ParseError> {
+        let mut terms = vec![];
+        loop {
+            let (key, accessor_symbol, span) = match self.peek()?.kind {
+                TokenKind::Dot => {
+                    let dot_span = self.next()?.span;
+                    let token = self.next()?;
+                    match token.kind {
+                        TokenKind::Ident(key) | TokenKind::String(key) =>
+                            (Elem::String(key), ElemSymbol::Object, dot_span.to(token.span)),
+                        kind => return Err(ParseError::UnexpectedToken {
+                            expected: "a key".to_string(),
+                            found: kind.to_string(),
+                            span: token.span,
+                        }),
+                    }
+                },
+                TokenKind::LeftBracket => {
+                    let open_span = self.next()?.span;
+                    let token = self.next()?;
+                    let accessor = match token.kind {
+                        TokenKind::String(key) => (Elem::String(key), ElemSymbol::Object),
+                        TokenKind::Number(ref ix) => match ix.parse::<u64>() {
+                            Ok(ix) => (Elem::Number(From::from(ix)), ElemSymbol::Array),
+                            Err(_) => return Err(ParseError::InvalidStackIx {
+                                ix: ix.clone(),
+                                span: token.span,
+                            }),
+                        },
+                        kind => return Err(ParseError::UnexpectedToken {
+                            expected: "a key or index".to_string(),
+                            found: kind.to_string(),
+                            span: token.span,
+                        }),
+                    };
+                    let close_span = self.expect(&TokenKind::RightBracket)?.span;
+                    (accessor.0, accessor.1, open_span.to(close_span))
+                },
+                TokenKind::LeftAngle => {
+                    let span = self.peek()?.span;
+                    let elem_symbol = self.parse_elem_symbol_argument()?;
+                    if elem_symbol != ElemSymbol::Json {
+                        terms.push(Term::Instruction {
+                            instruction: Instruction::UnpackJson(elem_symbol),
+                            span: span,
+                        });
+                    }
+                    return Ok(terms);
+                },
+                _ => return Ok(terms),
+            };
+            // the root of the PATH must already be an Object or Array
+            if !terms.is_empty() {
+                terms.push(Term::Instruction {
+                    instruction: Instruction::UnpackJson(accessor_symbol),
+                    span: span,
+                });
             }
-            // No need to support booleans, but it is trivial to do so.
-            _ => Err(ParseError::UnsupportedElem(s.to_string())),
+            let instruction = match accessor_symbol {
+                ElemSymbol::Array => Instruction::Index,
+                _ => Instruction::Lookup,
+            };
+            terms.push(Term::Instruction {
+                instruction: Instruction::Push(key),
+                span: span,
+            });
+            terms.push(Term::Instruction {
+                instruction: instruction,
+                span: span,
+            });
+        }
+    }
+
+    /// Parse an individual Instruction, starting from its mnemonic
+    fn parse_instruction(&mut self, token: Token) -> Result<Instruction, ParseError> {
+        let mnemonic = match token.kind {
+            TokenKind::Ident(ref mnemonic) => mnemonic.as_str(),
+            _ => return Err(ParseError::UnsupportedInstruction {
+                instruction: token.kind.to_string(),
+                span: token.span,
+            }),
+        };
+        match mnemonic {
+            "push" => Ok(Instruction::Push(self.parse_literal()?)),
+            "unpack_json" => Ok(Instruction::UnpackJson(self.parse_elem_symbol_argument()?)),
+            "dup" | "swap" | "drop" | "dig" | "dug" | "restack" =>
+                Ok(Instruction::Restack(self.parse_restack(mnemonic, token.span)?)),
+            "hash_sha256" => Ok(Instruction::HashSha256),
+            "check_le" => Ok(Instruction::CheckLe),
+            "check_lt" => Ok(Instruction::CheckLt),
+            "check_equal" | "check_eq" => Ok(Instruction::CheckEq),
+            "string_eq" => Ok(Instruction::StringEq),
+            "bytes_eq" => Ok(Instruction::BytesEq),
+            "concat" => Ok(Instruction::Concat),
+            "slice" => Ok(Instruction::Slice),
+            "index" => Ok(Instruction::Index),
+            "lookup" => Ok(Instruction::Lookup),
+            "assert_true" => Ok(Instruction::AssertTrue),
+            "to_json" => Ok(Instruction::ToJson),
+            "string_to_bytes" => Ok(Instruction::StringToBytes),
+            "assert_equal" => Ok(Instruction::AssertEq),
+            "assert_not_equal" => Ok(Instruction::AssertNe),
+            "assert_lt" => Ok(Instruction::AssertLt),
+            "assert_le" | "assert_lte" => Ok(Instruction::AssertLe),
+            "assert_gt" => Ok(Instruction::AssertGt),
+            "assert_ge" | "assert_gte" => Ok(Instruction::AssertGe),
+            "assert_member" | "assert_elem" => Ok(Instruction::AssertMember),
+            "verify_secp256k1" => Ok(Instruction::VerifySecp256k1),
+            "ecrecover" => Ok(Instruction::EcRecover),
+            "hash_eip191" => Ok(Instruction::HashEip191),
+            "verify_ed25519" => Ok(Instruction::VerifyEd25519),
+            "verify_p256" => Ok(Instruction::VerifyP256),
+            "webauthn_payload" => Ok(Instruction::WebAuthnPayload),
+            "hash_sha3_256" => Ok(Instruction::HashSha3_256),
+            "hash_keccak256" => Ok(Instruction::HashKeccak256),
+            "hash_sha512" => Ok(Instruction::HashSha512),
+            "hash_blake2b_256" => Ok(Instruction::HashBlake2b256),
+            "hash_blake3" => Ok(Instruction::HashBlake3),
+            "hash_ripemd160" => Ok(Instruction::HashRipemd160),
+            "hmac_sha256" => Ok(Instruction::HmacSha256),
+            "constant_time_bytes_eq" => Ok(Instruction::ConstantTimeBytesEq),
+            "verify_merkle_proof" => {
+                let (hash_function, pairing) = self.parse_merkle_arguments()?;
+                Ok(Instruction::VerifyMerkleProof(hash_function, pairing))
+            },
+            "verify_bls12_381" => Ok(Instruction::VerifyBls12381),
+            "fast_aggregate_verify_bls12_381" => Ok(Instruction::FastAggregateVerifyBls12381),
+            "check_multisig" => {
+                self.expect(&TokenKind::LeftAngle)?;
+                let key_type = self.parse_key_type()?;
+                self.expect(&TokenKind::RightAngle)?;
+                Ok(Instruction::CheckMultiSig(key_type))
+            },
+            "decode_multibase" => Ok(Instruction::DecodeMultibase),
+            "multicodec_code" => Ok(Instruction::MulticodecCode),
+            "strip_multicodec" => Ok(Instruction::StripMulticodec),
+            "verify_multihash" => Ok(Instruction::VerifyMultihash),
+            "jws_header" => Ok(Instruction::JwsHeader),
+            "jws_payload" => Ok(Instruction::JwsPayload),
+            "jws_signature" => Ok(Instruction::JwsSignature),
+            "verify_jws" => Ok(Instruction::VerifyJws),
+            "jwt_exp" => Ok(Instruction::JwtExp),
+            "jwt_nbf" => Ok(Instruction::JwtNbf),
+            "validate_vc" => Ok(Instruction::ValidateVc),
+            "validate_vp" => Ok(Instruction::ValidateVp),
+            "verify_jwt_vc" => Ok(Instruction::VerifyJwtVc),
+            "verify_jwt_vp" => Ok(Instruction::VerifyJwtVp),
+            "vp_signer" => Ok(Instruction::VpSigner),
+            "parse_siwe" => Ok(Instruction::ParseSiwe),
+            "resolve_did" => Ok(Instruction::ResolveDid),
+            "check_did_document" => Ok(Instruction::CheckDidDocument),
+            "verification_method" => Ok(Instruction::VerificationMethod),
+            _ => Err(ParseError::UnsupportedInstruction {
+                instruction: mnemonic.to_string(),
+                span: token.span,
+            }),
+        }
+    }
+
+    /// Parse the arguments of a Restack mnemonic
+    ///
+    /// dup and drop may be used without an argument
+    fn parse_restack(&mut self, mnemonic: &str, mnemonic_span: Span) -> Result<Restack, ParseError> {
+        let argument = match self.peek()?.kind {
+            TokenKind::Number(_) => Some(self.parse_stack_ix()?),
+            _ => None,
+        };
+        match (mnemonic, argument) {
+            ("dup", None) => Ok(Restack::dup()),
+            ("dup", Some((ix, _))) => Ok(Restack::dup_n(ix)),
+            ("swap", None) => Ok(Restack::swap()),
+            ("drop", None) => Ok(Restack::drop()),
+            ("drop", Some((n, _))) => Ok(Restack::drop_n(n)),
+            ("dig", Some((ix, _))) => Ok(Restack::dig(ix)),
+            ("dug", Some((ix, _))) => Ok(Restack::dug(ix)),
+            ("restack", Some((restack_depth, _))) => {
+                self.expect(&TokenKind::LeftBracket)?;
+                let mut restack_vec = vec![];
+                while self.peek()?.kind != TokenKind::RightBracket {
+                    restack_vec.push(self.parse_stack_ix()?.0);
+                    if self.eat(&TokenKind::Comma)?.is_none() {
+                        break;
+                    }
+                }
+                let close_span = self.expect(&TokenKind::RightBracket)?.span;
+                let restack = Restack {
+                    restack_depth: restack_depth,
+                    restack_vec: restack_vec,
+                };
+                if restack.is_valid_depth() {
+                    Ok(restack)
+                } else {
+                    Err(ParseError::UnsupportedRestack {
+                        restack: format!("{:?}", restack),
+                        span: mnemonic_span.to(close_span),
+                    })
+                }
+            },
+            (_, argument) => Err(ParseError::UnsupportedRestack {
+                restack: mnemonic.to_string(),
+                span: argument.map_or(mnemonic_span, |(_, span)| mnemonic_span.to(span)),
+            }),
+        }
+    }
+
+    /// Parse a StackIx, i.e. a non-negative decimal integer
+    fn parse_stack_ix(&mut self) -> Result<(StackIx, Span), ParseError> {
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::Number(ref ix) => ix
+                .parse()
+                .map(|ix| (ix, token.span))
+                .map_err(|_| ParseError::InvalidStackIx {
+                    ix: ix.clone(),
+                    span: token.span,
+                }),
+            _ => Err(ParseError::InvalidStackIx {
+                ix: token.kind.to_string(),
+                span: token.span,
+            }),
+        }
+    }
+
+    /// Parse an ElemSymbol type argument of the form "<ElemSymbol>"
+    fn parse_elem_symbol_argument(&mut self) -> Result<ElemSymbol, ParseError> {
+        let open = self.next()?;
+        if open.kind != TokenKind::LeftAngle {
+            return Err(ParseError::UnsupportedElemSymbol {
+                elem_symbol: open.kind.to_string(),
+                span: open.span,
+            });
+        }
+        let token = self.next()?;
+        let elem_symbol = match token.kind {
+            TokenKind::Ident(ref elem_symbol) => parse_elem_symbol(elem_symbol, token.span)?,
+            _ => return Err(ParseError::UnsupportedElemSymbol {
+                elem_symbol: token.kind.to_string(),
+                span: token.span,
+            }),
+        };
+        self.expect(&TokenKind::RightAngle)?;
+        Ok(elem_symbol)
+    }
+
+    /// Parse the arguments of "verify_merkle_proof", of the form
+    /// "<HASH_FUNCTION, MERKLE_PAIRING>"
+    fn parse_merkle_arguments(&mut self) -> Result<(HashFunction, MerklePairing), ParseError> {
+        self.expect(&TokenKind::LeftAngle)?;
+        let token = self.next()?;
+        let hash_function = match token.kind {
+            TokenKind::Ident(ref name) => HashFunction::from_name(name),
+            _ => None,
+        }.ok_or_else(|| ParseError::UnsupportedHashFunction {
+            hash_function: token.kind.to_string(),
+            span: token.span,
+        })?;
+        self.expect(&TokenKind::Comma)?;
+        let token = self.next()?;
+        let pairing = match token.kind {
+            TokenKind::Ident(ref name) => MerklePairing::from_name(name),
+            _ => None,
+        }.ok_or_else(|| ParseError::UnsupportedMerklePairing {
+            pairing: token.kind.to_string(),
+            span: token.span,
+        })?;
+        self.expect(&TokenKind::RightAngle)?;
+        Ok((hash_function, pairing))
+    }
+
+    /// Parse a KeyType argument, e.g. "secp256k1"
+    fn parse_key_type(&mut self) -> Result<KeyType, ParseError> {
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::Ident(ref name) => KeyType::from_name(name),
+            _ => None,
+        }.ok_or_else(|| ParseError::UnsupportedKeyType {
+            key_type: token.kind.to_string(),
+            span: token.span,
+        })
+    }
+
+    /// Parse a literal Elem, i.e. the argument of "push"
+    pub(crate) fn parse_literal(&mut self) -> Result<Elem, ParseError> {
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::LeftParen => {
+                self.expect(&TokenKind::RightParen)?;
+                Ok(Elem::Unit)
+            },
+            TokenKind::Ident(ref ident) if ident == "true" => Ok(Elem::Bool(true)),
+            TokenKind::Ident(ref ident) if ident == "false" => Ok(Elem::Bool(false)),
+            TokenKind::Ident(ref ident) if ident == "json" => Ok(Elem::Json(self.parse_json_value()?)),
+            TokenKind::Number(ref number) => Ok(Elem::Number(parse_number(number, token.span)?)),
+            TokenKind::String(string) => Ok(Elem::String(string)),
+            TokenKind::Bytes(bytes) => Ok(Elem::Bytes(bytes)),
+            TokenKind::Hex(hex_digits) => Ok(Elem::Bytes(parse_hex(&hex_digits, token.span)?)),
+            TokenKind::LeftBracket | TokenKind::LeftBrace => {
+                self.lexer.seek(token.span);
+                match self.lexer.lex_json()? {
+                    (Value::Array(array), _) => Ok(Elem::Array(array)),
+                    (Value::Object(object), _) => Ok(Elem::Object(object)),
+                    (value, span) => Err(ParseError::UnsupportedElem {
+                        elem: value.to_string(),
+                        span: span,
+                    }),
+                }
+            },
+            kind => Err(ParseError::UnsupportedElem {
+                elem: kind.to_string(),
+                span: token.span,
+            }),
+        }
+    }
+
+    /// Parse an inline JSON Value
+    fn parse_json_value(&mut self) -> Result<Value, ParseError> {
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::Ident(ref ident) if ident == "null" => Ok(Value::Null),
+            TokenKind::Ident(ref ident) if ident == "true" => Ok(Value::Bool(true)),
+            TokenKind::Ident(ref ident) if ident == "false" => Ok(Value::Bool(false)),
+            TokenKind::Number(ref number) => Ok(Value::Number(parse_number(number, token.span)?)),
+            TokenKind::String(string) => Ok(Value::String(string)),
+            TokenKind::LeftBracket | TokenKind::LeftBrace => {
+                self.lexer.seek(token.span);
+                Ok(self.lexer.lex_json()?.0)
+            },
+            kind => Err(ParseError::UnsupportedElem {
+                elem: kind.to_string(),
+                span: token.span,
+            }),
         }
     }
 }
 
+/// Is the identifier a keyword or built-in mnemonic?
+fn is_reserved(ident: &str) -> bool {
+    KEYWORDS.contains(&ident) || MNEMONICS.contains(&ident)
+}
+
+/// Parse a JSON Number with the given Span
+fn parse_number(number: &str, span: Span) -> Result<Number, ParseError> {
+    Number::from_str(number).map_err(|_| ParseError::UnsupportedElem {
+        elem: number.to_string(),
+        span: span,
+    })
+}
+
+/// Parse the digits of a 0x... literal with the given Span
+fn parse_hex(hex_digits: &str, span: Span) -> Result<Vec<u8>, ParseError> {
+    if !hex_digits.len().is_multiple_of(2) {
+        return Err(ParseError::HexElemWrongLength {
+            length: hex_digits.len(),
+            span: span,
+        });
+    }
+    // Second value can be ignored since there is a check above for evenness.
+    let (bytes, _) = hex_digits
+        .bytes()
+        .enumerate()
+        // convert the hex digits to their decimal value
+        .map(|(digit_index, byte)| match byte {
+            // convert digits
+            digit @ 48..=57 => Ok(digit - 48),
+            // convert uppercase A-F
+            upper @ 65..=70 => Ok(upper - 55),
+            // convert lowercase a-f
+            lower @ 97..=102 => Ok(lower - 87),
+            // skip the "0x" prefix
+            invalid => Err(ParseError::HexElemInvalid {
+                found: invalid as char,
+                span: Span {
+                    start: span.start + 2 + digit_index,
+                    end: span.start + 3 + digit_index,
+                    line: span.line,
+                    column: span.column + 2 + digit_index,
+                },
+            }),
+        })
+        // pair up the hex digits to make bytes
+        .try_fold(
+            (vec![], None),
+            |(mut acc, previous), digit| match previous {
+                None => Ok((acc, Some(digit?))),
+                Some(top) => {
+                    acc.push(top * 16 + digit?);
+                    Ok((acc, None))
+                }
+            },
+        )?;
+    Ok(bytes)
+}
+
+/// Parse an ElemSymbol with the given Span
+fn parse_elem_symbol(s: &str, span: Span) -> Result<ElemSymbol, ParseError> {
+    match s {
+        "Unit" => Ok(ElemSymbol::Unit),
+        "Bool" => Ok(ElemSymbol::Bool),
+        "Number" => Ok(ElemSymbol::Number),
+        "Bytes" => Ok(ElemSymbol::Bytes),
+        "String" | "str" => Ok(ElemSymbol::String),
+        "Array" => Ok(ElemSymbol::Array),
+        "Object" => Ok(ElemSymbol::Object),
+        "JSON" | "Json" => Ok(ElemSymbol::Json),
+        _ => Err(ParseError::UnsupportedElemSymbol {
+            elem_symbol: s.to_string(),
+            span: span,
+        }),
+    }
+}
+
+impl FromStr for ElemSymbol {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_elem_symbol(s, Span { start: 0, end: s.len(), line: 1, column: 1 })
+    }
+}
+
+impl FromStr for Elem {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(s);
+        let elem = parser.parse_literal()?;
+        parser.expect_eof()?;
+        Ok(elem)
+    }
+}
+
+/// Errors encountered while parsing, each with the Span where it occurred
 #[derive(Debug, Error)]
 pub enum ParseError {
-    #[error("input hex number did not have the expected 64 digits ({0})")]
-    HexElemWrongLength(usize),
-    #[error("there is an invalid character in the hex number ({0})")]
-    HexElemInvalid(char),
-    #[error("elem is malformed or cannot be parsed in this context")]
-    UnsupportedElem(String),
-    #[error("instruction is malformed or cannot be parsed in this context")]
-    UnsupportedInstruction(String),
-    #[error("error from serde_json ({0})")]
-    SerdeJsonError(serde_json::Error),
+    /// 0x... literal with an odd number of digits
+    #[error("{span}: input hex number has an odd number of digits ({length})")]
+    HexElemWrongLength {
+        /// Number of digits found
+        length: usize,
+        /// Location of the literal
+        span: Span,
+    },
+
+    /// 0x... literal with a non-hexadecimal digit
+    #[error("{span}: there is an invalid character in the hex number ({found})")]
+    HexElemInvalid {
+        /// Invalid character
+        found: char,
+        /// Location of the invalid character
+        span: Span,
+    },
+
+    /// Unsupported literal
+    #[error("{span}: elem is malformed or cannot be parsed in this context ({elem})")]
+    UnsupportedElem {
+        /// The unsupported literal
+        elem: String,
+        /// Location of the literal
+        span: Span,
+    },
+
+    /// Unknown or malformed instruction
+    #[error("{span}: instruction is malformed or cannot be parsed in this context ({instruction})")]
+    UnsupportedInstruction {
+        /// The unsupported instruction
+        instruction: String,
+        /// Location of the instruction
+        span: Span,
+    },
+
+    /// Restack mnemonic with missing, extra, or invalid arguments
+    #[error("{span}: restack is malformed, expected e.g. \"dig 2\" or \"restack 3 [2, 0, 1]\" ({restack})")]
+    UnsupportedRestack {
+        /// The malformed Restack
+        restack: String,
+        /// Location of the Restack
+        span: Span,
+    },
+
+    /// Restack argument that isn't a StackIx
+    #[error("{span}: stack index is not a non-negative integer ({ix})")]
+    InvalidStackIx {
+        /// The invalid stack index
+        ix: String,
+        /// Location of the stack index
+        span: Span,
+    },
+
+    /// Argument that isn't a HashFunction
+    #[error("{span}: unsupported hash function, expected one of sha256, sha3_256, keccak256, sha512, blake2b_256, blake3 or ripemd160 ({hash_function})")]
+    UnsupportedHashFunction {
+        /// The unsupported argument
+        hash_function: String,
+        /// Location of the argument
+        span: Span,
+    },
+
+    /// Argument that isn't a MerklePairing
+    #[error("{span}: unsupported Merkle pairing, expected sorted or positional ({pairing})")]
+    UnsupportedMerklePairing {
+        /// The unsupported argument
+        pairing: String,
+        /// Location of the argument
+        span: Span,
+    },
+
+    /// Argument that isn't a KeyType
+    #[error("{span}: unsupported key type, expected one of secp256k1, p256, ed25519 or bls12_381 ({key_type})")]
+    UnsupportedKeyType {
+        /// The unsupported argument
+        key_type: String,
+        /// Location of the argument
+        span: Span,
+    },
+
+    /// Type argument that isn't an ElemSymbol
+    #[error("{span}: type argument is not an ElemSymbol, expected e.g. \"<Object>\" ({elem_symbol})")]
+    UnsupportedElemSymbol {
+        /// The unsupported type argument
+        elem_symbol: String,
+        /// Location of the type argument
+        span: Span,
+    },
+
+    /// A Token other than the one expected
+    #[error("{span}: expected {expected}, but found {found}")]
+    UnexpectedToken {
+        /// Expected Token
+        expected: String,
+        /// Found Token
+        found: String,
+        /// Location of the found Token
+        span: Span,
+    },
+
+    /// A character that can't start any Token
+    #[error("{span}: unexpected character ({found:?})")]
+    UnexpectedChar {
+        /// The unexpected character
+        found: char,
+        /// Location of the character
+        span: Span,
+    },
+
+    /// Unknown or malformed escape sequence in a "..." or b"..." literal
+    #[error("{span}: invalid escape sequence")]
+    InvalidEscape {
+        /// Location of the escape sequence
+        span: Span,
+    },
+
+    /// base64"..." literal that isn't valid base64
+    #[error("{span}: invalid base64")]
+    InvalidBase64 {
+        /// Location of the literal
+        span: Span,
+    },
+
+    /// "///" doc comment after the start of the program
+    #[error("{span}: doc comments (///) may only appear at the start of a program, use // instead")]
+    MisplacedDocComment {
+        /// Location of the doc comment
+        span: Span,
+    },
+
+    /// "/*" block comment without a matching "*/"
+    #[error("{span}: block comment is missing its closing */")]
+    UnterminatedComment {
+        /// Location of the comment, up to the end of the input
+        span: Span,
+    },
+
+    /// A literal missing its closing delimiter
+    #[error("{span}: literal is missing its closing delimiter")]
+    UnterminatedLiteral {
+        /// Location of the literal, up to the end of the input
+        span: Span,
+    },
+
+    /// A define with the same name as an earlier one
+    #[error("{span}: {name} is already defined at {previous}")]
+    DuplicateDefine {
+        /// Name of the define
+        name: String,
+        /// Location of the earlier define's name
+        previous: Span,
+        /// Location of the later define's name
+        span: Span,
+    },
+
+    /// A define, param or let named after a keyword or built-in mnemonic
+    #[error("{span}: {name} is a keyword or built-in instruction and can't be used as a name")]
+    ReservedName {
+        /// The reserved name
+        name: String,
+        /// Location of the name
+        span: Span,
+    },
+
+    /// A define that (directly or indirectly) calls itself
+    #[error("{span}: define's may not be recursive ({cycle})")]
+    RecursiveDefine {
+        /// The chain of calls, e.g. "f -> g -> f"
+        cycle: String,
+        /// Location of the call closing the cycle
+        span: Span,
+    },
+
+    /// A define whose body consumes a different number of inputs than it has params
+    #[error("{span}: {name} has {expected} params, but its body consumes {found} inputs")]
+    DefineArity {
+        /// Name of the define
+        name: String,
+        /// Number of params
+        expected: usize,
+        /// Number of inputs consumed by the body
+        found: usize,
+        /// Location of the name
+        span: Span,
+    },
+
+    /// A setup block TERM other than "push PUSH_VALUE"
+    #[error("{span}: setup may only push literals")]
+    SetupNotLiteral {
+        /// Location of the TERM
+        span: Span,
+    },
+
+    /// A define with two params of the same name
+    #[error("{span}: {name} is already a param of this define")]
+    DuplicateParam {
+        /// Name of the param
+        name: String,
+        /// Location of the second param
+        span: Span,
+    },
+
+    /// A use of a let-bound name or param whose value is no longer on the stack
+    #[error("{span}: {name} is used after its value was consumed at {consumed}")]
+    UseAfterConsume {
+        /// The name
+        name: String,
+        /// Location of the TERM that consumed the value
+        consumed: Span,
+        /// Location of the use
+        span: Span,
+    },
+
+    /// An instruction that can't be converted to a typed instruction, to
+    /// track let-bound values through it
+    #[error("{span}: unable to track let-bound values through an invalid instruction:\n{error}")]
+    LetInstructionError {
+        /// Error from Instructions::to_instrs
+        error: InstructionError,
+        /// Location of the instruction
+        span: Span,
+    },
+
+    /// An instruction whose Type can't be found, to track let-bound values
+    /// through it
+    #[error("{span}: unable to track let-bound values through an ill-typed instruction:\n{error}")]
+    LetTypeError {
+        /// Error from Instrs::type_of
+        error: StackInstructionError,
+        /// Location of the instruction
+        span: Span,
+    },
+
+    /// A define whose body can't be converted to typed instructions
+    #[error("{span}: the body of {name} is invalid:\n{error}")]
+    DefineInstructionError {
+        /// Name of the define
+        name: String,
+        /// Error from Instructions::to_instrs
+        error: InstructionError,
+        /// Location of the name
+        span: Span,
+    },
+
+    /// A define whose body is ill-typed
+    #[error("{span}: the body of {name} is ill-typed:\n{error}")]
+    DefineTypeError {
+        /// Name of the define
+        name: String,
+        /// Error from Instrs::type_of
+        error: StackInstructionError,
+        /// Location of the name
+        span: Span,
+    },
+
+    /// Error from parse_json
+    #[error("{span}: error from serde_json ({error})")]
+    SerdeJsonError {
+        /// serde_json error
+        error: serde_json::Error,
+        /// Location reported by serde_json
+        span: Span,
+    },
 }
 
+impl ParseError {
+    /// Where the error occurred
+    pub fn span(&self) -> Span {
+        match self {
+            Self::HexElemWrongLength { span, .. } => *span,
+            Self::HexElemInvalid { span, .. } => *span,
+            Self::UnsupportedElem { span, .. } => *span,
+            Self::UnsupportedInstruction { span, .. } => *span,
+            Self::UnsupportedRestack { span, .. } => *span,
+            Self::InvalidStackIx { span, .. } => *span,
+            Self::UnsupportedElemSymbol { span, .. } => *span,
+            Self::UnsupportedHashFunction { span, .. } => *span,
+            Self::UnsupportedMerklePairing { span, .. } => *span,
+            Self::UnsupportedKeyType { span, .. } => *span,
+            Self::UnexpectedToken { span, .. } => *span,
+            Self::UnexpectedChar { span, .. } => *span,
+            Self::InvalidEscape { span } => *span,
+            Self::InvalidBase64 { span } => *span,
+            Self::MisplacedDocComment { span } => *span,
+            Self::UnterminatedComment { span } => *span,
+            Self::UnterminatedLiteral { span } => *span,
+            Self::DuplicateDefine { span, .. } => *span,
+            Self::ReservedName { span, .. } => *span,
+            Self::SetupNotLiteral { span } => *span,
+            Self::DuplicateParam { span, .. } => *span,
+            Self::UseAfterConsume { span, .. } => *span,
+            Self::LetInstructionError { span, .. } => *span,
+            Self::LetTypeError { span, .. } => *span,
+            Self::RecursiveDefine { span, .. } => *span,
+            Self::DefineArity { span, .. } => *span,
+            Self::DefineInstructionError { span, .. } => *span,
+            Self::DefineTypeError { span, .. } => *span,
+            Self::SerdeJsonError { span, .. } => *span,
+        }
+    }
+
+    /// Render the error with a caret-annotated snippet of the source it was
+    /// parsed from, e.g.
+    ///
+    /// ```text
+    /// error: 2:5: instruction is malformed or cannot be parsed in this context (hash_md5)
+    ///   |
+    /// 2 |     hash_md5;
+    ///   |     ^^^^^^^^
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        format!("error: {}\n{}", self, self.span().snippet(source))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stack::Stack;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn test_parse_all_mnemonics() {
+        let instructions = parse(r#"
+            push b"I am the walrus.";
+            hash_sha256;
+            check_le;
+            check_lt;
+            check_equal;
+            string_eq;
+            bytes_eq;
+            concat;
+            slice;
+            index;
+            lookup;
+            assert_true;
+            to_json;
+            unpack_json<Object>;
+            unpack_json<JSON>;
+            string_to_bytes;
+            assert_equal;
+            assert_not_equal;
+            assert_lt;
+            assert_le;
+            assert_gt;
+            assert_ge;
+            assert_member;
+            verify_secp256k1;
+            ecrecover;
+            hash_eip191;
+            verify_ed25519;
+            verify_p256;
+            webauthn_payload;
+            hash_sha3_256;
+            hash_keccak256;
+            hash_sha512;
+            hash_blake2b_256;
+            hash_blake3;
+            hash_ripemd160;
+            hmac_sha256;
+            constant_time_bytes_eq;
+            verify_merkle_proof<keccak256, sorted>;
+            verify_bls12_381;
+            fast_aggregate_verify_bls12_381;
+            check_multisig<secp256k1>;
+            decode_multibase;
+            multicodec_code;
+            strip_multicodec;
+            verify_multihash;
+            jws_header;
+            jws_payload;
+            jws_signature;
+            verify_jws;
+            jwt_exp;
+            jwt_nbf;
+            validate_vc;
+            validate_vp;
+            verify_jwt_vc;
+            verify_jwt_vp;
+            vp_signer;
+            parse_siwe;
+            resolve_did;
+            check_did_document;
+            verification_method;
+        "#).expect("failed to parse the input");
+        assert_eq!(Instructions {
+            docs: vec![],
+            instructions: vec![
+                Instruction::Push(Elem::Bytes(b"I am the walrus.".to_vec())),
+                Instruction::HashSha256,
+                Instruction::CheckLe,
+                Instruction::CheckLt,
+                Instruction::CheckEq,
+                Instruction::StringEq,
+                Instruction::BytesEq,
+                Instruction::Concat,
+                Instruction::Slice,
+                Instruction::Index,
+                Instruction::Lookup,
+                Instruction::AssertTrue,
+                Instruction::ToJson,
+                Instruction::UnpackJson(ElemSymbol::Object),
+                Instruction::UnpackJson(ElemSymbol::Json),
+                Instruction::StringToBytes,
+                Instruction::AssertEq,
+                Instruction::AssertNe,
+                Instruction::AssertLt,
+                Instruction::AssertLe,
+                Instruction::AssertGt,
+                Instruction::AssertGe,
+                Instruction::AssertMember,
+                Instruction::VerifySecp256k1,
+                Instruction::EcRecover,
+                Instruction::HashEip191,
+                Instruction::VerifyEd25519,
+                Instruction::VerifyP256,
+                Instruction::WebAuthnPayload,
+                Instruction::HashSha3_256,
+                Instruction::HashKeccak256,
+                Instruction::HashSha512,
+                Instruction::HashBlake2b256,
+                Instruction::HashBlake3,
+                Instruction::HashRipemd160,
+                Instruction::HmacSha256,
+                Instruction::ConstantTimeBytesEq,
+                Instruction::VerifyMerkleProof(HashFunction::Keccak256, MerklePairing::Sorted),
+                Instruction::VerifyBls12381,
+                Instruction::FastAggregateVerifyBls12381,
+                Instruction::CheckMultiSig(KeyType::Secp256k1),
+                Instruction::DecodeMultibase,
+                Instruction::MulticodecCode,
+                Instruction::StripMulticodec,
+                Instruction::VerifyMultihash,
+                Instruction::JwsHeader,
+                Instruction::JwsPayload,
+                Instruction::JwsSignature,
+                Instruction::VerifyJws,
+                Instruction::JwtExp,
+                Instruction::JwtNbf,
+                Instruction::ValidateVc,
+                Instruction::ValidateVp,
+                Instruction::VerifyJwtVc,
+                Instruction::VerifyJwtVp,
+                Instruction::VpSigner,
+                Instruction::ParseSiwe,
+                Instruction::ResolveDid,
+                Instruction::CheckDidDocument,
+                Instruction::VerificationMethod,
+            ],
+        }, instructions);
+        let json_instructions = serde_json::to_string(&instructions).expect("failed to serialize");
+        assert_eq!(instructions, parse_json(&json_instructions).expect("failed to parse JSON"));
+    }
+
+    #[test]
+    fn test_parse_restack() {
+        let instructions = parse("dup; dup 2; swap; drop; drop 3; dig 4; dug 2; restack 3 [2, 0, 1]; restack 0 [];")
+            .expect("failed to parse the input");
+        assert_eq!(vec![
+            Restack::dup(),
+            Restack::dup_n(2),
+            Restack::swap(),
+            Restack::drop(),
+            Restack::drop_n(3),
+            Restack::dig(4),
+            Restack::dug(2),
+            Restack { restack_depth: 3, restack_vec: vec![2, 0, 1] },
+            Restack::id(),
+        ].into_iter().map(Instruction::Restack).collect::<Vec<Instruction>>(), instructions.instructions);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(matches!(parse("dig;"), Err(ParseError::UnsupportedRestack { .. })));
+        assert!(matches!(parse("swap 2;"), Err(ParseError::UnsupportedRestack { .. })));
+        assert!(matches!(parse("restack 1 [1];"), Err(ParseError::UnsupportedRestack { .. })));
+        assert!(matches!(parse("unpack_json<Float>;"), Err(ParseError::UnsupportedElemSymbol { .. })));
+        assert!(matches!(parse("unpack_json Object;"), Err(ParseError::UnsupportedElemSymbol { .. })));
+        assert!(matches!(parse("hash_md5;"), Err(ParseError::UnsupportedInstruction { .. })));
+        assert!(matches!(parse("concat slice;"), Err(ParseError::UnexpectedToken { .. })));
+    }
+
+    #[test]
+    fn test_parse_error_spans() {
+        let source = "push b\"a\";\n  dig 2;\n  hash_md5;\n";
+        let error = parse(source).expect_err("hash_md5 is not an instruction");
+        assert_eq!(Span { start: 22, end: 30, line: 3, column: 3 }, error.span());
+        assert_eq!("error: 3:3: instruction is malformed or cannot be parsed in this context (hash_md5)\n  |\n3 |   hash_md5;\n  |   ^^^^^^^^",
+                   error.render(source));
+
+        let error = parse("push 0x12z4;").expect_err("z is not a hex digit");
+        assert_eq!(Span { start: 9, end: 10, line: 1, column: 10 }, error.span());
+
+        let error = parse_json("{\n  \"instructions\": [\"HashMd5\"]\n}").expect_err("HashMd5 is not an instruction");
+        assert_eq!((2, 28), (error.span().line, error.span().column));
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(Elem::Bytes(vec![]), "0x".parse().expect("failed to parse empty hex"));
+        assert_eq!(Elem::Bytes(vec![0x49, 0x20, 0xaB]), "0x4920aB".parse().expect("failed to parse hex"));
+        assert!(matches!("0x492".parse::<Elem>(), Err(ParseError::HexElemWrongLength { length: 3, .. })));
+        assert!(matches!("0x49g0".parse::<Elem>(), Err(ParseError::HexElemInvalid { found: 'g', .. })));
+    }
+
+    #[test]
+    fn test_pretty() {
+        let instructions = Instructions {
+            docs: vec!["Hash preimage".to_string(), "".to_string()],
+            instructions: vec![
+                Instruction::Push(Elem::Bytes(b"I am the walrus.".to_vec())),
+                Instruction::HashSha256,
+                Instruction::Restack(Restack::dig(2)),
+                Instruction::UnpackJson(ElemSymbol::Json),
+            ],
+        };
+        assert_eq!("/// Hash preimage\n///\npush 0x4920616d207468652077616c7275732e;\nhash_sha256;\ndig 2;\nunpack_json<JSON>;\n",
+                   format!("{}", instructions));
+    }
+
+    #[test]
+    fn test_parse_literals() {
+        for (literal, elem) in [
+            ("()", Elem::Unit),
+            ("true", Elem::Bool(true)),
+            ("false", Elem::Bool(false)),
+            ("-12", Elem::Number(From::from(-12i8))),
+            ("1.5e-7", Elem::Number(Number::from_str("1.5e-7").expect("invalid Number"))),
+            (r#""a\"b;\n""#, Elem::String("a\"b;\n".to_string())),
+            (r#"b"\xff;""#, Elem::Bytes(vec![0xff, b';'])),
+            ("0x", Elem::Bytes(vec![])),
+            ("base64\"SGk=\"", Elem::Bytes(b"Hi".to_vec())),
+            (r#"[1, "a"]"#, Elem::Array(vec![serde_json::json!(1), serde_json::json!("a")])),
+            (r#"{"a": [null]}"#, Elem::Object(serde_json::json!({"a": [null]}).as_object().expect("object").clone())),
+            ("json null", Elem::Json(Value::Null)),
+            ("json -1", Elem::Json(serde_json::json!(-1))),
+            (r#"json {"a": 1}"#, Elem::Json(serde_json::json!({"a": 1}))),
+        ] {
+            assert_eq!(elem, literal.parse().expect("failed to parse literal"), "{}", literal);
+            assert_eq!(elem, elem.literal().parse().expect("failed to parse Elem::literal"), "{}", literal);
+        }
+        assert!(matches!("(".parse::<Elem>(), Err(ParseError::UnexpectedToken { .. })));
+        assert!(matches!("01".parse::<Elem>(), Err(ParseError::UnsupportedElem { .. })));
+        assert!(matches!("[1,".parse::<Elem>(), Err(ParseError::SerdeJsonError { .. })));
+        assert!(matches!("json ;".parse::<Elem>(), Err(ParseError::UnsupportedElem { .. })));
+    }
+
+    #[test]
+    fn test_parse_json_literal_followed_by_instruction() {
+        let instructions = parse(r#"push {"a;b": [1]}; to_json; push json 2; push "x"; push ()"#)
+            .expect("failed to parse the input");
+        assert_eq!(5, instructions.instructions.len());
+    }
+
+    #[test]
+    fn test_parse_comments() {
+        let instructions = parse(r#"
+            /// Checks a preimage.
+            ///
+            ///  Indented
+            //// not a doc comment
+            push b"a;b"; // line comment; not an instruction
+            push "c;d /* not a comment */";
+            /* block comment;
+               spanning lines */ hash_sha256 /**/;;
+            check_equal // no trailing semicolon"#).expect("failed to parse the input");
+        assert_eq!(Instructions {
+            docs: vec!["Checks a preimage.".to_string(), "".to_string(), " Indented".to_string()],
+            instructions: vec![
+                Instruction::Push(Elem::Bytes(b"a;b".to_vec())),
+                Instruction::Push(Elem::String("c;d /* not a comment */".to_string())),
+                Instruction::HashSha256,
+                Instruction::CheckEq,
+            ],
+        }, instructions);
+
+        assert!(matches!(parse("push b\"\";\n/// late\nhash_sha256;"),
+                         Err(ParseError::MisplacedDocComment { span: Span { line: 2, column: 1, .. } })));
+        assert!(matches!(parse("hash_sha256; /* unterminated"),
+                         Err(ParseError::UnterminatedComment { span: Span { start: 13, end: 28, .. } })));
+    }
+
+    #[test]
+    fn test_parse_define() {
+        let instructions = parse(r#"
+            /// Check a preimage
+            push b"I am the walrus.";
+            check_preimage;
+
+            define check_preimage(preimage) {
+                hash_sha256;
+                push 0x475b03e74f7ee448273dbde5ab892746c7b23a2b4d050ccb7d9270b6fb152b72;
+                check_bytes_equal;
+            }
+
+            define check_bytes_equal(x, y) { bytes_eq; assert_true }
+        "#).expect("failed to parse the input");
+        assert_eq!(Instructions {
+            docs: vec!["Check a preimage".to_string()],
+            instructions: vec![
+                Instruction::Push(Elem::Bytes(b"I am the walrus.".to_vec())),
+                Instruction::HashSha256,
+                Instruction::Push(Elem::Bytes(vec![0x47, 0x5b, 0x03, 0xe7, 0x4f, 0x7e, 0xe4, 0x48,
+                    0x27, 0x3d, 0xbd, 0xe5, 0xab, 0x89, 0x27, 0x46, 0xc7, 0xb2, 0x3a, 0x2b, 0x4d,
+                    0x05, 0x0c, 0xcb, 0x7d, 0x92, 0x70, 0xb6, 0xfb, 0x15, 0x2b, 0x72])),
+                Instruction::BytesEq,
+                Instruction::AssertTrue,
+            ],
+        }, instructions);
+        assert_eq!(vec![Instruction::Restack(Restack::drop())],
+                   parse("define unused() { push 1; } define pop(x) { drop }; pop").expect("failed to parse the input").instructions);
+    }
+
+    #[test]
+    fn test_parse_define_invalid() {
+        assert!(matches!(parse("define f(x) { swap }"),
+                         Err(ParseError::DefineArity { expected: 1, found: 2, .. })));
+        assert!(matches!(parse("define f() { push true; hash_sha256 }"),
+                         Err(ParseError::DefineTypeError { .. })));
+        assert!(matches!(parse("define f() { f }"),
+                         Err(ParseError::RecursiveDefine { ref cycle, .. }) if cycle == "f -> f"));
+        assert!(matches!(parse("define f() { g } define g() { push 1; h } define h() { f }"),
+                         Err(ParseError::RecursiveDefine { ref cycle, .. }) if cycle == "f -> g -> h -> f"));
+        assert!(matches!(parse("define f() {} define f() {}"),
+                         Err(ParseError::DuplicateDefine { previous: Span { start: 7, .. }, span: Span { start: 21, .. }, .. })));
+        assert!(matches!(parse("define dup() {}"), Err(ParseError::ReservedName { .. })));
+        assert!(matches!(parse("define f() { define g() {} }"), Err(ParseError::UnsupportedInstruction { .. })));
+        assert!(matches!(parse("define f() { dup"), Err(ParseError::UnexpectedToken { .. })));
+        assert!(matches!(parse("g; define f() {}"), Err(ParseError::UnsupportedInstruction { .. })));
+    }
+
+    #[test]
+    fn test_parse_let() {
+        let instructions = parse(r#"
+            push b"walrus"; let preimage;
+            preimage; hash_sha256;
+            preimage; hash_sha256;
+            bytes_eq; assert_true;
+        "#).expect("failed to parse the input");
+        assert_eq!(vec![
+            Instruction::Push(Elem::Bytes(b"walrus".to_vec())),
+            Instruction::Restack(Restack::dup()),
+            Instruction::HashSha256,
+            Instruction::Restack(Restack::dig(1)),
+            Instruction::HashSha256,
+            Instruction::BytesEq,
+            Instruction::AssertTrue,
+        ], instructions.instructions);
+        let mut stack = Stack::new();
+        instructions.to_instrs().expect("failed to type instructions").run(&mut stack).expect("failed to run");
+        assert_eq!(vec![Elem::Bool(true)], stack.stack);
+
+        let instructions = parse(r#"
+            let x = push 1;
+            let y = push 2;
+            push 3;
+        "#).expect("failed to parse the input");
+        assert_eq!(vec![
+            Instruction::Push(Elem::Number(From::from(1u8))),
+            Instruction::Push(Elem::Number(From::from(2u8))),
+            Instruction::Push(Elem::Number(From::from(3u8))),
+            Instruction::Restack(Restack::dig(1)),
+            Instruction::Restack(Restack::drop()),
+            Instruction::Restack(Restack::dig(1)),
+            Instruction::Restack(Restack::drop()),
+        ], instructions.instructions);
+
+        let instructions = parse(r#"
+            define second(a, b) { a }
+            push "a"; push "b"; second;
+        "#).expect("failed to parse the input");
+        let mut stack = Stack::new();
+        instructions.to_instrs().expect("failed to type instructions").run(&mut stack).expect("failed to run");
+        assert_eq!(vec![Elem::String("a".to_string())], stack.stack);
+    }
+
+    #[test]
+    fn test_parse_let_dropped_at_end() {
+        let run = |source: &str| {
+            let mut stack = Stack::new();
+            parse(source).expect("failed to parse the input")
+                .to_instrs().expect("failed to type instructions")
+                .run(&mut stack).expect("failed to run");
+            stack.stack
+        };
+        assert_eq!(vec![Elem::Number(From::from(2u8))], run("push 1; let x; push 2;"));
+        assert_eq!(vec![Elem::Number(From::from(1u8)), Elem::Number(From::from(2u8))],
+                   run("push 1; let x; push 2; x;"));
+        assert_eq!(vec![Elem::Number(From::from(3u8))],
+                   run("push 3; push 1; let x; push 2; let y;"));
+        assert_eq!(vec![Elem::Number(From::from(2u8))],
+                   run("define f(x) { push 2; } push 1; f;"));
+    }
+
+    #[test]
+    fn test_parse_path() {
+        let instructions = parse(r#"
+            let vc;
+            vc.credentialSubject."@roles"[0]<str>;
+            vc["issuer"];
+        "#).expect("failed to parse the input");
+        assert_eq!(vec![
+            Instruction::Restack(Restack::dup()),
+            Instruction::Push(Elem::String("credentialSubject".to_string())),
+            Instruction::Lookup,
+            Instruction::UnpackJson(ElemSymbol::Object),
+            Instruction::Push(Elem::String("@roles".to_string())),
+            Instruction::Lookup,
+            Instruction::UnpackJson(ElemSymbol::Array),
+            Instruction::Push(Elem::Number(From::from(0u8))),
+            Instruction::Index,
+            Instruction::UnpackJson(ElemSymbol::String),
+            Instruction::Restack(Restack::dig(1)),
+            Instruction::Push(Elem::String("issuer".to_string())),
+            Instruction::Lookup,
+        ], instructions.instructions);
+
+        let instructions = parse(r#"
+            push {"a": [{"b": "c"}]};
+            let x;
+            x.a[0].b<String>;
+            push "c";
+            string_eq;
+            assert_true;
+        "#).expect("failed to parse the input");
+        let mut stack = Stack::new();
+        instructions.to_instrs().expect("failed to type instructions").run(&mut stack).expect("failed to run");
+        assert_eq!(vec![Elem::Bool(true)], stack.stack);
+
+        assert!(matches!(parse("let x; x.0"), Err(ParseError::UnexpectedToken { .. })));
+        assert!(matches!(parse("let x; x[-1]"), Err(ParseError::InvalidStackIx { .. })));
+        assert!(matches!(parse("let x; x[1"), Err(ParseError::UnexpectedToken { .. })));
+        assert!(matches!(parse("let x; x.a<Float>"), Err(ParseError::UnsupportedElemSymbol { .. })));
+    }
+
+    #[test]
+    fn test_parse_let_invalid() {
+        assert!(matches!(parse("push 1; let x; to_json; x"),
+                         Err(ParseError::UseAfterConsume { consumed: Span { start: 15, .. }, span: Span { start: 24, .. }, .. })));
+        assert!(matches!(parse("define f(x) { to_json; x }"),
+                         Err(ParseError::UseAfterConsume { .. })));
+        assert!(matches!(parse("let dup;"), Err(ParseError::ReservedName { .. })));
+        assert!(matches!(parse("define f(x, x) { drop 2 }"), Err(ParseError::DuplicateParam { .. })));
+        assert!(matches!(parse("let x = let y;"), Err(ParseError::UnsupportedInstruction { .. })));
+    }
+
+    #[quickcheck]
+    fn prop_parse_pretty(instructions: Instructions) -> bool {
+        parse(&format!("{}", instructions)).ok() == Some(instructions)
+    }
+
+    #[quickcheck]
+    fn prop_parse_literal(elem: Elem) -> bool {
+        elem.literal().parse::<Elem>().ok() == Some(elem)
+    }
+}
@@ -0,0 +1,166 @@
+use crate::stack::Stack;
+use crate::untyped_instruction::{Instruction, InstructionError};
+use crate::untyped_instructions::Instructions;
+use crate::typed_instruction::StackInstructionError;
+
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// A puzzle-style policy, split (like bitcoin's scriptSig/scriptPubKey) into:
+/// - setup: supplied by the presenter, and may only push literals
+/// - challenge: owned by the verifier, and run on the Stack left by setup
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Serialize, Deserialize)]
+pub struct Program {
+    /// Documentation for the program, one entry per line (from "///" comments)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub docs: Vec<String>,
+
+    /// Presenter-supplied Instructions, each of which must be a Push
+    pub setup: Instructions,
+
+    /// Verifier-owned Instructions
+    pub challenge: Instructions,
+}
+
+// Canonical cryptoscript source, accepted by parse_program:
+//
+// /// docs
+// setup {
+//     push ..;
+// }
+// challenge {
+//     ..;
+// }
+impl Display for Program {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        for doc in &self.docs {
+            if doc.is_empty() {
+                writeln!(f, "///")?;
+            } else {
+                writeln!(f, "/// {}", doc)?;
+            }
+        }
+        for (block, instructions) in [("setup", &self.setup), ("challenge", &self.challenge)] {
+            writeln!(f, "{} {{", block)?;
+            for instruction in &instructions.instructions {
+                writeln!(f, "    {};", instruction)?;
+            }
+            writeln!(f, "}}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Program {
+    /// Check that setup only pushes literals
+    pub fn check_setup(&self) -> Result<(), ProgramError> {
+        match self.setup.instructions.iter().enumerate().find(|(_, instruction)| !matches!(instruction, Instruction::Push(_))) {
+            Some((line_no, instruction)) => Err(ProgramError::SetupNotLiteral {
+                line_no: line_no,
+                instruction: instruction.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Run setup and then challenge on the given Stack, reporting which of
+    /// them failed
+    pub fn run(&self, stack: &mut Stack) -> Result<(), ProgramError> {
+        self.check_setup()?;
+        self.setup.clone().to_instrs()
+            .map_err(|e| ProgramError::SetupInstructionError(e))?
+            .run(stack)
+            .map_err(|e| ProgramError::SetupFailed(e))?;
+        self.challenge.clone().to_instrs()
+            .map_err(|e| ProgramError::ChallengeInstructionError(e))?
+            .run(stack)
+            .map_err(|e| ProgramError::ChallengeFailed(e))
+    }
+}
+
+/// Program::run errors
+#[derive(Clone, Debug, Error)]
+pub enum ProgramError {
+    /// Setup contains an Instruction other than Push
+    #[error("Program::run: setup may only push literals, but line {line_no} is: {instruction}")]
+    SetupNotLiteral {
+        /// Index of the Instruction in setup
+        line_no: usize,
+        /// The Instruction
+        instruction: Instruction,
+    },
+
+    /// Setup can't be converted to typed instructions
+    #[error("Program::run: invalid setup:\n{0}")]
+    SetupInstructionError(InstructionError),
+
+    /// Running setup failed
+    #[error("Program::run: setup failed:\n{0}")]
+    SetupFailed(StackInstructionError),
+
+    /// Challenge can't be converted to typed instructions
+    #[error("Program::run: invalid challenge:\n{0}")]
+    ChallengeInstructionError(InstructionError),
+
+    /// Running challenge failed
+    #[error("Program::run: challenge failed:\n{0}")]
+    ChallengeFailed(StackInstructionError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elem::Elem;
+    use crate::lexer::Span;
+    use crate::parse::{parse_program, ParseError};
+
+    const WALRUS: &str = r#"
+        /// Hash preimage puzzle
+        setup {
+            push b"I am the walrus.";
+        }
+        challenge {
+            check_preimage;
+            assert_true;
+        }
+        define check_preimage(preimage) {
+            hash_sha256;
+            push 0x475b03e74f7ee448273dbde5ab892746c7b23a2b4d050ccb7d9270b6fb152b72;
+            check_equal;
+        }
+    "#;
+
+    #[test]
+    fn test_program_run() {
+        let program = parse_program(WALRUS).expect("failed to parse the program");
+        assert_eq!(vec!["Hash preimage puzzle".to_string()], program.docs);
+        assert_eq!(vec![Instruction::Push(Elem::Bytes(b"I am the walrus.".to_vec()))], program.setup.instructions);
+        assert_eq!(4, program.challenge.instructions.len());
+        let mut stack = Stack::new();
+        program.run(&mut stack).expect("failed to run the program");
+        assert_eq!(vec![Elem::Bool(true)], stack.stack);
+
+        assert_eq!(Ok(program.clone()), parse_program(&format!("{}", program)).map_err(|e| e.to_string()));
+
+        let mut wrong_preimage = program.clone();
+        wrong_preimage.setup.instructions = vec![Instruction::Push(Elem::Bytes(b"I am the eggman.".to_vec()))];
+        assert!(matches!(wrong_preimage.run(&mut Stack::new()), Err(ProgramError::ChallengeFailed(_))));
+
+        let mut wrong_setup = program.clone();
+        wrong_setup.setup.instructions.push(Instruction::HashSha256);
+        assert!(matches!(wrong_setup.run(&mut Stack::new()), Err(ProgramError::SetupNotLiteral { line_no: 1, .. })));
+    }
+
+    #[test]
+    fn test_parse_program_invalid() {
+        assert!(matches!(parse_program("setup { push 1; dup } challenge {}"),
+                         Err(ParseError::SetupNotLiteral { span: Span { start: 16, .. } })));
+        assert!(matches!(parse_program("define one() { push 1 } setup { one } challenge {}"),
+                         Err(ParseError::SetupNotLiteral { .. })));
+        assert!(matches!(parse_program("challenge {} setup {}"), Err(ParseError::UnexpectedToken { .. })));
+        assert!(matches!(parse_program("setup {} challenge {} push 1;"), Err(ParseError::UnexpectedToken { .. })));
+    }
+}
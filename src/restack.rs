@@ -1,5 +1,8 @@
 use std::cmp;
+use std::fmt;
+use std::fmt::{Display, Formatter};
 
+use quickcheck::{Arbitrary, Gen};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -7,10 +10,6 @@ use thiserror::Error;
 /// Stack index
 pub type StackIx = usize;
 
-// TODO: pretty-printing?
-//     + REQUIRED: constant compile-time choice of manipulations
-//     + local: just print [x_old_stack_index_0, x_old_stack_index_1, ..]
-//     + global: keep track of stack indices (always possible?) and print where it's from???
 /// Stack manipulation:
 /// - All these stack manipulations:
 ///     + dig
@@ -155,6 +154,81 @@ impl Restack {
     }
 }
 
+// Formatting:
+// - Restack's matching dup, swap, drop, dup_n, drop_n, dig, or dug (in that
+//   order of preference) are printed using the corresponding mnemonic
+// - Otherwise, the Restack is printed as "restack depth [ix_0, ix_1, ..]"
+//
+// Results in e.g.
+// ```
+// dup
+// dig 4
+// restack 3 [0, 2]
+// ```
+impl Display for Restack {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        if *self == Self::dup() {
+            return write!(f, "dup");
+        }
+        if *self == Self::swap() {
+            return write!(f, "swap");
+        }
+        if *self == Self::drop() {
+            return write!(f, "drop");
+        }
+        if let Some(ix) = self.restack_depth.checked_sub(1) {
+            if *self == Self::dup_n(ix) {
+                return write!(f, "dup {}", ix);
+            }
+            if self.restack_vec.is_empty() {
+                return write!(f, "drop {}", self.restack_depth);
+            }
+            if *self == Self::dig(ix) {
+                return write!(f, "dig {}", ix);
+            }
+            if *self == Self::dug(ix) {
+                return write!(f, "dug {}", ix);
+            }
+        }
+        write!(f,
+               "restack {} [{}]",
+               self.restack_depth,
+               self.restack_vec
+                   .iter()
+                   .map(|x| x.to_string())
+                   .collect::<Vec<String>>()
+                   .join(", "))
+    }
+}
+
+/// Arbitrary Restack's always satisfy Restack::is_valid_depth
+impl Arbitrary for Restack {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let restack_depth = usize::arbitrary(g) % 8;
+        let restack_vec_len = if restack_depth == 0 { 0 } else { usize::arbitrary(g) % 8 };
+        Restack {
+            restack_depth: restack_depth,
+            restack_vec: (0..restack_vec_len).map(|_| usize::arbitrary(g) % restack_depth).collect(),
+        }
+    }
+
+    // Shrinks restack_depth down to the smallest depth covering restack_vec,
+    // then restack_vec itself (whose indices only ever decrease)
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let restack_depth = self.restack_depth;
+        let restack_vec = self.restack_vec.clone();
+        let min_depth = restack_vec.iter().max().map_or(0, |max_index| max_index + 1);
+        let shrunk_depths = (min_depth..restack_depth).map(move |shrunk_depth| Restack {
+            restack_depth: shrunk_depth,
+            restack_vec: restack_vec.clone(),
+        });
+        let shrunk_vecs = self.restack_vec.shrink().map(move |shrunk_vec| Restack {
+            restack_depth: restack_depth,
+            restack_vec: shrunk_vec,
+        });
+        Box::new(shrunk_depths.chain(shrunk_vecs))
+    }
+}
 
 #[derive(Clone, Copy, Debug, PartialEq, Error)]
 pub enum RestackError {
@@ -173,6 +247,7 @@ pub enum RestackError {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use quickcheck_macros::quickcheck;
 
     #[test]
     fn test_restack_id() {
@@ -232,6 +307,33 @@ mod tests {
         assert_eq!(Ok(example_stack_out), restack.run(&mut example_stack_in).map(|()| example_stack_in))
     }
 
+    #[test]
+    fn test_restack_display() {
+        assert_eq!("dup", format!("{}", Restack::dup()));
+        assert_eq!("dup 3", format!("{}", Restack::dup_n(3)));
+        assert_eq!("swap", format!("{}", Restack::swap()));
+        assert_eq!("drop", format!("{}", Restack::drop()));
+        assert_eq!("drop 3", format!("{}", Restack::drop_n(3)));
+        assert_eq!("dig 4", format!("{}", Restack::dig(4)));
+        assert_eq!("dug 2", format!("{}", Restack::dug(2)));
+        assert_eq!("restack 0 []", format!("{}", Restack::id()));
+        assert_eq!("restack 3 [0, 2]", format!("{}", Restack { restack_depth: 3, restack_vec: vec![0, 2] }));
+    }
+
+    #[test]
+    fn test_restack_shrink() {
+        let restack = Restack { restack_depth: 5, restack_vec: vec![2, 0, 2] };
+        let shrunk: Vec<Restack> = restack.shrink().collect();
+        assert_eq!(Some(&Restack { restack_depth: 3, restack_vec: vec![2, 0, 2] }), shrunk.first());
+        assert!(shrunk.contains(&Restack { restack_depth: 5, restack_vec: vec![] }), "{:?}", shrunk);
+        assert!(!shrunk.contains(&restack), "{:?}", shrunk);
+    }
+
+    #[quickcheck]
+    fn prop_restack_shrink_is_valid_depth(restack: Restack) -> bool {
+        restack.shrink().all(|shrunk| shrunk.is_valid_depth())
+    }
+
     #[test]
     fn test_restack_swap_twice_append() {
         let mut example_stack = vec![false, true];
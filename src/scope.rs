@@ -0,0 +1,158 @@
+use crate::lexer::Span;
+use crate::parse::ParseError;
+use crate::restack::Restack;
+use crate::typed_instruction::StackInstructionError;
+use crate::untyped_instruction::Instruction;
+use crate::untyped_instructions::Instructions;
+
+use std::collections::BTreeMap;
+
+/// Tracks which stack slots hold let-bound names (or define params), so that
+/// uses of those names can be lowered to Restack's.
+///
+/// Like Stack, the 0th slot is the top of the stack. Slots below the last one
+/// are unknown and unnamed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Scope {
+    /// The name bound to each known stack slot, if any
+    slots: Vec<Option<String>>,
+
+    /// Where each name that's no longer on the stack was consumed
+    consumed: BTreeMap<String, Span>,
+}
+
+impl Scope {
+    /// A Scope whose top slots are named by the given params, the last being
+    /// the top of the stack
+    pub(crate) fn new(params: &[String]) -> Self {
+        Scope {
+            slots: params.iter().rev().map(|param| Some(param.clone())).collect(),
+            consumed: BTreeMap::new(),
+        }
+    }
+
+    /// Is the name bound to any slot?
+    fn is_bound(&self, name: &str) -> bool {
+        self.slots.iter().any(|slot| slot.as_deref() == Some(name))
+    }
+
+    /// Record that the name was consumed at the given Span, unless it's still
+    /// bound to another slot (e.g. after a "dup")
+    fn consume(&mut self, name: String, span: Span) {
+        if !self.is_bound(&name) {
+            self.consumed.insert(name, span);
+        }
+    }
+
+    /// Bind the name to the top of the stack.
+    ///
+    /// Any slot it was previously bound to becomes unnamed, and any name
+    /// previously bound to the top slot is consumed.
+    pub(crate) fn bind(&mut self, name: &str, span: Span) {
+        for slot in self.slots.iter_mut() {
+            if slot.as_deref() == Some(name) {
+                *slot = None;
+            }
+        }
+        self.consumed.remove(name);
+        if self.slots.is_empty() {
+            self.slots.push(None);
+        }
+        if let Some(previous) = self.slots[0].replace(name.to_string()) {
+            self.consume(previous, span);
+        }
+    }
+
+    /// Lower a use of the name to the Restack's that bring its value to the top
+    /// of the stack, or None if it isn't a name in this Scope.
+    ///
+    /// The value is copied (dup_n) if the name is used again later, and moved
+    /// (dig) otherwise. Either way, the new top slot is unnamed.
+    pub(crate) fn use_name(&mut self, name: &str, used_later: bool, span: Span) -> Result<Option<Vec<Restack>>, ParseError> {
+        let ix = match self.slots.iter().position(|slot| slot.as_deref() == Some(name)) {
+            Some(ix) => ix,
+            None => match self.consumed.get(name) {
+                Some(consumed) => return Err(ParseError::UseAfterConsume {
+                    name: name.to_string(),
+                    consumed: *consumed,
+                    span: span,
+                }),
+                None => return Ok(None),
+            },
+        };
+        if used_later {
+            self.slots.insert(0, None);
+            Ok(Some(vec![Restack::dup_n(ix)]))
+        } else {
+            self.slots.remove(ix);
+            self.slots.insert(0, None);
+            self.consume(name.to_string(), span);
+            Ok(Some(if ix == 0 { vec![] } else { vec![Restack::dig(ix)] }))
+        }
+    }
+
+    /// Update the slots after running the given Instruction's, where names in
+    /// any slot they pop are consumed
+    pub(crate) fn run(&mut self, instructions: &[Instruction], span: Span) -> Result<(), ParseError> {
+        if self.slots.iter().all(|slot| slot.is_none()) {
+            self.slots.clear();
+            return Ok(());
+        }
+        if let [Instruction::Restack(restack)] = instructions {
+            let restack_depth = restack.restack_depth;
+            if self.slots.len() < restack_depth {
+                self.slots.resize(restack_depth, None);
+            }
+            let popped: Vec<Option<String>> = self.slots[..restack_depth].to_vec();
+            restack.run(&mut self.slots)
+                .map_err(|e| ParseError::LetTypeError {
+                    error: StackInstructionError::RestackError(e),
+                    span: span,
+                })?;
+            for name in popped.into_iter().flatten() {
+                self.consume(name, span);
+            }
+            return Ok(());
+        }
+
+        let instructions_type = Instructions {
+            docs: vec![],
+            instructions: instructions.to_vec(),
+        }.to_instrs()
+            .map_err(|e| ParseError::LetInstructionError {
+                error: e,
+                span: span,
+            })?
+            .type_of()
+            .map_err(|e| ParseError::LetTypeError {
+                error: e,
+                span: span,
+            })?;
+        let num_inputs = instructions_type.i_type.len().min(self.slots.len());
+        let popped: Vec<Option<String>> = self.slots.drain(..num_inputs).collect();
+        for name in popped.into_iter().flatten() {
+            self.consume(name, span);
+        }
+        self.slots.splice(0..0, instructions_type.o_type.iter().map(|_| None));
+        Ok(())
+    }
+
+    /// Restack's that drop every slot that's still named, i.e. at the end of a
+    /// program or define body, so that bound values never reach its final stack
+    /// or outputs
+    pub(crate) fn finish(mut self) -> Vec<Restack> {
+        let mut restacks = vec![];
+        while let Some(ix) = self.slots.iter().position(|slot| slot.is_some()) {
+            if ix == 0 {
+                let count = self.slots.iter().take_while(|slot| slot.is_some()).count();
+                self.slots.drain(..count);
+                restacks.push(Restack::drop_n(count));
+            } else {
+                self.slots.remove(ix);
+                restacks.push(Restack::dig(ix));
+                restacks.push(Restack::drop());
+            }
+        }
+        restacks
+    }
+}
@@ -0,0 +1,288 @@
+use crate::hash_function::HashFunction;
+
+use serde_json::{Map, Number, Value};
+use thiserror::Error;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+const PREAMBLE_SUFFIX: &str = " wants you to sign in with your Ethereum account:";
+
+/// Parse a Sign-In with Ethereum (EIP-4361) message into an Object with the
+/// keys domain, address, statement, uri, version, chainId, nonce, issuedAt,
+/// expirationTime, notBefore, requestId and resources, where optional fields
+/// are omitted when absent (scheme is included when present).
+///
+/// The address must be EIP-55 checksummed unless it's all one case, and the
+/// timestamps must be RFC 3339 date-times.
+pub fn parse_siwe(message: &str) -> Result<Map<String, Value>, SiweError> {
+    let mut lines = message.split('\n').enumerate();
+    let mut fields = Map::new();
+    let mut next_line = |expected: &'static str| lines.next()
+        .ok_or(SiweError::UnexpectedEnd {
+            expected: expected,
+        });
+
+    let (_, preamble) = next_line("preamble")?;
+    let authority = preamble.strip_suffix(PREAMBLE_SUFFIX)
+        .ok_or_else(|| SiweError::InvalidLine {
+            line_no: 0,
+            expected: "\"{domain} wants you to sign in with your Ethereum account:\"",
+            line: preamble.to_string(),
+        })?;
+    let domain = match authority.split_once("://") {
+        Some((scheme, domain)) => {
+            fields.insert("scheme".to_string(), Value::String(scheme.to_string()));
+            domain
+        },
+        None => authority,
+    };
+    if domain.is_empty() || domain.contains(char::is_whitespace) {
+        return Err(SiweError::InvalidField {
+            field: "domain",
+            value: domain.to_string(),
+        })
+    }
+    fields.insert("domain".to_string(), Value::String(domain.to_string()));
+
+    let (_, address) = next_line("address")?;
+    check_address(address)?;
+    fields.insert("address".to_string(), Value::String(address.to_string()));
+    expect_empty_line(next_line("empty line")?)?;
+
+    // The statement is optional, and older messages have an extra empty line
+    // when it's absent
+    let (mut line_no, mut line) = next_line("URI")?;
+    if !line.starts_with("URI: ") {
+        if !line.is_empty() {
+            fields.insert("statement".to_string(), Value::String(line.to_string()));
+            expect_empty_line(next_line("empty line")?)?;
+        }
+        let (next_line_no, next) = next_line("URI")?;
+        line_no = next_line_no;
+        line = next;
+    }
+
+    let tagged = |line_no: usize, line: &str, tag: &'static str, field: &'static str| -> Result<String, SiweError> {
+        line.strip_prefix(tag)
+            .map(|value| value.to_string())
+            .ok_or_else(|| SiweError::InvalidLine {
+                line_no: line_no,
+                expected: field,
+                line: line.to_string(),
+            })
+    };
+    let uri = tagged(line_no, line, "URI: ", "uri")?;
+    fields.insert("uri".to_string(), Value::String(uri));
+
+    let (line_no, line) = next_line("version")?;
+    let version = tagged(line_no, line, "Version: ", "version")?;
+    if version != "1" {
+        return Err(SiweError::InvalidField {
+            field: "version",
+            value: version,
+        })
+    }
+    fields.insert("version".to_string(), Value::String(version));
+
+    let (line_no, line) = next_line("chainId")?;
+    let chain_id = tagged(line_no, line, "Chain ID: ", "chainId")?;
+    let chain_id_number = chain_id.parse::<u64>()
+        .map_err(|_| SiweError::InvalidField {
+            field: "chainId",
+            value: chain_id.clone(),
+        })?;
+    fields.insert("chainId".to_string(), Value::Number(Number::from(chain_id_number)));
+
+    let (line_no, line) = next_line("nonce")?;
+    let nonce = tagged(line_no, line, "Nonce: ", "nonce")?;
+    if nonce.len() < 8 || !nonce.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(SiweError::InvalidField {
+            field: "nonce",
+            value: nonce,
+        })
+    }
+    fields.insert("nonce".to_string(), Value::String(nonce));
+
+    let (line_no, line) = next_line("issuedAt")?;
+    let issued_at = tagged(line_no, line, "Issued At: ", "issuedAt")?;
+    fields.insert("issuedAt".to_string(), Value::String(check_date_time("issuedAt", issued_at)?));
+
+    let optional_fields = [
+        ("Expiration Time: ", "expirationTime"),
+        ("Not Before: ", "notBefore"),
+        ("Request ID: ", "requestId"),
+    ];
+    let mut remaining = lines.peekable();
+    for (tag, field) in optional_fields.iter().copied() {
+        if let Some((_, line)) = remaining.next_if(|(_, line)| line.starts_with(tag)) {
+            let value = line[tag.len()..].to_string();
+            let value = if field == "requestId" { value } else { check_date_time(field, value)? };
+            fields.insert(field.to_string(), Value::String(value));
+        }
+    }
+    if remaining.next_if(|(_, line)| *line == "Resources:").is_some() {
+        let mut resources = vec![];
+        while let Some((_, line)) = remaining.next_if(|(_, line)| line.starts_with("- ")) {
+            resources.push(Value::String(line[2..].to_string()));
+        }
+        fields.insert("resources".to_string(), Value::Array(resources));
+    }
+    match remaining.next() {
+        None => Ok(fields),
+        Some((line_no, line)) => Err(SiweError::UnexpectedLine {
+            line_no: line_no,
+            line: line.to_string(),
+        }),
+    }
+}
+
+fn expect_empty_line((line_no, line): (usize, &str)) -> Result<(), SiweError> {
+    if line.is_empty() {
+        Ok(())
+    } else {
+        Err(SiweError::InvalidLine {
+            line_no: line_no,
+            expected: "empty line",
+            line: line.to_string(),
+        })
+    }
+}
+
+fn check_date_time(field: &'static str, value: String) -> Result<String, SiweError> {
+    match OffsetDateTime::parse(&value, &Rfc3339) {
+        Ok(_) => Ok(value),
+        Err(_) => Err(SiweError::InvalidField {
+            field: field,
+            value: value,
+        }),
+    }
+}
+
+/// Check that the address is "0x" followed by 40 hex digits, which must be
+/// EIP-55 checksummed unless they're all one case
+fn check_address(address: &str) -> Result<(), SiweError> {
+    let invalid_address = || SiweError::InvalidField {
+        field: "address",
+        value: address.to_string(),
+    };
+    let digits = address.strip_prefix("0x").ok_or_else(invalid_address)?;
+    if digits.len() != 40 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid_address())
+    }
+    if digits.chars().any(|c| c.is_ascii_lowercase()) && digits.chars().any(|c| c.is_ascii_uppercase()) {
+        let hash = HashFunction::Keccak256.digest(digits.to_ascii_lowercase().as_bytes());
+        let is_checksummed = digits.chars().enumerate().all(|(i, c)| {
+            let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+            !c.is_ascii_alphabetic() || c.is_ascii_uppercase() == (nibble >= 8)
+        });
+        if !is_checksummed {
+            return Err(SiweError::InvalidChecksum {
+                address: address.to_string(),
+            })
+        }
+    }
+    Ok(())
+}
+
+/// EIP-4361 message parsing errors
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum SiweError {
+    /// The message ended early
+    #[error("SIWE message ended early, expected: {expected}")]
+    UnexpectedEnd {
+        /// The expected line
+        expected: &'static str,
+    },
+
+    /// A line is malformed
+    #[error("SIWE message line {line_no} is malformed, expected {expected}: {line:?}")]
+    InvalidLine {
+        /// The (0-indexed) line number
+        line_no: usize,
+        /// The expected line or field
+        expected: &'static str,
+        /// The line
+        line: String,
+    },
+
+    /// A field's value is malformed
+    #[error("SIWE message has an invalid {field}: {value:?}")]
+    InvalidField {
+        /// The field's name
+        field: &'static str,
+        /// The field's value
+        value: String,
+    },
+
+    /// A mixed-case address has an invalid EIP-55 checksum
+    #[error("SIWE message address has an invalid EIP-55 checksum: {address}")]
+    InvalidChecksum {
+        /// The address
+        address: String,
+    },
+
+    /// A line follows the last field
+    #[error("SIWE message line {line_no} is unexpected: {line:?}")]
+    UnexpectedLine {
+        /// The (0-indexed) line number
+        line_no: usize,
+        /// The line
+        line: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const MESSAGE: &str = "service.org wants you to sign in with your Ethereum account:\n0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2\n\nI accept the ServiceOrg Terms of Service: https://service.org/tos\n\nURI: https://service.org/login\nVersion: 1\nChain ID: 1\nNonce: 32891757\nIssued At: 2021-09-30T16:25:24.000Z\nResources:\n- ipfs://Qme7ss3ARVgxv6rXqVPiikMJ8u2NLgmgszg13pYrDKEoiu\n- https://example.com/my-web2-claim.json";
+
+    #[test]
+    fn test_parse_siwe() {
+        assert_eq!(json!({
+            "domain": "service.org",
+            "address": "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2",
+            "statement": "I accept the ServiceOrg Terms of Service: https://service.org/tos",
+            "uri": "https://service.org/login",
+            "version": "1",
+            "chainId": 1,
+            "nonce": "32891757",
+            "issuedAt": "2021-09-30T16:25:24.000Z",
+            "resources": ["ipfs://Qme7ss3ARVgxv6rXqVPiikMJ8u2NLgmgszg13pYrDKEoiu", "https://example.com/my-web2-claim.json"],
+        }).as_object(), parse_siwe(MESSAGE).ok().as_ref());
+
+        let minimal = "https://localhost:4361 wants you to sign in with your Ethereum account:\n0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed\n\nURI: https://localhost/login\nVersion: 1\nChain ID: 10\nNonce: abcdef0123\nIssued At: 2022-03-17T12:45:13Z\nExpiration Time: 2022-03-18T12:45:13Z\nRequest ID: 42";
+        assert_eq!(json!({
+            "scheme": "https",
+            "domain": "localhost:4361",
+            "address": "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "uri": "https://localhost/login",
+            "version": "1",
+            "chainId": 10,
+            "nonce": "abcdef0123",
+            "issuedAt": "2022-03-17T12:45:13Z",
+            "expirationTime": "2022-03-18T12:45:13Z",
+            "requestId": "42",
+        }).as_object(), parse_siwe(minimal).ok().as_ref());
+        assert!(parse_siwe(&minimal.replace("\n\nURI", "\n\n\nURI")).is_ok());
+    }
+
+    #[test]
+    fn test_parse_siwe_invalid() {
+        assert!(matches!(parse_siwe(&MESSAGE.replace("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2", "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD")),
+                         Err(SiweError::InvalidChecksum { .. })));
+        assert!(matches!(parse_siwe(&MESSAGE.replace("Version: 1", "Version: 2")),
+                         Err(SiweError::InvalidField { field: "version", .. })));
+        assert!(matches!(parse_siwe(&MESSAGE.replace("Nonce: 32891757", "Nonce: 1234")),
+                         Err(SiweError::InvalidField { field: "nonce", .. })));
+        assert!(matches!(parse_siwe(&MESSAGE.replace("2021-09-30T16:25:24.000Z", "yesterday")),
+                         Err(SiweError::InvalidField { field: "issuedAt", .. })));
+        assert!(matches!(parse_siwe(&MESSAGE.replace("\nChain ID: 1", "")),
+                         Err(SiweError::InvalidLine { line_no: 7, expected: "chainId", .. })));
+        assert!(matches!(parse_siwe(&format!("{}\nextra", MESSAGE)),
+                         Err(SiweError::UnexpectedLine { line_no: 13, .. })));
+        assert!(matches!(parse_siwe(MESSAGE.split("\nVersion").next().expect("split")),
+                         Err(SiweError::UnexpectedEnd { expected: "version" })));
+    }
+}
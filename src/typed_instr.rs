@@ -1,10 +1,24 @@
-use crate::elem::ElemSymbol;
+use crate::elem::{Elem, ElemSymbol};
 use crate::restack::Restack;
 use crate::untyped_instruction::{Instruction, InstructionError};
 use crate::typed_instruction::{IsStackInstruction, StackInstructionError};
 use crate::typed_instructions::{AssertTrue, Lookup, Concat, Slice, Push,
     StringEq, BytesEq, ToJson, Index, CheckLe, CheckLt, CheckEq, HashSha256,
-    StringToBytes, UnpackJson};
+    StringToBytes, UnpackJson, AssertEq, AssertNe, AssertLt, AssertLe, AssertGt,
+    AssertGe, AssertMember};
+use crate::typed_instructions_crypto::{VerifySecp256k1, EcRecover, HashEip191,
+    VerifyEd25519, VerifyP256, WebAuthnPayload, HashSha3_256, HashKeccak256,
+    HashSha512, HashBlake2b256, HashBlake3, HashRipemd160, HmacSha256,
+    ConstantTimeBytesEq, VerifyMerkleProof, VerifyBls12381,
+    FastAggregateVerifyBls12381, CheckMultiSig};
+use crate::typed_instructions_siwe::ParseSiwe;
+use crate::typed_instructions_encoding::{DecodeMultibase, MulticodecCode,
+    StripMulticodec, VerifyMultihash};
+use crate::typed_instructions_jws::{JwsHeader, JwsPayload, JwsSignature, VerifyJws,
+    JwtExp, JwtNbf};
+use crate::typed_instructions_vc::{ValidateVc, ValidateVp, VerifyJwtVc, VerifyJwtVp,
+    VpSigner};
+use crate::typed_instructions_did::{ResolveDid, CheckDidDocument, VerificationMethod};
 
 use std::marker::PhantomData;
 use std::fmt::Debug;
@@ -37,7 +51,7 @@ impl Instruction {
     /// applied to an ElemSymbol that doesn't represent valid JSON
     pub fn to_instr(self) -> Result<Instr, InstructionError> {
         match self {
-            Self::Push(elem) => Ok(Instr::Instr(Arc::new(Push { push: elem }))),
+            Self::Push(elem) => Ok(elem.push_instr()),
             Self::Restack(restack) => Ok(Instr::Restack(restack.clone())),
             Self::HashSha256 => Ok(Instr::Instr(Arc::new(HashSha256 {}))),
             Self::CheckLe => Ok(Instr::Instr(Arc::new(CheckLe {}))),
@@ -65,7 +79,72 @@ impl Instruction {
                 }
             },
             Self::StringToBytes => Ok(Instr::Instr(Arc::new(StringToBytes {}))),
+            Self::AssertEq => Ok(Instr::Instr(Arc::new(AssertEq {}))),
+            Self::AssertNe => Ok(Instr::Instr(Arc::new(AssertNe {}))),
+            Self::AssertLt => Ok(Instr::Instr(Arc::new(AssertLt {}))),
+            Self::AssertLe => Ok(Instr::Instr(Arc::new(AssertLe {}))),
+            Self::AssertGt => Ok(Instr::Instr(Arc::new(AssertGt {}))),
+            Self::AssertGe => Ok(Instr::Instr(Arc::new(AssertGe {}))),
+            Self::AssertMember => Ok(Instr::Instr(Arc::new(AssertMember {}))),
+            Self::VerifySecp256k1 => Ok(Instr::Instr(Arc::new(VerifySecp256k1 {}))),
+            Self::EcRecover => Ok(Instr::Instr(Arc::new(EcRecover {}))),
+            Self::HashEip191 => Ok(Instr::Instr(Arc::new(HashEip191 {}))),
+            Self::VerifyEd25519 => Ok(Instr::Instr(Arc::new(VerifyEd25519 {}))),
+            Self::VerifyP256 => Ok(Instr::Instr(Arc::new(VerifyP256 {}))),
+            Self::WebAuthnPayload => Ok(Instr::Instr(Arc::new(WebAuthnPayload {}))),
+            Self::HashSha3_256 => Ok(Instr::Instr(Arc::new(HashSha3_256 {}))),
+            Self::HashKeccak256 => Ok(Instr::Instr(Arc::new(HashKeccak256 {}))),
+            Self::HashSha512 => Ok(Instr::Instr(Arc::new(HashSha512 {}))),
+            Self::HashBlake2b256 => Ok(Instr::Instr(Arc::new(HashBlake2b256 {}))),
+            Self::HashBlake3 => Ok(Instr::Instr(Arc::new(HashBlake3 {}))),
+            Self::HashRipemd160 => Ok(Instr::Instr(Arc::new(HashRipemd160 {}))),
+            Self::HmacSha256 => Ok(Instr::Instr(Arc::new(HmacSha256 {}))),
+            Self::ConstantTimeBytesEq => Ok(Instr::Instr(Arc::new(ConstantTimeBytesEq {}))),
+            Self::VerifyMerkleProof(hash_function, pairing) => Ok(Instr::Instr(Arc::new(VerifyMerkleProof {
+                hash_function: hash_function,
+                pairing: pairing,
+            }))),
+            Self::VerifyBls12381 => Ok(Instr::Instr(Arc::new(VerifyBls12381 {}))),
+            Self::FastAggregateVerifyBls12381 => Ok(Instr::Instr(Arc::new(FastAggregateVerifyBls12381 {}))),
+            Self::CheckMultiSig(key_type) => Ok(Instr::Instr(Arc::new(CheckMultiSig {
+                key_type: key_type,
+            }))),
+            Self::DecodeMultibase => Ok(Instr::Instr(Arc::new(DecodeMultibase {}))),
+            Self::MulticodecCode => Ok(Instr::Instr(Arc::new(MulticodecCode {}))),
+            Self::StripMulticodec => Ok(Instr::Instr(Arc::new(StripMulticodec {}))),
+            Self::VerifyMultihash => Ok(Instr::Instr(Arc::new(VerifyMultihash {}))),
+            Self::JwsHeader => Ok(Instr::Instr(Arc::new(JwsHeader {}))),
+            Self::JwsPayload => Ok(Instr::Instr(Arc::new(JwsPayload {}))),
+            Self::JwsSignature => Ok(Instr::Instr(Arc::new(JwsSignature {}))),
+            Self::VerifyJws => Ok(Instr::Instr(Arc::new(VerifyJws {}))),
+            Self::JwtExp => Ok(Instr::Instr(Arc::new(JwtExp {}))),
+            Self::JwtNbf => Ok(Instr::Instr(Arc::new(JwtNbf {}))),
+            Self::ValidateVc => Ok(Instr::Instr(Arc::new(ValidateVc {}))),
+            Self::ValidateVp => Ok(Instr::Instr(Arc::new(ValidateVp {}))),
+            Self::VerifyJwtVc => Ok(Instr::Instr(Arc::new(VerifyJwtVc {}))),
+            Self::VerifyJwtVp => Ok(Instr::Instr(Arc::new(VerifyJwtVp {}))),
+            Self::VpSigner => Ok(Instr::Instr(Arc::new(VpSigner {}))),
+            Self::ParseSiwe => Ok(Instr::Instr(Arc::new(ParseSiwe {}))),
+            Self::ResolveDid => Ok(Instr::Instr(Arc::new(ResolveDid {}))),
+            Self::CheckDidDocument => Ok(Instr::Instr(Arc::new(CheckDidDocument {}))),
+            Self::VerificationMethod => Ok(Instr::Instr(Arc::new(VerificationMethod {}))),
         }
     }
 }
 
+impl Elem {
+    /// Push this Elem, typed by its ElemSymbol so that the resulting Instr
+    /// has a monomorphic output type
+    fn push_instr(self) -> Instr {
+        match self {
+            Self::Unit => Instr::Instr(Arc::new(Push { push: () })),
+            Self::Bool(x) => Instr::Instr(Arc::new(Push { push: x })),
+            Self::Number(x) => Instr::Instr(Arc::new(Push { push: x })),
+            Self::Bytes(x) => Instr::Instr(Arc::new(Push { push: x })),
+            Self::String(x) => Instr::Instr(Arc::new(Push { push: x })),
+            Self::Array(x) => Instr::Instr(Arc::new(Push { push: x })),
+            Self::Object(x) => Instr::Instr(Arc::new(Push { push: x })),
+            Self::Json(x) => Instr::Instr(Arc::new(Push { push: x })),
+        }
+    }
+}
@@ -5,6 +5,7 @@ use crate::restack::Restack;
 use crate::elems::ElemsPopError;
 use crate::typed_instruction::{IsStackInstruction, StackInstructionError};
 use crate::typed_instr::Instr;
+use crate::types::Type;
 
 use std::fmt::Debug;
 use std::sync::Arc;
@@ -50,6 +51,23 @@ impl Instrs {
         Ok(())
     }
 
+    /// The Type of the whole list of Instr's, composed from the Type of each
+    /// Instr (or Restack) in order
+    pub fn type_of(&self) -> Result<Type, StackInstructionError> {
+        let mut instrs_type = Type::id();
+        for (line_no, instr_or_restack) in (&self.instrs).into_iter().enumerate() {
+            let instr_type = match instr_or_restack {
+                Instr::Instr(instr) => instr.type_of()
+                    .map_err(|e| StackInstructionError::ElemsPopError(e))?,
+                Instr::Restack(restack) => restack.type_of(From::from(line_no))
+                    .map_err(|e| StackInstructionError::RestackError(e))?,
+            };
+            instrs_type = instr_type.compose(instrs_type)
+                .map_err(|e| StackInstructionError::TypeError(e))?;
+        }
+        Ok(instrs_type)
+    }
+
     /// Assuming an input stack of [Json, Json, ..] (num_input_json count),
     /// what's the monomorphic type of Self?
     pub fn type_of_mono(&self, num_input_json: usize) -> Result<StackType, StackInstructionError> {
@@ -131,3 +149,42 @@ impl Instrs {
 }
 
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    fn io_counts(source: &str) -> (usize, usize) {
+        let instrs: Instrs = parse(source).expect("failed to parse")
+            .to_instrs().expect("failed to type instructions");
+        let instrs_type = instrs.type_of().expect("failed to type check");
+        (instrs_type.i_type.len(), instrs_type.o_type.len())
+    }
+
+    // ReturnSingleton's with N inputs used to be typed with N - 1 inputs, so
+    // e.g. hash_sha256 appeared to leave its input on the stack
+    #[test]
+    fn test_type_of_return_singleton_inputs() {
+        assert_eq!((1, 1), io_counts("hash_sha256;"));
+        assert_eq!((0, 1), io_counts("push 0x00; hash_sha256;"));
+    }
+
+    // Push used to be typed as Push<Elem>, i.e. as pushing any ElemSymbol, so
+    // that a literal of the wrong type passed the type checker
+    #[test]
+    fn test_type_of_push() {
+        let instrs: Instrs = parse(r#"push 1; push "a"; string_eq;"#).expect("failed to parse")
+            .to_instrs().expect("failed to type instructions");
+        assert!(matches!(instrs.type_of(), Err(StackInstructionError::TypeError(_))));
+        assert_eq!((0, 1), io_counts(r#"push "1"; push "a"; string_eq;"#));
+    }
+
+    // Type::compose used to rename each unified TypeId only in the earlier
+    // Type's outputs, so a TypeId repeated by e.g. dup was left behind in the
+    // other vectors and "dup; swap;" failed to type check
+    #[test]
+    fn test_type_of_repeated_type_id() {
+        assert_eq!((1, 2), io_counts("dup; swap;"));
+        assert_eq!((0, 1), io_counts(r#"push "a"; dup; swap; string_eq;"#));
+    }
+}
@@ -91,7 +91,7 @@ where
         let stack_input = &IsList::pop(PhantomData::<<T as IsInstructionT>::IO>, stack)
             .map_err(|e| StackInstructionError::ElemsPopError(e))?;
         self.run(stack_input)
-            .map_err(|e| StackInstructionError::RawStackInstructionError(format!("{:?}", e)))?;
+            .map_err(|e| StackInstructionError::RawStackInstructionError(format!("{}", e)))?;
         let output_value = stack_input
             .returning()
             .ok_or_else(|| StackInstructionError::MissingOutput {
@@ -600,7 +600,7 @@ impl IsInstructionT for StringToBytes {
 /// input: [x: T, y: T]
 /// output: [x <= y : bool]
 ///
-/// false if incomparable
+/// false if incomparable. Numbers are compared by value, exactly for integers
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct CheckLe {}
 #[derive(Clone, Debug, PartialEq, Eq, Error)]
@@ -647,7 +647,7 @@ impl IsInstructionT for CheckLe {
 /// input: [x: T, y: T]
 /// output: [x < y : bool]
 ///
-/// false if incomparable
+/// false if incomparable. Numbers are compared by value, exactly for integers
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct CheckLt {}
 #[derive(Clone, Debug, PartialEq, Eq, Error)]
@@ -790,3 +790,389 @@ impl IsInstructionT for BytesEq {
     }
 }
 
+
+/// Error from AssertEq, AssertNe, AssertLt, AssertLe, AssertGt, or AssertGe,
+/// including both operands
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum AssertCmpError {
+    /// The operands were compared, but the assertion didn't hold
+    #[error("{instruction}: expected {} {relation} {}", .lhs.literal(), .rhs.literal())]
+    Failed {
+        /// Name of the instruction
+        instruction: &'static str,
+        /// The asserted relation, e.g. "<="
+        relation: &'static str,
+        /// The left-hand operand, i.e. the deeper stack element
+        lhs: Elem,
+        /// The right-hand operand, i.e. the top stack element
+        rhs: Elem,
+    },
+
+    /// The operands can't be ordered, e.g. two distinct Array's
+    #[error("{instruction} applied to incomparable elements: {}, {}", .lhs.literal(), .rhs.literal())]
+    Incomparable {
+        /// Name of the instruction
+        instruction: &'static str,
+        /// The left-hand operand, i.e. the deeper stack element
+        lhs: Elem,
+        /// The right-hand operand, i.e. the top stack element
+        rhs: Elem,
+    },
+}
+
+/// Order lhs and rhs, failing with AssertCmpError unless the resulting
+/// Ordering is one of the expected ones
+fn assert_cmp(instruction: &'static str,
+              relation: &'static str,
+              expected: &[cmp::Ordering],
+              lhs: Elem,
+              rhs: Elem) -> Result<(), AssertCmpError> {
+    match lhs.partial_cmp(&rhs) {
+        None => Err(AssertCmpError::Incomparable {
+            instruction: instruction,
+            lhs: lhs,
+            rhs: rhs,
+        }),
+        Some(ordering) if expected.contains(&ordering) => Ok(()),
+        Some(_) => Err(AssertCmpError::Failed {
+            instruction: instruction,
+            relation: relation,
+            lhs: lhs,
+            rhs: rhs,
+        }),
+    }
+}
+
+/// forall T,
+/// input: [y: T, x: T]
+/// output: [true: bool]
+///
+/// Fails unless x == y, i.e. "push x; push y; assert_equal" succeeds iff x == y
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AssertEq {}
+
+impl IsInstructionT for AssertEq {
+    type IO = ConsOut<ReturnSingleton<bool, U0>, Cons<AllElems<U2>, Nil>>;
+    type Error = AssertCmpError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::AssertEq)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "assert_equal".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().untyped();
+        let rhs = array[0].clone();
+        let lhs = array[1].clone();
+        if lhs == rhs {
+            returning.returning(true);
+            Ok(())
+        } else {
+            Err(AssertCmpError::Failed {
+                instruction: "assert_equal",
+                relation: "==",
+                lhs: lhs,
+                rhs: rhs,
+            })
+        }
+    }
+}
+
+/// forall T,
+/// input: [y: T, x: T]
+/// output: [true: bool]
+///
+/// Fails unless x != y
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AssertNe {}
+
+impl IsInstructionT for AssertNe {
+    type IO = ConsOut<ReturnSingleton<bool, U0>, Cons<AllElems<U2>, Nil>>;
+    type Error = AssertCmpError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::AssertNe)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "assert_not_equal".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().untyped();
+        let rhs = array[0].clone();
+        let lhs = array[1].clone();
+        if lhs != rhs {
+            returning.returning(true);
+            Ok(())
+        } else {
+            Err(AssertCmpError::Failed {
+                instruction: "assert_not_equal",
+                relation: "!=",
+                lhs: lhs,
+                rhs: rhs,
+            })
+        }
+    }
+}
+
+/// forall T,
+/// input: [y: T, x: T]
+/// output: [true: bool]
+///
+/// Fails unless x < y, i.e. "push x; push y; assert_lt" succeeds iff x < y
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AssertLt {}
+
+impl IsInstructionT for AssertLt {
+    type IO = ConsOut<ReturnSingleton<bool, U0>, Cons<AllElems<U2>, Nil>>;
+    type Error = AssertCmpError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::AssertLt)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "assert_lt".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().untyped();
+        assert_cmp("assert_lt", "<", &[cmp::Ordering::Less], array[1].clone(), array[0].clone())?;
+        returning.returning(true);
+        Ok(())
+    }
+}
+
+/// forall T,
+/// input: [y: T, x: T]
+/// output: [true: bool]
+///
+/// Fails unless x <= y
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AssertLe {}
+
+impl IsInstructionT for AssertLe {
+    type IO = ConsOut<ReturnSingleton<bool, U0>, Cons<AllElems<U2>, Nil>>;
+    type Error = AssertCmpError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::AssertLe)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "assert_le".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().untyped();
+        assert_cmp("assert_le", "<=", &[cmp::Ordering::Less, cmp::Ordering::Equal], array[1].clone(), array[0].clone())?;
+        returning.returning(true);
+        Ok(())
+    }
+}
+
+/// forall T,
+/// input: [y: T, x: T]
+/// output: [true: bool]
+///
+/// Fails unless x > y
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AssertGt {}
+
+impl IsInstructionT for AssertGt {
+    type IO = ConsOut<ReturnSingleton<bool, U0>, Cons<AllElems<U2>, Nil>>;
+    type Error = AssertCmpError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::AssertGt)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "assert_gt".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().untyped();
+        assert_cmp("assert_gt", ">", &[cmp::Ordering::Greater], array[1].clone(), array[0].clone())?;
+        returning.returning(true);
+        Ok(())
+    }
+}
+
+/// forall T,
+/// input: [y: T, x: T]
+/// output: [true: bool]
+///
+/// Fails unless x >= y
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AssertGe {}
+
+impl IsInstructionT for AssertGe {
+    type IO = ConsOut<ReturnSingleton<bool, U0>, Cons<AllElems<U2>, Nil>>;
+    type Error = AssertCmpError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::AssertGe)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "assert_ge".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().untyped();
+        assert_cmp("assert_ge", ">=", &[cmp::Ordering::Greater, cmp::Ordering::Equal], array[1].clone(), array[0].clone())?;
+        returning.returning(true);
+        Ok(())
+    }
+}
+
+/// forall T,
+/// input: [xs: Vec<Value>, x: T]
+/// output: [true: bool]
+///
+/// Fails unless x, as JSON, is equal to some element of xs, i.e.
+/// "push x; push [a, b, c]; assert_member" succeeds iff x is one of a, b, c.
+/// Bytes are never members.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AssertMember {}
+
+/// Error from AssertMember, including both operands
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+#[error("assert_member: {} is not one of {}", .elem.literal(), Value::Array(.array.clone()))]
+pub struct AssertMemberError {
+    /// The Elem
+    elem: Elem,
+    /// The Array
+    array: Vec<Value>,
+}
+
+impl IsInstructionT for AssertMember {
+    type IO = ConsOut<ReturnSingleton<bool, U0>,
+                    Cons<Singleton<Vec<Value>, U1>,
+                    Cons<AllElems<U1>, Nil>>>;
+    type Error = AssertMemberError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::AssertMember)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "assert_member".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array[0].clone();
+        let elem = x.clone().tl().tl().hd().untyped()[0].clone();
+        let value = match elem.clone() {
+            Elem::Unit => Some(Value::Null),
+            Elem::Bool(x) => Some(Value::Bool(x)),
+            Elem::Number(x) => Some(Value::Number(x)),
+            Elem::Bytes(_) => None,
+            Elem::String(x) => Some(Value::String(x)),
+            Elem::Array(x) => Some(Value::Array(x)),
+            Elem::Object(x) => Some(Value::Object(x)),
+            Elem::Json(x) => Some(x),
+        };
+        match value {
+            Some(value) if array.contains(&value) => {
+                returning.returning(true);
+                Ok(())
+            },
+            _ => Err(AssertMemberError {
+                elem: elem,
+                array: array,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+    use crate::stack::Stack;
+
+    fn run(source: &str) -> Result<Stack, StackInstructionError> {
+        let mut stack = Stack::new();
+        parse(source)
+            .expect("failed to parse the input")
+            .to_instrs()
+            .expect("failed to type instructions")
+            .run(&mut stack)?;
+        Ok(stack)
+    }
+
+    #[test]
+    fn test_assert_cmp() {
+        for source in [
+            "push 2; push 2; assert_equal",
+            r#"push "a"; push "b"; assert_not_equal"#,
+            "push 9; push 10; assert_lt",
+            "push 10; push 10; assert_le",
+            "push 10; push 9; assert_gt",
+            "push 2; push 2; assert_gte",
+            "push 0x01; push 0x02; assert_lt",
+        ] {
+            assert_eq!(vec![Elem::Bool(true)], run(source).expect(source).stack, "{}", source);
+        }
+
+        let error = run("push 1; push 2; assert_ge").expect_err("1 >= 2 should fail");
+        assert!(error.to_string().contains("assert_ge: expected 1 >= 2"), "{}", error);
+        let error = run(r#"push "a"; push "b"; assert_equal"#).expect_err("a == b should fail");
+        assert!(error.to_string().contains(r#"assert_equal: expected "a" == "b""#), "{}", error);
+        let error = run("push [1]; push [2]; assert_lt").expect_err("arrays are incomparable");
+        assert!(error.to_string().contains("incomparable"), "{}", error);
+    }
+
+    // Numbers used to be compared by their decimal strings, e.g. "10" < "9".
+    // "push x; push y; check_lt" checks y < x
+    #[test]
+    fn test_check_cmp_numbers() {
+        for (source, result) in [
+            ("push 10; push 9; check_lt", true),
+            ("push 9; push 10; check_lt", false),
+            ("push 10; push 9; check_le", true),
+            ("push 9.5; push 10; check_le", false),
+            ("push 2; push 2; check_le", true),
+        ] {
+            assert_eq!(vec![Elem::Bool(result)], run(source).expect(source).stack, "{}", source);
+        }
+    }
+
+    // Instruction errors used to be formatted with Debug, e.g. "AssertTrueError",
+    // rather than with their Display messages
+    #[test]
+    fn test_instruction_error_display() {
+        assert_eq!("AssertTrueError", format!("{:?}", AssertTrueError {}));
+        let error = run("push false; assert_true").expect_err("assert_true should fail");
+        assert!(error.to_string().contains("RawStackInstructionError:\nAssertTrue: found false"), "{}", error);
+        assert!(!error.to_string().contains("AssertTrueError"), "{}", error);
+    }
+
+    #[test]
+    fn test_assert_member() {
+        for source in [
+            r#"push "did:web:example.com"; push ["did:key:z6Mk", "did:web:example.com"]; assert_elem"#,
+            r#"push 2; push [1, 2, 3]; assert_member"#,
+            r#"push json {"a": null}; push [{"a": null}]; assert_member"#,
+        ] {
+            assert_eq!(vec![Elem::Bool(true)], run(source).expect(source).stack, "{}", source);
+        }
+
+        let error = run(r#"push "c"; push ["a", "b"]; assert_member"#).expect_err("c is not a member");
+        assert!(error.to_string().contains(r#"assert_member: "c" is not one of ["a","b"]"#), "{}", error);
+        assert!(run(r#"push 0x00; push [0]; assert_member"#).is_err());
+    }
+}
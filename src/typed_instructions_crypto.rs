@@ -0,0 +1,1438 @@
+use crate::elems_singleton::Singleton;
+use crate::hash_function::{HashFunction, DigestLengthError};
+use crate::key_type::{bls12_381_public_key, bls12_381_signature, KeyType, KeyTypeError};
+use crate::merkle::MerklePairing;
+use crate::elems_input_output_singleton::ReturnSingleton;
+use crate::elems_list::IsList;
+use crate::elems_list_nil::Nil;
+use crate::elems_list_cons::Cons;
+use crate::elems_list_input_output_cons::ConsOut;
+use crate::untyped_instruction::Instruction;
+use crate::typed_instruction::{IsInstructionT, StackInstructionError};
+use crate::types::empty::Empty;
+
+use std::convert::TryFrom;
+use std::marker::PhantomData;
+
+use generic_array::typenum::{U0, U1, U2, U3};
+use hmac::{Hmac, Mac, NewMac};
+use k256::ecdsa::recoverable;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use sha2::Sha256;
+use serde_json::{Number, Value};
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+
+
+/// input: [public_key: Vec<u8>, signature: Vec<u8>, message: Vec<u8>]
+/// output: [is_valid: bool]
+///
+/// Verify a secp256k1 ECDSA signature of SHA-256(message), i.e.
+/// "push message; push signature; push public_key; verify_secp256k1"
+///
+/// - public_key is a SEC1 point: 33 bytes (compressed) or 65 bytes (uncompressed)
+/// - signature is either 64 bytes (r || s) or ASN.1 DER, and may be high-S
+///
+/// Fails if public_key or signature is malformed, otherwise returns whether
+/// the signature is valid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifySecp256k1 {}
+
+/// Error from VerifySecp256k1, distinguishing malformed inputs from invalid
+/// signatures (which result in false)
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum VerifySecp256k1Error {
+    /// public_key isn't a valid SEC1 secp256k1 point
+    #[error("verify_secp256k1: malformed SEC1 public key: 0x{}", hex::encode(.public_key))]
+    MalformedPublicKey {
+        /// The malformed public key
+        public_key: Vec<u8>,
+    },
+
+    /// signature is neither 64 bytes (r || s) nor ASN.1 DER, or r or s is out
+    /// of range
+    #[error("verify_secp256k1: malformed signature, expected 64 bytes (r || s) or DER: 0x{}", hex::encode(.signature))]
+    MalformedSignature {
+        /// The malformed signature
+        signature: Vec<u8>,
+    },
+}
+
+impl From<KeyTypeError> for VerifySecp256k1Error {
+    fn from(error: KeyTypeError) -> Self {
+        match error {
+            KeyTypeError::MalformedPublicKey { public_key, .. } => Self::MalformedPublicKey { public_key: public_key },
+            KeyTypeError::MalformedSignature { signature, .. } => Self::MalformedSignature { signature: signature },
+        }
+    }
+}
+
+impl IsInstructionT for VerifySecp256k1 {
+    type IO = ConsOut<ReturnSingleton<bool, U0>, Cons<Singleton<Vec<u8>, U3>, Nil>>;
+    type Error = VerifySecp256k1Error;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::VerifySecp256k1)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "verify_secp256k1".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array;
+        returning.returning(KeyType::Secp256k1.verify(&array[0], &array[1], &array[2])?);
+        Ok(())
+    }
+}
+
+/// Decode an Array of hex Strings (with or without "0x"), or return the
+/// position and value of the first element that isn't one
+fn decode_hex_array(array: &[Value]) -> Result<Vec<Vec<u8>>, (usize, Value)> {
+    array.iter().enumerate().map(|(position, value)| {
+        value.as_str()
+            .and_then(|hex_value| hex::decode(hex_value.strip_prefix("0x").unwrap_or(hex_value)).ok())
+            .ok_or_else(|| (position, value.clone()))
+    }).collect()
+}
+
+/// input: [Bytes]
+/// output: [Bytes]
+///
+/// SHA3-256 (FIPS 202), with a 32 byte digest
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HashSha3_256 {}
+
+impl IsInstructionT for HashSha3_256 {
+    type IO = ConsOut<ReturnSingleton<Vec<u8>, U1>, Nil>;
+    type Error = Empty;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::HashSha3_256)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "sha3_256".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let array = x.clone().hd().singleton.array;
+        let returning = x.clone().hd().returning;
+        returning.returning(HashFunction::Sha3_256.digest(&array[0]));
+        Ok(())
+    }
+}
+
+/// input: [Bytes]
+/// output: [Bytes]
+///
+/// Keccak-256, as used by Ethereum, with a 32 byte digest
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HashKeccak256 {}
+
+impl IsInstructionT for HashKeccak256 {
+    type IO = ConsOut<ReturnSingleton<Vec<u8>, U1>, Nil>;
+    type Error = Empty;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::HashKeccak256)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "keccak256".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let array = x.clone().hd().singleton.array;
+        let returning = x.clone().hd().returning;
+        returning.returning(HashFunction::Keccak256.digest(&array[0]));
+        Ok(())
+    }
+}
+
+/// input: [Bytes]
+/// output: [Bytes]
+///
+/// SHA-512, with a 64 byte digest
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HashSha512 {}
+
+impl IsInstructionT for HashSha512 {
+    type IO = ConsOut<ReturnSingleton<Vec<u8>, U1>, Nil>;
+    type Error = Empty;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::HashSha512)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "sha512".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let array = x.clone().hd().singleton.array;
+        let returning = x.clone().hd().returning;
+        returning.returning(HashFunction::Sha512.digest(&array[0]));
+        Ok(())
+    }
+}
+
+/// input: [Bytes]
+/// output: [Bytes]
+///
+/// BLAKE2b with a 256-bit (32 byte) digest
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HashBlake2b256 {}
+
+impl IsInstructionT for HashBlake2b256 {
+    type IO = ConsOut<ReturnSingleton<Vec<u8>, U1>, Nil>;
+    type Error = Empty;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::HashBlake2b256)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "blake2b_256".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let array = x.clone().hd().singleton.array;
+        let returning = x.clone().hd().returning;
+        returning.returning(HashFunction::Blake2b256.digest(&array[0]));
+        Ok(())
+    }
+}
+
+/// input: [Bytes]
+/// output: [Bytes]
+///
+/// BLAKE3, with a 32 byte digest
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HashBlake3 {}
+
+impl IsInstructionT for HashBlake3 {
+    type IO = ConsOut<ReturnSingleton<Vec<u8>, U1>, Nil>;
+    type Error = Empty;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::HashBlake3)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "blake3".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let array = x.clone().hd().singleton.array;
+        let returning = x.clone().hd().returning;
+        returning.returning(HashFunction::Blake3.digest(&array[0]));
+        Ok(())
+    }
+}
+
+/// input: [Bytes]
+/// output: [Bytes]
+///
+/// RIPEMD-160, with a 20 byte digest
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HashRipemd160 {}
+
+impl IsInstructionT for HashRipemd160 {
+    type IO = ConsOut<ReturnSingleton<Vec<u8>, U1>, Nil>;
+    type Error = Empty;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::HashRipemd160)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "ripemd160".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let array = x.clone().hd().singleton.array;
+        let returning = x.clone().hd().returning;
+        returning.returning(HashFunction::Ripemd160.digest(&array[0]));
+        Ok(())
+    }
+}
+
+/// input: [signature: Vec<u8>, digest: Vec<u8>]
+/// output: [address: Vec<u8>]
+///
+/// Recover the 20-byte Ethereum address that produced the signature of the
+/// 32-byte digest, i.e. the last 20 bytes of the Keccak-256 hash of the
+/// uncompressed public key (without its 0x04 prefix), as with Ethereum's
+/// ecrecover:
+/// "push digest; push signature; ecrecover"
+///
+/// - signature is 65 bytes (r || s || v), where v is 0, 1, 27 or 28
+///
+/// Fails if the digest or signature is malformed, or if no public key can be
+/// recovered. Any other signature recovers some address, so the result
+/// must be compared to the expected signer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EcRecover {}
+
+/// Error from EcRecover
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum EcRecoverError {
+    /// digest isn't 32 bytes
+    #[error("ecrecover: expected a 32-byte digest, but found {} bytes: 0x{}", .digest.len(), hex::encode(.digest))]
+    DigestLength {
+        /// The malformed digest
+        digest: Vec<u8>,
+    },
+
+    /// signature isn't 65 bytes
+    #[error("ecrecover: expected a 65-byte signature (r || s || v), but found {} bytes: 0x{}", .signature.len(), hex::encode(.signature))]
+    SignatureLength {
+        /// The malformed signature
+        signature: Vec<u8>,
+    },
+
+    /// v isn't 0, 1, 27 or 28
+    #[error("ecrecover: expected a recovery id (v) of 0, 1, 27 or 28, but found {recovery_id}")]
+    RecoveryId {
+        /// The invalid recovery id
+        recovery_id: u8,
+    },
+
+    /// r or s is out of range
+    #[error("ecrecover: malformed signature, r or s is out of range: 0x{}", hex::encode(.signature))]
+    MalformedSignature {
+        /// The malformed signature
+        signature: Vec<u8>,
+    },
+
+    /// There's no public key for the given signature and digest
+    #[error("ecrecover: failed to recover a public key from signature 0x{} of digest 0x{}", hex::encode(.signature), hex::encode(.digest))]
+    RecoveryFailed {
+        /// The signature
+        signature: Vec<u8>,
+        /// The signed digest
+        digest: Vec<u8>,
+    },
+}
+
+impl IsInstructionT for EcRecover {
+    type IO = ConsOut<ReturnSingleton<Vec<u8>, U0>, Cons<Singleton<Vec<u8>, U2>, Nil>>;
+    type Error = EcRecoverError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::EcRecover)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "ecrecover".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array;
+        let signature = &array[0];
+        let digest = &array[1];
+        let digest_bytes = <[u8; 32]>::try_from(digest.as_slice())
+            .map_err(|_| EcRecoverError::DigestLength {
+                digest: digest.clone(),
+            })?;
+        if signature.len() != 65 {
+            return Err(EcRecoverError::SignatureLength {
+                signature: signature.clone(),
+            })
+        }
+        let recovery_id = match signature[64] {
+            v @ (0 | 1) => v,
+            v @ (27 | 28) => v - 27,
+            v => return Err(EcRecoverError::RecoveryId {
+                recovery_id: v,
+            }),
+        };
+        let recoverable_signature = k256::ecdsa::Signature::try_from(&signature[..64])
+            .and_then(|rs| recoverable::Signature::new(&rs, recoverable::Id::new(recovery_id)?))
+            .map_err(|_| EcRecoverError::MalformedSignature {
+                signature: signature.clone(),
+            })?;
+        let verifying_key = recoverable_signature
+            .recover_verify_key_from_digest_bytes(&k256::FieldBytes::from(digest_bytes))
+            .map_err(|_| EcRecoverError::RecoveryFailed {
+                signature: signature.clone(),
+                digest: digest.clone(),
+            })?;
+        let public_key = verifying_key.to_encoded_point(false);
+        returning.returning(HashFunction::Keccak256.digest(&public_key.as_bytes()[1..])[12..].to_vec());
+        Ok(())
+    }
+}
+
+/// input: [message: Vec<u8>]
+/// output: [digest: Vec<u8>]
+///
+/// The EIP-191 (version 0x45) digest signed by Ethereum's personal_sign, i.e.
+/// Keccak-256("\x19Ethereum Signed Message:\n" ++ len(message) ++ message),
+/// where len(message) is in decimal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HashEip191 {}
+
+impl IsInstructionT for HashEip191 {
+    type IO = ConsOut<ReturnSingleton<Vec<u8>, U1>, Nil>;
+    type Error = Empty;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::HashEip191)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "hash_eip191".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let array = x.clone().hd().singleton.array;
+        let returning = x.clone().hd().returning;
+        let mut prefixed = format!("\x19Ethereum Signed Message:\n{}", array[0].len()).into_bytes();
+        prefixed.extend_from_slice(&array[0]);
+        returning.returning(HashFunction::Keccak256.digest(&prefixed));
+        Ok(())
+    }
+}
+
+/// input: [public_key: Vec<u8>, signature: Vec<u8>, message: Vec<u8>]
+/// output: [is_valid: bool]
+///
+/// Verify an Ed25519 (RFC 8032) signature of the message, i.e.
+/// "push message; push signature; push public_key; verify_ed25519"
+///
+/// - public_key is 32 bytes
+/// - signature is 64 bytes (R || S)
+///
+/// Fails if public_key or signature is malformed, otherwise returns whether
+/// the signature is valid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifyEd25519 {}
+
+/// Error from VerifyEd25519, distinguishing malformed inputs from invalid
+/// signatures (which result in false)
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum VerifyEd25519Error {
+    /// public_key isn't 32 bytes, or isn't a valid point
+    #[error("verify_ed25519: malformed public key, expected a 32-byte point: 0x{}", hex::encode(.public_key))]
+    MalformedPublicKey {
+        /// The malformed public key
+        public_key: Vec<u8>,
+    },
+
+    /// signature isn't 64 bytes, or S isn't reduced
+    #[error("verify_ed25519: malformed signature, expected 64 bytes (R || S): 0x{}", hex::encode(.signature))]
+    MalformedSignature {
+        /// The malformed signature
+        signature: Vec<u8>,
+    },
+}
+
+impl From<KeyTypeError> for VerifyEd25519Error {
+    fn from(error: KeyTypeError) -> Self {
+        match error {
+            KeyTypeError::MalformedPublicKey { public_key, .. } => Self::MalformedPublicKey { public_key: public_key },
+            KeyTypeError::MalformedSignature { signature, .. } => Self::MalformedSignature { signature: signature },
+        }
+    }
+}
+
+impl IsInstructionT for VerifyEd25519 {
+    type IO = ConsOut<ReturnSingleton<bool, U0>, Cons<Singleton<Vec<u8>, U3>, Nil>>;
+    type Error = VerifyEd25519Error;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::VerifyEd25519)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "verify_ed25519".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array;
+        returning.returning(KeyType::Ed25519.verify(&array[0], &array[1], &array[2])?);
+        Ok(())
+    }
+}
+
+/// input: [public_key: Vec<u8>, signature: Vec<u8>, message: Vec<u8>]
+/// output: [is_valid: bool]
+///
+/// Verify a P-256 (secp256r1) ECDSA signature of SHA-256(message), as used by
+/// WebAuthn/passkeys (ES256), i.e.
+/// "push message; push signature; push public_key; verify_p256"
+///
+/// - public_key is a SEC1 point: 33 bytes (compressed) or 65 bytes (uncompressed)
+/// - signature is either 64 bytes (r || s) or ASN.1 DER
+///
+/// Fails if public_key or signature is malformed, otherwise returns whether
+/// the signature is valid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifyP256 {}
+
+/// Error from VerifyP256, distinguishing malformed inputs from invalid
+/// signatures (which result in false)
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum VerifyP256Error {
+    /// public_key isn't a valid SEC1 P-256 point
+    #[error("verify_p256: malformed SEC1 public key: 0x{}", hex::encode(.public_key))]
+    MalformedPublicKey {
+        /// The malformed public key
+        public_key: Vec<u8>,
+    },
+
+    /// signature is neither 64 bytes (r || s) nor ASN.1 DER, or r or s is out
+    /// of range
+    #[error("verify_p256: malformed signature, expected 64 bytes (r || s) or DER: 0x{}", hex::encode(.signature))]
+    MalformedSignature {
+        /// The malformed signature
+        signature: Vec<u8>,
+    },
+}
+
+impl From<KeyTypeError> for VerifyP256Error {
+    fn from(error: KeyTypeError) -> Self {
+        match error {
+            KeyTypeError::MalformedPublicKey { public_key, .. } => Self::MalformedPublicKey { public_key: public_key },
+            KeyTypeError::MalformedSignature { signature, .. } => Self::MalformedSignature { signature: signature },
+        }
+    }
+}
+
+impl IsInstructionT for VerifyP256 {
+    type IO = ConsOut<ReturnSingleton<bool, U0>, Cons<Singleton<Vec<u8>, U3>, Nil>>;
+    type Error = VerifyP256Error;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::VerifyP256)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "verify_p256".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array;
+        returning.returning(KeyType::P256.verify(&array[0], &array[1], &array[2])?);
+        Ok(())
+    }
+}
+
+/// input: [client_data_json: Vec<u8>, authenticator_data: Vec<u8>]
+/// output: [payload: Vec<u8>]
+///
+/// The payload signed by a WebAuthn authenticator, i.e.
+/// authenticator_data ++ SHA-256(client_data_json), so that an assertion can
+/// be checked with:
+/// "push authenticator_data; push client_data_json; webauthn_payload;
+///  push signature; push public_key; verify_p256"
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WebAuthnPayload {}
+
+impl IsInstructionT for WebAuthnPayload {
+    type IO = ConsOut<ReturnSingleton<Vec<u8>, U0>, Cons<Singleton<Vec<u8>, U2>, Nil>>;
+    type Error = Empty;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::WebAuthnPayload)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "webauthn_payload".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array;
+        let mut payload = array[1].clone();
+        payload.extend(super::sha256(&array[0]));
+        returning.returning(payload);
+        Ok(())
+    }
+}
+
+/// input: [key: Vec<u8>, message: Vec<u8>]
+/// output: [tag: Vec<u8>]
+///
+/// HMAC-SHA256 (RFC 2104) of the message, i.e.
+/// "push message; push key; hmac_sha256"
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HmacSha256 {}
+
+impl IsInstructionT for HmacSha256 {
+    type IO = ConsOut<ReturnSingleton<Vec<u8>, U0>, Cons<Singleton<Vec<u8>, U2>, Nil>>;
+    type Error = Empty;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::HmacSha256)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "hmac_sha256".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array;
+        let mut mac = Hmac::<Sha256>::new_from_slice(&array[0]).expect("HMAC accepts keys of any length");
+        mac.update(&array[1]);
+        returning.returning(mac.finalize().into_bytes().to_vec());
+        Ok(())
+    }
+}
+
+/// input: [x: Vec<u8>, y: Vec<u8>]
+/// output: [x == y: bool]
+///
+/// Like BytesEq, except that the time taken only depends on the lengths of x
+/// and y, and not on their contents, e.g. to compare MAC's
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConstantTimeBytesEq {}
+
+impl IsInstructionT for ConstantTimeBytesEq {
+    type IO = ConsOut<ReturnSingleton<bool, U0>, Cons<Singleton<Vec<u8>, U2>, Nil>>;
+    type Error = Empty;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::ConstantTimeBytesEq)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "constant_time_bytes_eq".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array;
+        returning.returning(bool::from(array[0].ct_eq(&array[1])));
+        Ok(())
+    }
+}
+
+/// input: [root: Vec<u8>, index: Number, siblings: Vec<Value>, leaf: Vec<u8>]
+/// output: [is_valid: bool]
+///
+/// Verify a Merkle proof that the leaf (hash) is in the tree with the given
+/// root, i.e. "push leaf; push siblings; push index; push root;
+/// verify_merkle_proof<keccak256, sorted>"
+///
+/// - leaf, root, and each sibling must be HashFunction::output_len() bytes
+/// - siblings is a non-empty Array of hex Strings (with or without "0x"),
+///   from the bottom of the tree up
+/// - index gives the position of the leaf for MerklePairing::Positional,
+///   and must be less than 2^(number of siblings). It's otherwise ignored
+///
+/// Leaves and inner nodes are hashed the same way (see MerklePairing), so a
+/// proof only shows that the "leaf" is some node of the tree: the leaf should
+/// be hashed with a prefix that inner nodes can't have, or the number of
+/// siblings should be checked against the depth of the tree.
+///
+/// Fails if index isn't a u64 (or is out of range), siblings are malformed or
+/// empty, or a node has the wrong length, otherwise returns whether the
+/// computed root is equal to the given root
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifyMerkleProof {
+    /// The HashFunction used for each pair of nodes
+    pub hash_function: HashFunction,
+
+    /// How each node is paired with its sibling
+    pub pairing: MerklePairing,
+}
+
+/// Error from VerifyMerkleProof
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum VerifyMerkleProofError {
+    /// index isn't a u64
+    #[error("verify_merkle_proof: expected a u64 index, but found {index}")]
+    IndexNotU64 {
+        /// The invalid index
+        index: Number,
+    },
+
+    /// A sibling isn't a hex String
+    #[error("verify_merkle_proof: expected sibling {position} to be a hex string, but found {sibling}")]
+    SiblingNotHex {
+        /// Position of the sibling in siblings
+        position: usize,
+        /// The invalid sibling
+        sibling: Value,
+    },
+
+    /// A positional proof is deeper than the bits in its index
+    #[error("verify_merkle_proof: positional proofs may have at most 64 siblings, but found {depth}")]
+    ProofTooDeep {
+        /// Number of siblings
+        depth: usize,
+    },
+
+    /// A positional index doesn't fit in the depth of the proof
+    #[error("verify_merkle_proof: expected an index less than 2^{depth}, but found {index}")]
+    IndexOutOfRange {
+        /// The invalid index
+        index: u64,
+        /// Number of siblings
+        depth: usize,
+    },
+
+    /// siblings is empty
+    #[error("verify_merkle_proof: expected at least one sibling")]
+    EmptyProof,
+
+    /// The leaf, root, or a sibling isn't HashFunction::output_len() bytes
+    #[error("verify_merkle_proof: invalid {node}: {error}")]
+    NodeLength {
+        /// Which node is invalid, e.g. "sibling 0"
+        node: String,
+        /// The DigestLengthError
+        error: DigestLengthError,
+    },
+}
+
+impl IsInstructionT for VerifyMerkleProof {
+    type IO = ConsOut<ReturnSingleton<bool,      U0>,
+                 Cons<Singleton<Vec<u8>,     U1>,
+                 Cons<Singleton<Number,      U1>,
+                 Cons<Singleton<Vec<Value>,  U1>,
+                 Cons<Singleton<Vec<u8>,     U1>, Nil>>>>>;
+    type Error = VerifyMerkleProofError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::VerifyMerkleProof(self.hash_function, self.pairing))
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "verify_merkle_proof".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let root = &x.clone().tl().hd().array[0];
+        let index = &x.clone().tl().tl().hd().array[0];
+        let siblings = &x.clone().tl().tl().tl().hd().array[0];
+        let leaf = &x.clone().tl().tl().tl().tl().hd().array[0];
+        let u_index = index.as_u64()
+            .ok_or_else(|| VerifyMerkleProofError::IndexNotU64 {
+                index: index.clone(),
+            })?;
+        if siblings.is_empty() {
+            return Err(VerifyMerkleProofError::EmptyProof)
+        }
+        if self.pairing == MerklePairing::Positional {
+            if siblings.len() > 64 {
+                return Err(VerifyMerkleProofError::ProofTooDeep {
+                    depth: siblings.len(),
+                })
+            }
+            if u_index.checked_shr(siblings.len() as u32).unwrap_or(0) != 0 {
+                return Err(VerifyMerkleProofError::IndexOutOfRange {
+                    index: u_index,
+                    depth: siblings.len(),
+                })
+            }
+        }
+        let sibling_hashes = decode_hex_array(siblings)
+            .map_err(|(position, sibling)| VerifyMerkleProofError::SiblingNotHex {
+                position: position,
+                sibling: sibling,
+            })?;
+        let nodes = vec![("leaf".to_string(), leaf), ("root".to_string(), root)].into_iter()
+            .chain(sibling_hashes.iter().enumerate().map(|(position, sibling)| (format!("sibling {}", position), sibling)));
+        for (node, digest) in nodes {
+            self.hash_function.check_output_len(digest)
+                .map_err(|error| VerifyMerkleProofError::NodeLength {
+                    node: node,
+                    error: error,
+                })?;
+        }
+        returning.returning(self.pairing.root(self.hash_function, leaf, &sibling_hashes, u_index) == *root);
+        Ok(())
+    }
+}
+
+/// Domain separation tag of the IETF BLS signature ciphersuite used by
+/// VerifyBls12381 and FastAggregateVerifyBls12381 (and KeyType::Bls12381):
+/// public keys in G1, signatures in G2, and proofs of possession (as in
+/// Ethereum's consensus layer)
+pub const BLS12_381_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+/// Error from VerifyBls12381 or FastAggregateVerifyBls12381, distinguishing
+/// malformed inputs from invalid signatures (which result in false)
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum VerifyBls12381Error {
+    /// A public key isn't a valid compressed G1 point (48 bytes), is the
+    /// identity, or isn't in the prime-order subgroup
+    #[error("{instruction}: malformed BLS12-381 public key: 0x{}", hex::encode(.public_key))]
+    MalformedPublicKey {
+        /// Name of the instruction
+        instruction: String,
+        /// The malformed public key
+        public_key: Vec<u8>,
+    },
+
+    /// The signature isn't a valid compressed G2 point (96 bytes) in the
+    /// prime-order subgroup
+    #[error("{instruction}: malformed BLS12-381 signature: 0x{}", hex::encode(.signature))]
+    MalformedSignature {
+        /// Name of the instruction
+        instruction: String,
+        /// The malformed signature
+        signature: Vec<u8>,
+    },
+
+    /// An element of public_keys isn't a hex String
+    #[error("fast_aggregate_verify_bls12_381: expected public key {position} to be a hex string, but found {public_key}")]
+    PublicKeyNotHex {
+        /// Position of the public key in public_keys
+        position: usize,
+        /// The invalid public key
+        public_key: Value,
+    },
+
+    /// public_keys is empty
+    #[error("fast_aggregate_verify_bls12_381: expected at least one public key")]
+    NoPublicKeys,
+}
+
+/// input: [public_key: Vec<u8>, signature: Vec<u8>, message: Vec<u8>]
+/// output: [is_valid: bool]
+///
+/// Verify a BLS12-381 signature of the message (see BLS12_381_DST), i.e.
+/// "push message; push signature; push public_key; verify_bls12_381"
+///
+/// - public_key is a compressed G1 point (48 bytes)
+/// - signature is a compressed G2 point (96 bytes)
+///
+/// Fails if public_key or signature is malformed, otherwise returns whether
+/// the signature is valid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifyBls12381 {}
+
+impl VerifyBls12381Error {
+    /// Add the name of the instruction to a KeyTypeError
+    fn from_key_type_error(instruction: String, error: KeyTypeError) -> Self {
+        match error {
+            KeyTypeError::MalformedPublicKey { public_key, .. } => Self::MalformedPublicKey {
+                instruction: instruction,
+                public_key: public_key,
+            },
+            KeyTypeError::MalformedSignature { signature, .. } => Self::MalformedSignature {
+                instruction: instruction,
+                signature: signature,
+            },
+        }
+    }
+}
+
+impl IsInstructionT for VerifyBls12381 {
+    type IO = ConsOut<ReturnSingleton<bool, U0>, Cons<Singleton<Vec<u8>, U3>, Nil>>;
+    type Error = VerifyBls12381Error;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::VerifyBls12381)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "verify_bls12_381".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array;
+        returning.returning(KeyType::Bls12381.verify(&array[0], &array[1], &array[2])
+            .map_err(|error| VerifyBls12381Error::from_key_type_error(Self::name(PhantomData), error))?);
+        Ok(())
+    }
+}
+
+/// input: [public_keys: Vec<Value>, signature: Vec<u8>, message: Vec<u8>]
+/// output: [is_valid: bool]
+///
+/// Verify an aggregate BLS12-381 signature of the same message by all of the
+/// public_keys (see BLS12_381_DST), i.e.
+/// "push message; push signature; push public_keys; fast_aggregate_verify_bls12_381"
+///
+/// - public_keys is a non-empty Array of hex Strings, each a compressed G1 point
+/// - signature is a compressed G2 point (96 bytes)
+///
+/// This is only secure when each public key has a proof of possession, e.g.
+/// when they're the (fixed) members of a committee.
+///
+/// Fails if any public key or the signature is malformed, otherwise returns
+/// whether the signature is valid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FastAggregateVerifyBls12381 {}
+
+impl IsInstructionT for FastAggregateVerifyBls12381 {
+    type IO = ConsOut<ReturnSingleton<bool,      U0>,
+                 Cons<Singleton<Vec<Value>,  U1>,
+                 Cons<Singleton<Vec<u8>,     U2>, Nil>>>;
+    type Error = VerifyBls12381Error;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::FastAggregateVerifyBls12381)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "fast_aggregate_verify_bls12_381".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let hex_public_keys = &x.clone().tl().hd().array[0];
+        let signature_message = x.clone().tl().tl().hd().array;
+        if hex_public_keys.is_empty() {
+            return Err(VerifyBls12381Error::NoPublicKeys)
+        }
+        let key_type_error = |error| VerifyBls12381Error::from_key_type_error(Self::name(PhantomData), error);
+        let public_keys = decode_hex_array(hex_public_keys)
+            .map_err(|(position, public_key)| VerifyBls12381Error::PublicKeyNotHex {
+                position: position,
+                public_key: public_key,
+            })?
+            .iter()
+            .map(|public_key| bls12_381_public_key(public_key))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(key_type_error)?;
+        let signature = bls12_381_signature(&signature_message[0]).map_err(key_type_error)?;
+        let result = signature.fast_aggregate_verify(false,
+                                                     &signature_message[1],
+                                                     BLS12_381_DST,
+                                                     &public_keys.iter().collect::<Vec<_>>());
+        returning.returning(result == blst::BLST_ERROR::BLST_SUCCESS);
+        Ok(())
+    }
+}
+
+/// input: [threshold: Number, signatures: Vec<Value>, public_keys: Vec<Value>, message: Vec<u8>]
+/// output: [is_valid: bool]
+///
+/// Check that at least threshold of the public_keys signed the message, like
+/// Bitcoin's OP_CHECKMULTISIG, i.e.
+/// "push message; push public_keys; push signatures; push threshold;
+///  check_multisig<ed25519>"
+///
+/// - public_keys and signatures are Arrays of hex Strings (with or without
+///   "0x"), encoded as for the given KeyType
+/// - each signature counts for at most one public key, and vice versa, in any
+///   order
+///
+/// The element types of public_keys and signatures are checked when the
+/// instruction is run, rather than by the type checker: an Array holds JSON
+/// Values, and ElemSymbol has no element types to check them against.
+///
+/// Fails if threshold isn't a u64 from 1 to the number of public_keys, if any
+/// element of public_keys or signatures isn't a hex String, or if any public
+/// key or signature is malformed, otherwise returns whether the threshold is met
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CheckMultiSig {
+    /// The KeyType of every public key and signature
+    pub key_type: KeyType,
+}
+
+/// Error from CheckMultiSig
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum CheckMultiSigError {
+    /// A public key or signature is malformed
+    #[error("check_multisig: {0}")]
+    KeyTypeError(#[from] KeyTypeError),
+
+    /// threshold isn't a u64
+    #[error("check_multisig: expected a u64 threshold, but found {threshold}")]
+    ThresholdNotU64 {
+        /// The invalid threshold
+        threshold: Number,
+    },
+
+    /// threshold is zero (so any signatures would be accepted), or can never
+    /// be met
+    #[error("check_multisig: expected a threshold from 1 to the number of public keys ({num_public_keys}), but found {threshold}")]
+    ThresholdOutOfRange {
+        /// The threshold
+        threshold: u64,
+        /// Number of public keys
+        num_public_keys: usize,
+    },
+
+    /// An element of public_keys or signatures isn't a hex String
+    #[error("check_multisig: expected {array} element {position} to be a hex string, but found {value}")]
+    NotHex {
+        /// "public_keys" or "signatures"
+        array: String,
+        /// Position of the element
+        position: usize,
+        /// The invalid element
+        value: Value,
+    },
+}
+
+impl IsInstructionT for CheckMultiSig {
+    type IO = ConsOut<ReturnSingleton<bool,      U0>,
+                 Cons<Singleton<Number,      U1>,
+                 Cons<Singleton<Vec<Value>,  U2>,
+                 Cons<Singleton<Vec<u8>,     U1>, Nil>>>>;
+    type Error = CheckMultiSigError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::CheckMultiSig(self.key_type))
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "check_multisig".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let threshold = &x.clone().tl().hd().array[0];
+        let signatures_public_keys = x.clone().tl().tl().hd().array;
+        let message = &x.clone().tl().tl().tl().hd().array[0];
+        let decode = |array: &str, values: &[Value]| decode_hex_array(values)
+            .map_err(|(position, value)| CheckMultiSigError::NotHex {
+                array: array.to_string(),
+                position: position,
+                value: value,
+            });
+        let signatures = decode("signatures", &signatures_public_keys[0])?;
+        let public_keys = decode("public_keys", &signatures_public_keys[1])?;
+        let u_threshold = threshold.as_u64()
+            .ok_or_else(|| CheckMultiSigError::ThresholdNotU64 {
+                threshold: threshold.clone(),
+            })?;
+        if u_threshold == 0 || u_threshold > public_keys.len() as u64 {
+            return Err(CheckMultiSigError::ThresholdOutOfRange {
+                threshold: u_threshold,
+                num_public_keys: public_keys.len(),
+            })
+        }
+
+        let mut unused_public_keys: Vec<&Vec<u8>> = public_keys.iter().collect();
+        let mut num_valid: u64 = 0;
+        for signature in &signatures {
+            let mut signer = None;
+            for (position, public_key) in unused_public_keys.iter().enumerate() {
+                if self.key_type.verify(public_key, signature, message)? {
+                    signer = Some(position);
+                    break
+                }
+            }
+            if let Some(position) = signer {
+                unused_public_keys.remove(position);
+                num_valid += 1;
+            }
+        }
+        returning.returning(num_valid >= u_threshold);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elem::Elem;
+    use crate::parse::parse;
+    use crate::stack::Stack;
+
+    use hex_literal::hex;
+    use k256::ecdsa::signature::Signer;
+
+    fn run(instructions: Vec<Instruction>) -> Result<Stack, StackInstructionError> {
+        let mut stack = Stack::new();
+        crate::untyped_instructions::Instructions {
+            docs: vec![],
+            instructions: instructions,
+        }.to_instrs()
+            .expect("failed to type instructions")
+            .run(&mut stack)?;
+        Ok(stack)
+    }
+
+    fn verify_secp256k1(message: &[u8], signature: Vec<u8>, public_key: Vec<u8>) -> Result<Stack, StackInstructionError> {
+        run(vec![
+            Instruction::Push(Elem::Bytes(message.to_vec())),
+            Instruction::Push(Elem::Bytes(signature)),
+            Instruction::Push(Elem::Bytes(public_key)),
+            Instruction::VerifySecp256k1,
+        ])
+    }
+
+    #[test]
+    fn test_verify_secp256k1() {
+        let signing_key = k256::ecdsa::SigningKey::from_bytes(&[0x11; 32]).expect("invalid signing key");
+        let public_key = signing_key.verifying_key().to_bytes().to_vec();
+        let uncompressed_public_key = signing_key.verifying_key().to_encoded_point(false).as_bytes().to_vec();
+        let signature: k256::ecdsa::Signature = signing_key.sign(b"I am the walrus.");
+        let der_signature = signature.to_der().as_bytes().to_vec();
+        let high_s_signature = k256::ecdsa::Signature::from_scalars(signature.r().to_bytes(), (-*signature.s()).to_bytes())
+            .expect("invalid high-S signature");
+
+        for (signature, public_key) in [
+            (signature.as_ref().to_vec(), public_key.clone()),
+            (der_signature, public_key.clone()),
+            (high_s_signature.as_ref().to_vec(), public_key.clone()),
+            (signature.as_ref().to_vec(), uncompressed_public_key),
+        ] {
+            assert_eq!(vec![Elem::Bool(true)],
+                       verify_secp256k1(b"I am the walrus.", signature, public_key).expect("failed to verify").stack);
+        }
+        assert_eq!(vec![Elem::Bool(false)],
+                   verify_secp256k1(b"I am the eggman.", signature.as_ref().to_vec(), public_key.clone())
+                   .expect("failed to verify").stack);
+
+        let error = verify_secp256k1(b"", signature.as_ref().to_vec(), vec![0x02; 32]).expect_err("malformed public key");
+        assert!(error.to_string().contains("verify_secp256k1: malformed SEC1 public key"), "{}", error);
+        let error = verify_secp256k1(b"", vec![0; 64], public_key).expect_err("malformed signature");
+        assert!(error.to_string().contains("verify_secp256k1: malformed signature"), "{}", error);
+
+        assert_eq!(vec![Instruction::VerifySecp256k1],
+                   parse("verify_secp256k1;").expect("failed to parse").instructions);
+    }
+
+    fn verify_ed25519(message: &[u8], signature: Vec<u8>, public_key: Vec<u8>) -> Result<Stack, StackInstructionError> {
+        run(vec![
+            Instruction::Push(Elem::Bytes(message.to_vec())),
+            Instruction::Push(Elem::Bytes(signature)),
+            Instruction::Push(Elem::Bytes(public_key)),
+            Instruction::VerifyEd25519,
+        ])
+    }
+
+    #[test]
+    fn test_verify_ed25519() {
+        // RFC 8032, section 7.1, TEST 2
+        let public_key = hex!("3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c").to_vec();
+        let signature = hex!("92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00").to_vec();
+        assert_eq!(vec![Elem::Bool(true)],
+                   verify_ed25519(&[0x72], signature.clone(), public_key.clone()).expect("failed to verify").stack);
+        assert_eq!(vec![Elem::Bool(false)],
+                   verify_ed25519(&[0x73], signature.clone(), public_key.clone()).expect("failed to verify").stack);
+
+        let error = verify_ed25519(&[0x72], signature.clone(), public_key[..31].to_vec()).expect_err("malformed public key");
+        assert!(error.to_string().contains("verify_ed25519: malformed public key"), "{}", error);
+        let error = verify_ed25519(&[0x72], signature[..63].to_vec(), public_key).expect_err("malformed signature");
+        assert!(error.to_string().contains("verify_ed25519: malformed signature"), "{}", error);
+
+        // The identity public key, with R the identity and S = 0, satisfies
+        // the (non-strict) verification equation for every message
+        let identity = hex!("0100000000000000000000000000000000000000000000000000000000000000").to_vec();
+        let weak_signature = [identity.clone(), vec![0; 32]].concat();
+        for message in [&[0x72][..], b"I am the walrus."] {
+            assert_eq!(vec![Elem::Bool(false)],
+                       verify_ed25519(message, weak_signature.clone(), identity.clone()).expect("failed to verify").stack);
+        }
+
+        assert_eq!(vec![Instruction::VerifyEd25519],
+                   parse("verify_ed25519;").expect("failed to parse").instructions);
+    }
+
+    fn verify_p256(message: &[u8], signature: Vec<u8>, public_key: Vec<u8>) -> Result<Stack, StackInstructionError> {
+        run(vec![
+            Instruction::Push(Elem::Bytes(message.to_vec())),
+            Instruction::Push(Elem::Bytes(signature)),
+            Instruction::Push(Elem::Bytes(public_key)),
+            Instruction::VerifyP256,
+        ])
+    }
+
+    #[test]
+    fn test_verify_p256() {
+        let signing_key = p256::ecdsa::SigningKey::from_bytes(&[0x11; 32]).expect("invalid signing key");
+        let public_key = signing_key.verifying_key().to_encoded_point(true).as_bytes().to_vec();
+        let uncompressed_public_key = signing_key.verifying_key().to_encoded_point(false).as_bytes().to_vec();
+        let signature: p256::ecdsa::Signature = signing_key.sign(b"I am the walrus.");
+        let der_signature = signature.to_der().as_bytes().to_vec();
+
+        for (signature, public_key) in [
+            (signature.as_ref().to_vec(), public_key.clone()),
+            (der_signature, public_key.clone()),
+            (signature.as_ref().to_vec(), uncompressed_public_key),
+        ] {
+            assert_eq!(vec![Elem::Bool(true)],
+                       verify_p256(b"I am the walrus.", signature, public_key).expect("failed to verify").stack);
+        }
+        assert_eq!(vec![Elem::Bool(false)],
+                   verify_p256(b"I am the eggman.", signature.as_ref().to_vec(), public_key.clone())
+                   .expect("failed to verify").stack);
+
+        let error = verify_p256(b"", signature.as_ref().to_vec(), vec![0x02; 32]).expect_err("malformed public key");
+        assert!(error.to_string().contains("verify_p256: malformed SEC1 public key"), "{}", error);
+        let error = verify_p256(b"", vec![0; 64], public_key).expect_err("malformed signature");
+        assert!(error.to_string().contains("verify_p256: malformed signature"), "{}", error);
+
+        assert_eq!(vec![Instruction::VerifyP256, Instruction::WebAuthnPayload],
+                   parse("verify_p256; webauthn_payload;").expect("failed to parse").instructions);
+    }
+
+    #[test]
+    fn test_webauthn_assertion() {
+        let signing_key = p256::ecdsa::SigningKey::from_bytes(&[0x22; 32]).expect("invalid signing key");
+        let public_key = signing_key.verifying_key().to_encoded_point(false).as_bytes().to_vec();
+        let authenticator_data = [[0x49; 32].as_slice(), &[0x05, 0x00, 0x00, 0x00, 0x01]].concat();
+        let client_data_json = br#"{"type":"webauthn.get","challenge":"SSBhbSB0aGUgd2FscnVzLg","origin":"https://example.com"}"#.to_vec();
+        let payload = [authenticator_data.clone(), crate::sha256(&client_data_json)].concat();
+        let signature: p256::ecdsa::Signature = signing_key.sign(&payload);
+
+        let instructions = vec![
+            Instruction::Push(Elem::Bytes(authenticator_data)),
+            Instruction::Push(Elem::Bytes(client_data_json)),
+            Instruction::WebAuthnPayload,
+            Instruction::Push(Elem::Bytes(signature.to_der().as_bytes().to_vec())),
+            Instruction::Push(Elem::Bytes(public_key)),
+            Instruction::VerifyP256,
+            Instruction::AssertTrue,
+        ];
+        assert_eq!(vec![Elem::Bool(true)], run(instructions.clone()).expect("failed to verify").stack);
+
+        let mut forged = instructions;
+        forged[1] = Instruction::Push(Elem::Bytes(br#"{"type":"webauthn.get","challenge":"SSBhbSB0aGUgZWdnbWFuLg"}"#.to_vec()));
+        assert!(run(forged).is_err());
+    }
+
+    #[test]
+    fn test_hash() {
+        let instructions = parse(r#"
+            push "abc"; string_to_bytes; hash_sha3_256;
+            push "abc"; string_to_bytes; hash_keccak256;
+            push "abc"; string_to_bytes; hash_sha512;
+            push "abc"; string_to_bytes; hash_blake2b_256;
+            push "abc"; string_to_bytes; hash_blake3;
+            push "abc"; string_to_bytes; hash_ripemd160;
+        "#).expect("failed to parse").instructions;
+        let stack = run(instructions).expect("failed to hash");
+        let hash_functions = [HashFunction::Ripemd160, HashFunction::Blake3, HashFunction::Blake2b256,
+            HashFunction::Sha512, HashFunction::Keccak256, HashFunction::Sha3_256];
+        assert_eq!(hash_functions.iter().map(|x| Elem::Bytes(x.digest(b"abc"))).collect::<Vec<_>>(), stack.stack);
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231, test case 2
+        let tag = hex!("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+        let mut source = format!(r#"
+            push b"what do ya want for nothing?";
+            push b"Jefe";
+            hmac_sha256;
+            push 0x{};
+            constant_time_bytes_eq;
+        "#, hex::encode(tag));
+        assert_eq!(vec![Elem::Bool(true)],
+                   run(parse(&source).expect("failed to parse").instructions).expect("failed to run").stack);
+
+        source = source.replace("Jefe", "Joe");
+        assert_eq!(vec![Elem::Bool(false)],
+                   run(parse(&source).expect("failed to parse").instructions).expect("failed to run").stack);
+        assert_eq!(vec![Elem::Bool(false)],
+                   run(parse("push 0x00; push 0x0000; constant_time_bytes_eq;").expect("failed to parse").instructions)
+                   .expect("failed to run").stack);
+    }
+
+    /// The siblings of each of four leaves, from the bottom up, and the root
+    fn merkle_tree(hash_function: HashFunction, pairing: MerklePairing, leaves: &[Vec<u8>; 4]) -> ([[Vec<u8>; 2]; 4], Vec<u8>) {
+        let hash_pair = |x: &Vec<u8>, y: &Vec<u8>| match pairing {
+            MerklePairing::Sorted if y < x => hash_function.digest(&[y.as_slice(), x].concat()),
+            _ => hash_function.digest(&[x.as_slice(), y].concat()),
+        };
+        let left = hash_pair(&leaves[0], &leaves[1]);
+        let right = hash_pair(&leaves[2], &leaves[3]);
+        ([[leaves[1].clone(), right.clone()],
+          [leaves[0].clone(), right.clone()],
+          [leaves[3].clone(), left.clone()],
+          [leaves[2].clone(), left.clone()]],
+         hash_pair(&left, &right))
+    }
+
+    fn verify_merkle_proof(instruction: &str, leaf: &[u8], siblings: &[Vec<u8>], index: u64, root: &[u8]) -> Result<Stack, StackInstructionError> {
+        run(parse(&format!("push 0x{}; push {}; push {}; push 0x{}; {};",
+                           hex::encode(leaf),
+                           serde_json::to_string(&siblings.iter().map(|x| format!("0x{}", hex::encode(x))).collect::<Vec<_>>())
+                               .expect("failed to serialize"),
+                           index,
+                           hex::encode(root),
+                           instruction)).expect("failed to parse").instructions)
+    }
+
+    #[test]
+    fn test_verify_merkle_proof() {
+        for hash_function in [HashFunction::Keccak256, HashFunction::Sha256] {
+            for pairing in MerklePairing::all() {
+                let instruction = format!("verify_merkle_proof<{}, {}>", hash_function, pairing);
+                let leaves = [b"a", b"b", b"c", b"d"].map(|x| hash_function.digest(x));
+                let (proofs, root) = merkle_tree(hash_function, pairing, &leaves);
+                for (index, (leaf, siblings)) in leaves.iter().zip(proofs.iter()).enumerate() {
+                    assert_eq!(vec![Elem::Bool(true)],
+                               verify_merkle_proof(&instruction, leaf, siblings, index as u64, &root)
+                               .expect("failed to verify").stack, "{} {}", instruction, index);
+                    assert_eq!(vec![Elem::Bool(false)],
+                               verify_merkle_proof(&instruction, &leaves[(index + 1) % 4], siblings, index as u64, &root)
+                               .expect("failed to verify").stack, "{} {}", instruction, index);
+                }
+            }
+        }
+
+        let error = run(parse(r#"push 0x00; push ["0xzz"]; push 0; push 0x00; verify_merkle_proof<sha256, sorted>"#)
+                        .expect("failed to parse").instructions).expect_err("invalid sibling");
+        assert!(error.to_string().contains("sibling 0 to be a hex string"), "{}", error);
+
+        let leaves = [b"a", b"b", b"c", b"d"].map(|x| HashFunction::Sha256.digest(x));
+        let (proofs, root) = merkle_tree(HashFunction::Sha256, MerklePairing::Positional, &leaves);
+        for (leaf, siblings, index, root, expected_error) in [
+            (&[0x00][..], &[][..], 0, &[0x00][..], "expected at least one sibling"),
+            (&root, &[][..], 0, &root, "expected at least one sibling"),
+            (&leaves[0], &proofs[0], 4, &root, "expected an index less than 2^2, but found 4"),
+            (&leaves[0], &proofs[0], 1 << 40, &root, "expected an index less than 2^2"),
+            (&leaves[0][1..], &proofs[0], 0, &root, "invalid leaf: expected a 32 byte sha256 digest, but found 31 bytes"),
+            (&leaves[0], &proofs[0], 0, &root[1..], "invalid root: expected a 32 byte sha256 digest"),
+            (&leaves[0], &[proofs[0][0].clone(), vec![0x00]], 0, &root, "invalid sibling 1: expected a 32 byte sha256 digest"),
+        ] {
+            let error = verify_merkle_proof("verify_merkle_proof<sha256, positional>", leaf, siblings, index, root)
+                .expect_err(expected_error);
+            assert!(error.to_string().contains(expected_error), "{}", error);
+        }
+        let error = verify_merkle_proof("verify_merkle_proof<sha256, sorted>", &root, &[], 0, &root)
+            .expect_err("empty proof");
+        assert!(error.to_string().contains("expected at least one sibling"), "{}", error);
+        let error = verify_merkle_proof("verify_merkle_proof<sha256, sorted>", &[0x00], &proofs[0][..1], 0, &root)
+            .expect_err("invalid leaf");
+        assert!(error.to_string().contains("invalid leaf"), "{}", error);
+        assert_eq!(Instruction::VerifyMerkleProof(HashFunction::Blake3, MerklePairing::Positional),
+                   parse("verify_merkle_proof<blake3, positional>").expect("failed to parse").instructions[0]);
+        assert!(parse("verify_merkle_proof<md5, sorted>").is_err());
+    }
+
+    fn bls12_381_secret_key(seed: u8) -> blst::min_pk::SecretKey {
+        blst::min_pk::SecretKey::key_gen(&[seed; 32], &[]).expect("invalid key material")
+    }
+
+    #[test]
+    fn test_verify_bls12_381() {
+        let secret_key = bls12_381_secret_key(1);
+        let public_key = secret_key.sk_to_pk().compress().to_vec();
+        let signature = secret_key.sign(b"I am the walrus.", BLS12_381_DST, &[]).compress().to_vec();
+        let verify = |message: &[u8], signature: &[u8], public_key: &[u8]| run(vec![
+            Instruction::Push(Elem::Bytes(message.to_vec())),
+            Instruction::Push(Elem::Bytes(signature.to_vec())),
+            Instruction::Push(Elem::Bytes(public_key.to_vec())),
+            Instruction::VerifyBls12381,
+        ]);
+
+        assert_eq!(vec![Elem::Bool(true)],
+                   verify(b"I am the walrus.", &signature, &public_key).expect("failed to verify").stack);
+        assert_eq!(vec![Elem::Bool(false)],
+                   verify(b"I am the eggman.", &signature, &public_key).expect("failed to verify").stack);
+        let error = verify(b"", &signature, &public_key[1..]).expect_err("malformed public key");
+        assert!(error.to_string().contains("verify_bls12_381: malformed BLS12-381 public key"), "{}", error);
+        let error = verify(b"", &[0; 96], &public_key).expect_err("malformed signature");
+        assert!(error.to_string().contains("verify_bls12_381: malformed BLS12-381 signature"), "{}", error);
+    }
+
+    #[test]
+    fn test_fast_aggregate_verify_bls12_381() {
+        let secret_keys = [bls12_381_secret_key(1), bls12_381_secret_key(2), bls12_381_secret_key(3)];
+        let public_keys: Vec<String> = secret_keys.iter()
+            .map(|secret_key| format!("0x{}", hex::encode(secret_key.sk_to_pk().compress())))
+            .collect();
+        let signatures: Vec<blst::min_pk::Signature> = secret_keys.iter()
+            .map(|secret_key| secret_key.sign(b"I am the walrus.", BLS12_381_DST, &[]))
+            .collect();
+        let signature = blst::min_pk::AggregateSignature::aggregate(&signatures.iter().collect::<Vec<_>>(), true)
+            .expect("failed to aggregate").to_signature().compress();
+        let verify = |public_keys: &[String]| run(parse(&format!(
+            "push b\"I am the walrus.\"; push 0x{}; push {}; fast_aggregate_verify_bls12_381",
+            hex::encode(signature),
+            serde_json::to_string(public_keys).expect("failed to serialize"))).expect("failed to parse").instructions);
+
+        assert_eq!(vec![Elem::Bool(true)], verify(&public_keys).expect("failed to verify").stack);
+        assert_eq!(vec![Elem::Bool(false)], verify(&public_keys[..2]).expect("failed to verify").stack);
+        assert!(matches!(verify(&[]), Err(StackInstructionError::RawStackInstructionError(_))));
+        let error = verify(&["0x00".to_string()]).expect_err("malformed public key");
+        assert!(error.to_string().contains("fast_aggregate_verify_bls12_381: malformed BLS12-381 public key"), "{}", error);
+    }
+
+    #[test]
+    fn test_check_multisig() {
+        let signing_keys: Vec<ed25519_dalek::Keypair> = (1..=3u8).map(|seed| {
+            let secret = ed25519_dalek::SecretKey::from_bytes(&[seed; 32]).expect("invalid secret key");
+            let public = ed25519_dalek::PublicKey::from(&secret);
+            ed25519_dalek::Keypair { secret: secret, public: public }
+        }).collect();
+        let public_keys: Vec<String> = signing_keys.iter().map(|x| hex::encode(x.public.as_bytes())).collect();
+        let signatures: Vec<String> = signing_keys.iter()
+            .map(|x| hex::encode(ed25519_dalek::Signer::sign(x, b"I am the walrus.").to_bytes()))
+            .collect();
+        let check_multisig = |signatures: &[String], threshold: u64| run(parse(&format!(
+            "push b\"I am the walrus.\"; push {}; push {}; push {}; check_multisig<ed25519>",
+            serde_json::to_string(&public_keys).expect("failed to serialize"),
+            serde_json::to_string(signatures).expect("failed to serialize"),
+            threshold)).expect("failed to parse").instructions);
+
+        let two_of_three = [signatures[2].clone(), signatures[0].clone()];
+        assert_eq!(vec![Elem::Bool(true)], check_multisig(&two_of_three, 2).expect("failed to check").stack);
+        assert_eq!(vec![Elem::Bool(false)], check_multisig(&two_of_three, 3).expect("failed to check").stack);
+        let duplicated = [signatures[1].clone(), signatures[1].clone()];
+        assert_eq!(vec![Elem::Bool(false)], check_multisig(&duplicated, 2).expect("failed to check").stack);
+        for threshold in [0, 4] {
+            let error = check_multisig(&signatures, threshold).expect_err("threshold out of range");
+            assert!(error.to_string().contains(&format!("from 1 to the number of public keys (3), but found {}", threshold)), "{}", error);
+        }
+        let error = run(parse(r#"push b"m"; push []; push []; push 0; check_multisig<ed25519>"#)
+                        .expect("failed to parse").instructions).expect_err("zero threshold");
+        assert!(error.to_string().contains("but found 0"), "{}", error);
+        let error = run(parse(r#"push b"m"; push [1]; push []; push 1; check_multisig<ed25519>"#)
+                        .expect("failed to parse").instructions).expect_err("public key isn't a string");
+        assert!(error.to_string().contains("expected public_keys element 0 to be a hex string"), "{}", error);
+        let error = check_multisig(&["0x00".to_string()], 1).expect_err("malformed signature");
+        assert!(error.to_string().contains("malformed ed25519 signature"), "{}", error);
+
+        assert!(matches!(parse("check_multisig<rsa>"), Err(crate::parse::ParseError::UnsupportedKeyType { .. })));
+    }
+
+    // personal_sign of "Some data" by the private key
+    // 0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318
+    const ADDRESS: [u8; 20] = hex!("2c7536e3605d9c16a7a3d7b1898e529396a65c23");
+    const SIGNATURE: [u8; 65] = hex!("b91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c");
+
+    fn ecrecover(message: &[u8], signature: Vec<u8>) -> Result<Stack, StackInstructionError> {
+        run(vec![
+            Instruction::Push(Elem::Bytes(message.to_vec())),
+            Instruction::HashEip191,
+            Instruction::Push(Elem::Bytes(signature)),
+            Instruction::EcRecover,
+        ])
+    }
+
+    #[test]
+    fn test_ecrecover() {
+        let hashed = run(vec![Instruction::Push(Elem::Bytes(b"Some data".to_vec())), Instruction::HashEip191])
+            .expect("failed to hash");
+        assert_eq!(vec![Elem::Bytes(hex!("1da44b586eb0729ff70a73c326926f6ed5a25f5b056e7f47fbc6e58d86871655").to_vec())],
+                   hashed.stack);
+
+        assert_eq!(vec![Elem::Bytes(ADDRESS.to_vec())],
+                   ecrecover(b"Some data", SIGNATURE.to_vec()).expect("failed to recover").stack);
+        let mut signature = SIGNATURE.to_vec();
+        signature[64] -= 27;
+        assert_eq!(vec![Elem::Bytes(ADDRESS.to_vec())],
+                   ecrecover(b"Some data", signature.clone()).expect("failed to recover").stack);
+        assert_ne!(vec![Elem::Bytes(ADDRESS.to_vec())],
+                   ecrecover(b"Some other data", signature.clone()).expect("failed to recover").stack);
+
+        signature[64] = 2;
+        let error = ecrecover(b"Some data", signature).expect_err("invalid recovery id");
+        assert!(error.to_string().contains("recovery id (v)"), "{}", error);
+        let error = ecrecover(b"Some data", SIGNATURE[..64].to_vec()).expect_err("invalid signature length");
+        assert!(error.to_string().contains("65-byte signature"), "{}", error);
+        let error = run(vec![
+            Instruction::Push(Elem::Bytes(b"Some data".to_vec())),
+            Instruction::Push(Elem::Bytes(SIGNATURE.to_vec())),
+            Instruction::EcRecover,
+        ]).expect_err("invalid digest length");
+        assert!(error.to_string().contains("32-byte digest"), "{}", error);
+
+        assert_eq!(vec![Instruction::HashEip191, Instruction::EcRecover],
+                   parse("hash_eip191; ecrecover;").expect("failed to parse").instructions);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::elems_singleton::Singleton;
+use crate::elems_input_output_singleton::ReturnSingleton;
+use crate::elems_list::IsList;
+use crate::elems_list_nil::Nil;
+use crate::elems_list_cons::Cons;
+use crate::elems_list_input_output_cons::ConsOut;
+use crate::did::{check_did_document, resolve_did, select_verification_method, DidError};
+use crate::untyped_instruction::Instruction;
+use crate::typed_instruction::{IsInstructionT, StackInstructionError};
+
+use std::marker::PhantomData;
+
+use generic_array::typenum::{U0, U1};
+use serde_json::{Map, Value};
+
+
+/// input: [did: String]
+/// output: [document: Object]
+///
+/// Resolve a did:key or did:pkh to its DID document, without any queries
+///
+/// Fails for other DID methods: a did:web is resolved by a query (see
+/// did_web_query_template), whose result is checked with check_did_document
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResolveDid {}
+
+impl IsInstructionT for ResolveDid {
+    type IO = ConsOut<ReturnSingleton<Map<String, Value>, U0>, Cons<Singleton<String, U1>, Nil>>;
+    type Error = DidError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::ResolveDid)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "resolve_did".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array;
+        returning.returning(resolve_did(&array[0])?);
+        Ok(())
+    }
+}
+
+/// input: [did: String, document: Object]
+/// output: [document: Object]
+///
+/// Check that the document (e.g. a did:web query's result) is the DID
+/// document of the given DID, i.e. "push document; push did; check_did_document"
+///
+/// Fails unless the document's id is the DID
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CheckDidDocument {}
+
+impl IsInstructionT for CheckDidDocument {
+    type IO = ConsOut<ReturnSingleton<Map<String, Value>, U0>,
+                 Cons<Singleton<String, U1>,
+                 Cons<Singleton<Map<String, Value>, U1>, Nil>>>;
+    type Error = DidError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::CheckDidDocument)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "check_did_document".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let did = &x.clone().tl().hd().array[0];
+        let document = &x.clone().tl().tl().hd().array[0];
+        check_did_document(did, document)?;
+        returning.returning(document.clone());
+        Ok(())
+    }
+}
+
+/// input: [id: String, document: Object]
+/// output: [verification_method: Object]
+///
+/// Select a verification method from a DID document by its id, which may be
+/// relative (e.g. "#key-1"), i.e. "push document; push id; verification_method"
+///
+/// Fails if there's no such verification method
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerificationMethod {}
+
+impl IsInstructionT for VerificationMethod {
+    type IO = ConsOut<ReturnSingleton<Map<String, Value>, U0>,
+                 Cons<Singleton<String, U1>,
+                 Cons<Singleton<Map<String, Value>, U1>, Nil>>>;
+    type Error = DidError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::VerificationMethod)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "verification_method".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let id = &x.clone().tl().hd().array[0];
+        let document = &x.clone().tl().tl().hd().array[0];
+        returning.returning(select_verification_method(document, id)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elem::Elem;
+    use crate::parse::parse;
+    use crate::stack::Stack;
+
+    use serde_json::json;
+
+    fn run(source: &str) -> Result<Stack, StackInstructionError> {
+        let mut stack = Stack::new();
+        parse(source).expect("failed to parse")
+            .to_instrs().expect("failed to type instructions")
+            .run(&mut stack)?;
+        Ok(stack)
+    }
+
+    #[test]
+    fn test_did_key_verification_method() {
+        let did = "did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK";
+        let source = format!(r##"push "{}"; resolve_did; push "#z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK"; verification_method"##, did);
+        let expected = json!({
+            "id": format!("{}#z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK", did),
+            "type": "Multikey",
+            "controller": did,
+            "publicKeyMultibase": "z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK",
+            "publicKeyJwk": {"kty": "OKP", "crv": "Ed25519", "x": "Lm_M42cB3HkUiODQsXRcweM6TByfzEHGO9ND274JcOY"},
+        });
+        assert_eq!(vec![Elem::Object(expected.as_object().expect("not an Object").clone())],
+                   run(&source).expect("failed to run").stack);
+
+        let error = run(&format!(r##"push "{}"; resolve_did; push "#key-1"; verification_method"##, did)).expect_err("unknown method");
+        assert!(error.to_string().contains("#key-1"), "{}", error);
+        let error = run(&format!(r#"push "{}"; resolve_did; push "did:web:evil.com"; check_did_document"#, did)).expect_err("wrong DID");
+        assert!(error.to_string().contains("did:web:evil.com"), "{}", error);
+        let error = run(r#"push "did:web:credentials.corp.com"; resolve_did"#).expect_err("did:web");
+        assert!(error.to_string().contains("did:web:credentials.corp.com"), "{}", error);
+    }
+}
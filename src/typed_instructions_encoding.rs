@@ -0,0 +1,198 @@
+use crate::elems_singleton::Singleton;
+use crate::elems_input_output_singleton::ReturnSingleton;
+use crate::elems_list::IsList;
+use crate::elems_list_nil::Nil;
+use crate::elems_list_cons::Cons;
+use crate::elems_list_input_output_cons::ConsOut;
+use crate::multiformats::{decode_multibase, parse_multihash, split_multicodec, MultiformatsError};
+use crate::untyped_instruction::Instruction;
+use crate::typed_instruction::{IsInstructionT, StackInstructionError};
+
+use std::marker::PhantomData;
+
+use generic_array::typenum::{U0, U1, U2};
+use serde_json::Number;
+
+
+/// input: [String]
+/// output: [Bytes]
+///
+/// Decode a multibase String, e.g. "z6Mk.." (base58btc) or "bafy.." (base32)
+///
+/// Fails if the base prefix is unknown or the String isn't valid in that base
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeMultibase {}
+
+impl IsInstructionT for DecodeMultibase {
+    type IO = ConsOut<ReturnSingleton<Vec<u8>, U0>, Cons<Singleton<String, U1>, Nil>>;
+    type Error = MultiformatsError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::DecodeMultibase)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "decode_multibase".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array;
+        returning.returning(decode_multibase(&array[0])?);
+        Ok(())
+    }
+}
+
+/// input: [Bytes]
+/// output: [code: Number]
+///
+/// The multicodec code prefixing the Bytes, e.g. 0xed for an Ed25519 public key
+///
+/// Fails if the Bytes don't start with a valid varint
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MulticodecCode {}
+
+impl IsInstructionT for MulticodecCode {
+    type IO = ConsOut<ReturnSingleton<Number, U0>, Cons<Singleton<Vec<u8>, U1>, Nil>>;
+    type Error = MultiformatsError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::MulticodecCode)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "multicodec_code".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array;
+        let (code, _payload) = split_multicodec(&array[0])?;
+        returning.returning(From::from(code));
+        Ok(())
+    }
+}
+
+/// input: [code: Number, Bytes]
+/// output: [payload: Bytes]
+///
+/// Remove the expected multicodec prefix from the Bytes, i.e.
+/// "push bytes; push code; strip_multicodec"
+///
+/// Fails if the Bytes are prefixed by any other code
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StripMulticodec {}
+
+impl IsInstructionT for StripMulticodec {
+    type IO = ConsOut<ReturnSingleton<Vec<u8>, U0>,
+                 Cons<Singleton<Number,  U1>,
+                 Cons<Singleton<Vec<u8>, U1>, Nil>>>;
+    type Error = MultiformatsError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::StripMulticodec)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "strip_multicodec".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let code = &x.clone().tl().hd().array[0];
+        let bytes = &x.clone().tl().tl().hd().array[0];
+        let expected = code.as_u64()
+            .ok_or_else(|| MultiformatsError::CodeNotU64 {
+                code: code.clone(),
+            })?;
+        let (found, payload) = split_multicodec(bytes)?;
+        if found != expected {
+            return Err(MultiformatsError::UnexpectedMulticodec {
+                expected: expected,
+                found: found,
+            })
+        }
+        returning.returning(payload.to_vec());
+        Ok(())
+    }
+}
+
+/// input: [multihash: Bytes, data: Bytes]
+/// output: [is_valid: bool]
+///
+/// Check that the multihash is a hash of the data, i.e.
+/// "push data; push multihash; verify_multihash"
+///
+/// Fails if the multihash is malformed (including truncated or empty
+/// digests) or its HashFunction is unsupported
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifyMultihash {}
+
+impl IsInstructionT for VerifyMultihash {
+    type IO = ConsOut<ReturnSingleton<bool, U0>, Cons<Singleton<Vec<u8>, U2>, Nil>>;
+    type Error = MultiformatsError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::VerifyMultihash)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "verify_multihash".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array;
+        let (hash_function, digest) = parse_multihash(&array[0])?;
+        returning.returning(hash_function.digest(&array[1]) == digest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elem::Elem;
+    use crate::parse::parse;
+    use crate::stack::Stack;
+
+    use hex_literal::hex;
+
+    fn run(source: &str) -> Result<Stack, StackInstructionError> {
+        let mut stack = Stack::new();
+        parse(source).expect("failed to parse")
+            .to_instrs().expect("failed to type instructions")
+            .run(&mut stack)?;
+        Ok(stack)
+    }
+
+    #[test]
+    fn test_did_key_multibase() {
+        let did_key = r#"push "z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK"; decode_multibase;"#;
+        assert_eq!(vec![Elem::Number(From::from(0xedu64))],
+                   run(&format!("{} multicodec_code", did_key)).expect("failed to run").stack);
+        assert_eq!(vec![Elem::Bytes(hex!("2e6fcce36701dc791488e0d0b1745cc1e33a4c1c9fcc41c63bd343dbbe0970e6").to_vec())],
+                   run(&format!("{} push 237; strip_multicodec", did_key)).expect("failed to run").stack);
+
+        let error = run(&format!("{} push 231; strip_multicodec", did_key)).expect_err("unexpected multicodec");
+        assert!(error.to_string().contains("expected multicodec 0xe7, but found 0xed"), "{}", error);
+        let error = run(r#"push "q123"; decode_multibase"#).expect_err("invalid multibase");
+        assert!(error.to_string().contains("invalid multibase string"), "{}", error);
+    }
+
+    #[test]
+    fn test_verify_multihash() {
+        let multihash = "0x1220b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+        assert_eq!(vec![Elem::Bool(true)],
+                   run(&format!(r#"push b"hello world"; push {}; verify_multihash"#, multihash)).expect("failed to run").stack);
+        assert_eq!(vec![Elem::Bool(false)],
+                   run(&format!(r#"push b"hello walrus"; push {}; verify_multihash"#, multihash)).expect("failed to run").stack);
+
+        for multihash in ["0x1200", "0x1204b94d27b9", "0x1205b94d27b9", &format!("{}00", multihash)] {
+            let error = run(&format!(r#"push b"hello world"; push {}; verify_multihash"#, multihash)).expect_err("invalid length");
+            assert!(error.to_string().contains("invalid sha256 multihash"), "{} {}", multihash, error);
+        }
+        let error = run(r#"push b"hello world"; push 0xd50100; verify_multihash"#).expect_err("unsupported multihash");
+        assert!(error.to_string().contains("unsupported multihash code: 0xd5"), "{}", error);
+    }
+}
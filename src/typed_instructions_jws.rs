@@ -0,0 +1,289 @@
+use crate::elems_singleton::Singleton;
+use crate::elems_input_output_singleton::ReturnSingleton;
+use crate::elems_list::IsList;
+use crate::elems_list_nil::Nil;
+use crate::elems_list_cons::Cons;
+use crate::elems_list_input_output_cons::ConsOut;
+use crate::jws::{numeric_date, Jws, JwsError};
+use crate::untyped_instruction::Instruction;
+use crate::typed_instruction::{IsInstructionT, StackInstructionError};
+
+use std::marker::PhantomData;
+
+use generic_array::typenum::{U0, U1};
+use serde_json::{Map, Number, Value};
+
+
+/// input: [jws: String]
+/// output: [header: Object]
+///
+/// The decoded protected header of a compact JWS, e.g. {"alg": "ES256"}
+///
+/// Fails if the JWS isn't compact, or its header or payload isn't a
+/// base64url-encoded JSON Object
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JwsHeader {}
+
+impl IsInstructionT for JwsHeader {
+    type IO = ConsOut<ReturnSingleton<Map<String, Value>, U0>, Cons<Singleton<String, U1>, Nil>>;
+    type Error = JwsError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::JwsHeader)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "jws_header".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array;
+        returning.returning(Jws::parse(&array[0])?.header);
+        Ok(())
+    }
+}
+
+/// input: [jws: String]
+/// output: [payload: Object]
+///
+/// The decoded payload of a compact JWS, e.g. the claims of a JWT
+///
+/// The signature is NOT verified: see verify_jws
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JwsPayload {}
+
+impl IsInstructionT for JwsPayload {
+    type IO = ConsOut<ReturnSingleton<Map<String, Value>, U0>, Cons<Singleton<String, U1>, Nil>>;
+    type Error = JwsError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::JwsPayload)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "jws_payload".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array;
+        returning.returning(Jws::parse(&array[0])?.payload);
+        Ok(())
+    }
+}
+
+/// input: [jws: String]
+/// output: [signature: Bytes]
+///
+/// The decoded signature of a compact JWS
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JwsSignature {}
+
+impl IsInstructionT for JwsSignature {
+    type IO = ConsOut<ReturnSingleton<Vec<u8>, U0>, Cons<Singleton<String, U1>, Nil>>;
+    type Error = JwsError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::JwsSignature)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "jws_signature".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array;
+        returning.returning(Jws::parse(&array[0])?.signature);
+        Ok(())
+    }
+}
+
+/// input: [jwk: Object, jws: String]
+/// output: [is_valid: bool]
+///
+/// Verify a compact JWS signed with ES256, ES256K or EdDSA (Ed25519), i.e.
+/// "push jws; push jwk; verify_jws"
+///
+/// Fails if the JWS or JWK is malformed, or the JWK doesn't match the JWS's "alg"
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifyJws {}
+
+impl IsInstructionT for VerifyJws {
+    type IO = ConsOut<ReturnSingleton<bool, U0>,
+                 Cons<Singleton<Map<String, Value>, U1>,
+                 Cons<Singleton<String, U1>, Nil>>>;
+    type Error = JwsError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::VerifyJws)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "verify_jws".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let jwk = &x.clone().tl().hd().array[0];
+        let jws = &x.clone().tl().tl().hd().array[0];
+        returning.returning(Jws::parse(jws)?.verify(jwk)?);
+        Ok(())
+    }
+}
+
+/// input: [claims: Object]
+/// output: [exp: Number]
+///
+/// The "exp" (expiration time) claim of a JWT, in seconds since the epoch
+///
+/// Fails if "exp" is missing or not a Number
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JwtExp {}
+
+impl IsInstructionT for JwtExp {
+    type IO = ConsOut<ReturnSingleton<Number, U0>, Cons<Singleton<Map<String, Value>, U1>, Nil>>;
+    type Error = JwsError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::JwtExp)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "jwt_exp".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array;
+        returning.returning(numeric_date(&array[0], "exp")?);
+        Ok(())
+    }
+}
+
+/// input: [claims: Object]
+/// output: [nbf: Number]
+///
+/// The "nbf" (not before) claim of a JWT, in seconds since the epoch
+///
+/// Fails if "nbf" is missing or not a Number
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JwtNbf {}
+
+impl IsInstructionT for JwtNbf {
+    type IO = ConsOut<ReturnSingleton<Number, U0>, Cons<Singleton<Map<String, Value>, U1>, Nil>>;
+    type Error = JwsError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::JwtNbf)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "jwt_nbf".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array;
+        returning.returning(numeric_date(&array[0], "nbf")?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elem::Elem;
+    use crate::parse::parse;
+    use crate::stack::Stack;
+
+    use base64::Engine;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use k256::ecdsa::signature::Signer;
+    use k256::elliptic_curve::sec1::ToEncodedPoint;
+    use serde_json::json;
+
+    fn run(source: &str) -> Result<Stack, StackInstructionError> {
+        let mut stack = Stack::new();
+        parse(source).expect("failed to parse")
+            .to_instrs().expect("failed to type instructions")
+            .run(&mut stack)?;
+        Ok(stack)
+    }
+
+    fn sign_jws(alg: &str, sign: impl Fn(&[u8]) -> Vec<u8>) -> String {
+        let header = URL_SAFE_NO_PAD.encode(json!({"alg": alg}).to_string());
+        let payload = URL_SAFE_NO_PAD.encode(json!({"sub": "walrus", "nbf": 1600000000, "exp": 1700000000}).to_string());
+        let signing_input = format!("{}.{}", header, payload);
+        format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(sign(signing_input.as_bytes())))
+    }
+
+    fn ec_jwk(crv: &str, point: &[u8]) -> Value {
+        json!({
+            "kty": "EC",
+            "crv": crv,
+            "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&point[33..]),
+        })
+    }
+
+    #[test]
+    fn test_es256_jws() {
+        let signing_key = p256::ecdsa::SigningKey::from_bytes(&[0x11; 32]).expect("invalid p256 key");
+        let jws = sign_jws("ES256", |message| {
+            let signature: p256::ecdsa::Signature = signing_key.sign(message);
+            signature.as_ref().to_vec()
+        });
+        let point = signing_key.verifying_key().to_encoded_point(false);
+        let jwk = ec_jwk("P-256", point.as_bytes());
+        let verify = format!(r#"push "{}"; push {}; verify_jws"#, jws, jwk);
+        assert_eq!(vec![Elem::Bool(true)], run(&verify).expect("failed to run").stack);
+        let tampered = verify.replace(".eyJ", ".eyI");
+        assert_eq!(vec![Elem::Bool(false)], run(&tampered).expect("failed to run").stack);
+
+        assert_eq!(vec![Elem::Number(From::from(1700000000u64))],
+                   run(&format!(r#"push "{}"; jws_payload; jwt_exp"#, jws)).expect("failed to run").stack);
+        assert_eq!(vec![Elem::Object(json!({"alg": "ES256"}).as_object().expect("object").clone())],
+                   run(&format!(r#"push "{}"; jws_header"#, jws)).expect("failed to run").stack);
+        let error = run(&format!(r#"push "{}"; jws_header; jwt_nbf"#, jws)).expect_err("no nbf");
+        assert!(error.to_string().contains(r#"JWT claim "nbf" is missing"#), "{}", error);
+
+        let es256k_jwk = ec_jwk("secp256k1", point.as_bytes());
+        let error = run(&format!(r#"push "{}"; push {}; verify_jws"#, jws, es256k_jwk)).expect_err("wrong curve");
+        assert!(error.to_string().contains("JWS alg is ES256, but the JWK is a secp256k1 key"), "{}", error);
+        let error = run(&format!(r#"push "{}.."; push {}; verify_jws"#, jws, jwk)).expect_err("not compact");
+        assert!(error.to_string().contains("found 5 part(s)"), "{}", error);
+    }
+
+    #[test]
+    fn test_es256k_and_eddsa_jws() {
+        let signing_key = k256::ecdsa::SigningKey::from_bytes(&[0x42; 32]).expect("invalid secp256k1 key");
+        let es256k = sign_jws("ES256K", |message| {
+            let signature: k256::ecdsa::Signature = signing_key.sign(message);
+            signature.as_ref().to_vec()
+        });
+        let point = signing_key.verifying_key().to_encoded_point(false);
+        let es256k_jwk = ec_jwk("secp256k1", point.as_bytes());
+        assert_eq!(vec![Elem::Bool(true)],
+                   run(&format!(r#"push "{}"; push {}; verify_jws"#, es256k, es256k_jwk)).expect("failed to run").stack);
+
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[0x42; 32]).expect("invalid ed25519 key");
+        let keypair = ed25519_dalek::Keypair {
+            public: ed25519_dalek::PublicKey::from(&secret),
+            secret: secret,
+        };
+        let eddsa = sign_jws("EdDSA", |message| keypair.sign(message).to_bytes().to_vec());
+        let eddsa_jwk = json!({"kty": "OKP", "crv": "Ed25519", "x": URL_SAFE_NO_PAD.encode(keypair.public.as_bytes())});
+        assert_eq!(vec![Elem::Bool(true)],
+                   run(&format!(r#"push "{}"; push {}; verify_jws"#, eddsa, eddsa_jwk)).expect("failed to run").stack);
+        assert_eq!(vec![Elem::Number(From::from(1600000000u64))],
+                   run(&format!(r#"push "{}"; jws_payload; jwt_nbf"#, eddsa)).expect("failed to run").stack);
+
+        let error = run(&format!(r#"push "{}"; push {}; verify_jws"#, es256k, eddsa_jwk)).expect_err("wrong key");
+        assert!(error.to_string().contains("JWS alg is ES256K, but the JWK is a ed25519 key"), "{}", error);
+        let none = format!("{}.e30.", URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#));
+        let error = run(&format!(r#"push "{}"; push {}; verify_jws"#, none, eddsa_jwk)).expect_err("alg none");
+        assert!(error.to_string().contains("unsupported JWS alg"), "{}", error);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::elems_singleton::Singleton;
+use crate::elems_input_output_singleton::ReturnSingleton;
+use crate::elems_list::IsList;
+use crate::elems_list_nil::Nil;
+use crate::elems_list_cons::Cons;
+use crate::elems_list_input_output_cons::ConsOut;
+use crate::siwe::{parse_siwe, SiweError};
+use crate::untyped_instruction::Instruction;
+use crate::typed_instruction::{IsInstructionT, StackInstructionError};
+
+use std::marker::PhantomData;
+
+use generic_array::typenum::{U0, U1};
+use serde_json::{Map, Value};
+
+
+/// input: [message: String]
+/// output: [fields: Object]
+///
+/// Parse a Sign-In with Ethereum (EIP-4361) message, e.g. to check the signed
+/// address and nonce, into an Object with the keys domain, address,
+/// statement, uri, version, chainId (a Number), nonce, issuedAt,
+/// expirationTime, notBefore, requestId and resources (an Array), where
+/// optional fields are omitted when absent
+///
+/// Fails if the message is malformed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseSiwe {}
+
+impl IsInstructionT for ParseSiwe {
+    type IO = ConsOut<ReturnSingleton<Map<String, Value>, U0>, Cons<Singleton<String, U1>, Nil>>;
+    type Error = SiweError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::ParseSiwe)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "parse_siwe".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array;
+        returning.returning(parse_siwe(&array[0])?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elem::Elem;
+    use crate::stack::Stack;
+    use crate::untyped_instructions::Instructions;
+
+    fn run(message: &str) -> Result<Stack, StackInstructionError> {
+        let mut stack = Stack::new();
+        Instructions {
+            docs: vec![],
+            instructions: vec![Instruction::Push(Elem::String(message.to_string())), Instruction::ParseSiwe],
+        }.to_instrs()
+            .expect("failed to type instructions")
+            .run(&mut stack)?;
+        Ok(stack)
+    }
+
+    #[test]
+    fn test_parse_siwe_instruction() {
+        let message = "localhost wants you to sign in with your Ethereum account:\n0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed\n\nURI: https://localhost/login\nVersion: 1\nChain ID: 1\nNonce: abcdef0123\nIssued At: 2022-03-17T12:45:13Z";
+        let stack = run(message).expect("failed to run").stack;
+        assert_eq!(vec![Elem::Object(parse_siwe(message).expect("failed to parse"))], stack);
+
+        let error = run(&message.replace("Version: 1", "Version: 2")).expect_err("invalid version");
+        assert!(error.to_string().contains("version"), "{}", error);
+    }
+}
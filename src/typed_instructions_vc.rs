@@ -0,0 +1,431 @@
+use crate::elems_singleton::Singleton;
+use crate::elems_input_output_singleton::ReturnSingleton;
+use crate::elems_list::IsList;
+use crate::elems_list_nil::Nil;
+use crate::elems_list_cons::Cons;
+use crate::elems_list_input_output_cons::ConsOut;
+use crate::jws::Jws;
+use crate::vc::{jwt_vc_credential, jwt_vp_presentation, presentation_signer, validate_credential, validate_presentation, verify_jwt_signer, VcError};
+use crate::untyped_instruction::Instruction;
+use crate::typed_instruction::{IsInstructionT, StackInstructionError};
+
+use std::marker::PhantomData;
+
+use generic_array::typenum::{U0, U1};
+use serde_json::{Map, Number, Value};
+
+
+fn now_seconds(now: &Number) -> Result<i64, VcError> {
+    now.as_i64()
+        .ok_or_else(|| VcError::NowNotInteger {
+            now: now.clone(),
+        })
+}
+
+/// input: [now: Number, credential: Object]
+/// output: [credential: Object]
+///
+/// Check the structure of a W3C Verifiable Credential and that it's valid at
+/// the given time, in seconds since the epoch, i.e.
+/// "push credential; push now; validate_vc"
+///
+/// Fails if the credential is malformed, expired or not yet valid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ValidateVc {}
+
+impl IsInstructionT for ValidateVc {
+    type IO = ConsOut<ReturnSingleton<Map<String, Value>, U0>,
+                 Cons<Singleton<Number, U1>,
+                 Cons<Singleton<Map<String, Value>, U1>, Nil>>>;
+    type Error = VcError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::ValidateVc)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "validate_vc".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let now = &x.clone().tl().hd().array[0];
+        let credential = &x.clone().tl().tl().hd().array[0];
+        validate_credential(credential, now_seconds(now)?)?;
+        returning.returning(credential.clone());
+        Ok(())
+    }
+}
+
+/// input: [now: Number, presentation: Object]
+/// output: [presentation: Object]
+///
+/// Check the structure of a W3C Verifiable Presentation and each of its
+/// credentials, as for validate_vc, i.e.
+/// "push presentation; push now; validate_vp"
+///
+/// Embedded JWT-VC's are decoded, but NOT verified: see verify_jwt_vc
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ValidateVp {}
+
+impl IsInstructionT for ValidateVp {
+    type IO = ConsOut<ReturnSingleton<Map<String, Value>, U0>,
+                 Cons<Singleton<Number, U1>,
+                 Cons<Singleton<Map<String, Value>, U1>, Nil>>>;
+    type Error = VcError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::ValidateVp)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "validate_vp".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let now = &x.clone().tl().hd().array[0];
+        let presentation = &x.clone().tl().tl().hd().array[0];
+        validate_presentation(presentation, now_seconds(now)?)?;
+        returning.returning(presentation.clone());
+        Ok(())
+    }
+}
+
+/// input: [document: Object, jwt: String]
+/// output: [credential: Object]
+///
+/// Verify a JWT-VC with a key of its issuer and return its credential, with
+/// the JWT's claims filled in (e.g. "iss" as the issuer), i.e.
+/// "push jwt; push document; verify_jwt_vc"
+///
+/// The document is the DID document of the JWT's "iss" (e.g. from
+/// resolve_did, or a did:web query), and the key is the publicKeyJwk of one
+/// of its assertionMethod's (see verify_jwt_signer)
+///
+/// Fails unless the document is for the "iss" and the signature is valid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifyJwtVc {}
+
+impl IsInstructionT for VerifyJwtVc {
+    type IO = ConsOut<ReturnSingleton<Map<String, Value>, U0>,
+                 Cons<Singleton<Map<String, Value>, U1>,
+                 Cons<Singleton<String, U1>, Nil>>>;
+    type Error = VcError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::VerifyJwtVc)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "verify_jwt_vc".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let document = &x.clone().tl().hd().array[0];
+        let jws = Jws::parse(&x.clone().tl().tl().hd().array[0])?;
+        verify_jwt_signer(&jws, document, "assertionMethod")?;
+        returning.returning(jwt_vc_credential(&jws)?);
+        Ok(())
+    }
+}
+
+/// input: [document: Object, jwt: String]
+/// output: [presentation: Object]
+///
+/// Verify a JWT-VP with a key of its holder and return its presentation,
+/// with "iss" filled in as the holder, i.e.
+/// "push jwt; push document; verify_jwt_vp"
+///
+/// The document is the DID document of the JWT's "iss", and the key is the
+/// publicKeyJwk of one of its authentication methods (see verify_jwt_signer)
+///
+/// Fails unless the document is for the "iss" and the signature is valid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifyJwtVp {}
+
+impl IsInstructionT for VerifyJwtVp {
+    type IO = ConsOut<ReturnSingleton<Map<String, Value>, U0>,
+                 Cons<Singleton<Map<String, Value>, U1>,
+                 Cons<Singleton<String, U1>, Nil>>>;
+    type Error = VcError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::VerifyJwtVp)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "verify_jwt_vp".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let document = &x.clone().tl().hd().array[0];
+        let jws = Jws::parse(&x.clone().tl().tl().hd().array[0])?;
+        verify_jwt_signer(&jws, document, "authentication")?;
+        returning.returning(jwt_vp_presentation(&jws)?);
+        Ok(())
+    }
+}
+
+/// input: [presentation: Object]
+/// output: [signer: String]
+///
+/// The signer (holder) of a presentation, e.g. a DID
+///
+/// Fails if the holder is missing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VpSigner {}
+
+impl IsInstructionT for VpSigner {
+    type IO = ConsOut<ReturnSingleton<String, U0>, Cons<Singleton<Map<String, Value>, U1>, Nil>>;
+    type Error = VcError;
+
+    fn to_instruction(&self) -> Result<Instruction, StackInstructionError> {
+        Ok(Instruction::VpSigner)
+    }
+
+    fn name(_x: PhantomData<Self>) -> String {
+        "vp_signer".to_string()
+    }
+
+    fn run(&self, x: &Self::IO) -> Result<(), Self::Error> {
+        let returning = x.clone().hd().returning;
+        let array = x.clone().tl().hd().array;
+        returning.returning(presentation_signer(&array[0])?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elem::Elem;
+    use crate::parse::parse;
+    use crate::stack::Stack;
+    use crate::did::DID_CONTEXT_V1;
+    use crate::vc::CREDENTIALS_CONTEXT_V1;
+
+    use base64::Engine;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use k256::ecdsa::signature::Signer;
+    use serde_json::json;
+
+    // The README's policy
+    const ENGINEER_ACCESS: &str = r#"
+        define engineer_access(now, issuer_document, vp_jwt) {
+            vp_jwt;
+            push "did:key:zQ3shjyJXUaRJC2GC43mX8aPrUhoTdoiongXhZjsdTzPKYZUM";
+            resolve_did;
+            verify_jwt_vp;
+            let vp;
+            vp; now; validate_vp;
+            vp_signer;
+            push "did:key:zQ3shjyJXUaRJC2GC43mX8aPrUhoTdoiongXhZjsdTzPKYZUM";
+            assert_equal;
+            vp.verifiableCredential[0]<str>; issuer_document; verify_jwt_vc;
+            now; validate_vc;
+            let vc;
+            vc.issuer;
+            push [
+                "did:pkh:eip155:1:0xb9c5714089478a327f09197987f16f9e5d936e8a",
+                "did:ion:EiD3DIbDgBCajj2zCkE48x74FKTV9_Dcu1u_imzZddDKfg",
+                "did:web:credentials.corp.com"
+            ];
+            assert_member;
+            vc.credentialSubject.department<str>;
+            push "engineering";
+            assert_equal;
+            vc.credentialSubject.access_level<Number>;
+            push 2;
+            assert_ge;
+        }
+    "#;
+
+    // The did:key of holder_key(0x11)
+    const HOLDER: &str = "did:key:zQ3shjyJXUaRJC2GC43mX8aPrUhoTdoiongXhZjsdTzPKYZUM";
+
+    fn sign_jwt(alg: &str, kid: Option<&str>, payload: Value, sign: impl Fn(&[u8]) -> Vec<u8>) -> String {
+        let mut header = json!({"alg": alg, "typ": "JWT"});
+        if let Some(kid) = kid {
+            header["kid"] = json!(kid);
+        }
+        let header = URL_SAFE_NO_PAD.encode(header.to_string());
+        let signing_input = format!("{}.{}", header, URL_SAFE_NO_PAD.encode(payload.to_string()));
+        format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(sign(signing_input.as_bytes())))
+    }
+
+    fn issuer_keypair(seed: u8) -> ed25519_dalek::Keypair {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[seed; 32]).expect("invalid ed25519 key");
+        ed25519_dalek::Keypair {
+            public: ed25519_dalek::PublicKey::from(&secret),
+            secret: secret,
+        }
+    }
+
+    fn holder_key(seed: u8) -> k256::ecdsa::SigningKey {
+        k256::ecdsa::SigningKey::from_bytes(&[seed; 32]).expect("invalid secp256k1 key")
+    }
+
+    // A did:web document whose only assertionMethod is "#key-1"
+    fn issuer_document(issuer: &str, keypair: &ed25519_dalek::Keypair) -> Value {
+        json!({
+            "@context": [DID_CONTEXT_V1],
+            "id": issuer,
+            "verificationMethod": [{
+                "id": "#key-1",
+                "type": "JsonWebKey2020",
+                "controller": issuer,
+                "publicKeyJwk": {"kty": "OKP", "crv": "Ed25519", "x": URL_SAFE_NO_PAD.encode(keypair.public.as_bytes())},
+            }],
+            "assertionMethod": ["#key-1"],
+        })
+    }
+
+    fn sign_vc_jwt(keypair: &ed25519_dalek::Keypair, kid: Option<&str>, issuer: &str, access_level: u64) -> String {
+        sign_jwt("EdDSA", kid, json!({
+            "iss": issuer,
+            "sub": HOLDER,
+            "nbf": 1600000000,
+            "exp": 1900000000,
+            "vc": {
+                "@context": [CREDENTIALS_CONTEXT_V1],
+                "type": ["VerifiableCredential", "EmployeeCredential"],
+                "credentialSubject": {"department": "engineering", "access_level": access_level},
+            },
+        }), |message| keypair.sign(message).to_bytes().to_vec())
+    }
+
+    fn sign_vp_jwt(signing_key: &k256::ecdsa::SigningKey, vc_jwt: &str) -> String {
+        sign_jwt("ES256K", None, json!({
+            "iss": HOLDER,
+            "vp": {
+                "@context": [CREDENTIALS_CONTEXT_V1],
+                "type": ["VerifiablePresentation"],
+                "verifiableCredential": [vc_jwt],
+            },
+        }), |message| {
+            let signature: k256::ecdsa::Signature = signing_key.sign(message);
+            signature.as_ref().to_vec()
+        })
+    }
+
+    struct Fixture {
+        issuer_document: Value,
+        vc_jwt: String,
+        vp_jwt: String,
+    }
+
+    fn fixture(issuer: &str, access_level: u64) -> Fixture {
+        let keypair = issuer_keypair(0x42);
+        let vc_jwt = sign_vc_jwt(&keypair, Some("#key-1"), issuer, access_level);
+        Fixture {
+            issuer_document: issuer_document(issuer, &keypair),
+            vp_jwt: sign_vp_jwt(&holder_key(0x11), &vc_jwt),
+            vc_jwt: vc_jwt,
+        }
+    }
+
+    fn run(source: &str) -> Result<Stack, StackInstructionError> {
+        let mut stack = Stack::new();
+        parse(source).expect("failed to parse")
+            .to_instrs().expect("failed to type instructions")
+            .run(&mut stack)?;
+        Ok(stack)
+    }
+
+    fn run_engineer_access(now: u64, fixture: &Fixture) -> Result<Stack, StackInstructionError> {
+        run(&format!(r#"{} push {}; push {}; push "{}"; engineer_access;"#,
+                     ENGINEER_ACCESS, now, fixture.issuer_document, fixture.vp_jwt))
+    }
+
+    fn verify_jwt_vc(vc_jwt: &str, document: &Value) -> Result<Stack, StackInstructionError> {
+        run(&format!(r#"push "{}"; push {}; verify_jwt_vc"#, vc_jwt, document))
+    }
+
+    #[test]
+    fn test_engineer_access() {
+        let valid = fixture("did:web:credentials.corp.com", 3);
+        assert_eq!(vec![Elem::Bool(true); 4], run_engineer_access(1700000000, &valid).expect("failed to run").stack);
+
+        let error = run_engineer_access(1900000000, &valid).expect_err("expired");
+        assert!(error.to_string().contains("expired at expirationDate (1900000000), but now is 1900000000"), "{}", error);
+        let error = run_engineer_access(1500000000, &valid).expect_err("not yet valid");
+        assert!(error.to_string().contains("not valid until issuanceDate"), "{}", error);
+        assert!(run_engineer_access(1700000000, &fixture("did:web:evil.com", 3)).is_err());
+        assert!(run_engineer_access(1700000000, &fixture("did:web:credentials.corp.com", 1)).is_err());
+
+        // The holder's key comes from its did:key, not from the presenter
+        let mut wrong_holder_key = fixture("did:web:credentials.corp.com", 3);
+        wrong_holder_key.vp_jwt = sign_vp_jwt(&holder_key(0x66), &wrong_holder_key.vc_jwt);
+        let error = run_engineer_access(1700000000, &wrong_holder_key).expect_err("wrong holder key");
+        assert!(error.to_string().contains("invalid JWT signature"), "{}", error);
+    }
+
+    #[test]
+    fn test_verify_jwt_vc() {
+        let valid = fixture("did:web:credentials.corp.com", 3);
+        let stack = verify_jwt_vc(&valid.vc_jwt, &valid.issuer_document).expect("failed to run").stack;
+        assert_eq!(vec![Elem::Object(json!({
+            "@context": [CREDENTIALS_CONTEXT_V1],
+            "type": ["VerifiableCredential", "EmployeeCredential"],
+            "credentialSubject": {"department": "engineering", "access_level": 3, "id": HOLDER},
+            "issuer": "did:web:credentials.corp.com",
+            "issuanceDate": "2020-09-13T12:26:40Z",
+            "expirationDate": "2030-03-17T17:46:40Z",
+        }).as_object().expect("object").clone())], stack);
+        let without_kid = sign_vc_jwt(&issuer_keypair(0x42), None, "did:web:credentials.corp.com", 3);
+        assert_eq!(stack, verify_jwt_vc(&without_kid, &valid.issuer_document).expect("failed to run").stack);
+
+        let forged = format!("{}.{}", fixture("did:web:credentials.corp.com", 9).vc_jwt.rsplit_once('.').expect("compact").0,
+                             valid.vc_jwt.rsplit_once('.').expect("compact").1);
+        let error = verify_jwt_vc(&forged, &valid.issuer_document).expect_err("forged");
+        assert!(error.to_string().contains("invalid JWT signature"), "{}", error);
+
+        let error = run(r#"push {"@context": ["https://www.w3.org/2018/credentials/v1"], "type": "VerifiableCredential",
+                                  "issuer": "did:web:credentials.corp.com", "credentialSubject": []};
+                            push 1700000000; validate_vc"#).expect_err("no subject");
+        assert!(error.to_string().contains("credentialSubject must be an Object"), "{}", error);
+        let error = run(r#"push {"@context": ["https://www.w3.org/ns/credentials/v2"], "type": "VerifiableCredential",
+                                  "issuer": "did:web:credentials.corp.com", "credentialSubject": {}, "validUntil": "tomorrow"};
+                            push 1700000000; validate_vc"#).expect_err("invalid date");
+        assert!(error.to_string().contains("validUntil must be an RFC 3339 date-time"), "{}", error);
+        let error = run(r#"push {"@context": "https://www.w3.org/2018/credentials/v1"}; push 1700000000; validate_vp"#)
+            .expect_err("invalid context");
+        assert!(error.to_string().contains("@context must be an Array"), "{}", error);
+    }
+
+    #[test]
+    fn test_verify_jwt_vc_attacker_key() {
+        let valid = fixture("did:web:credentials.corp.com", 3);
+        let attacker = issuer_keypair(0x66);
+        let attacker_did = "did:web:attacker.com";
+
+        // The attacker's key signs a JWT claiming the trusted "iss"
+        let forged = sign_vc_jwt(&attacker, Some("#key-1"), "did:web:credentials.corp.com", 9);
+        let error = verify_jwt_vc(&forged, &valid.issuer_document).expect_err("attacker's key");
+        assert!(error.to_string().contains("invalid JWT signature"), "{}", error);
+
+        // ..with the attacker's own DID document
+        let error = verify_jwt_vc(&forged, &issuer_document(attacker_did, &attacker)).expect_err("attacker's document");
+        assert!(error.to_string().contains("expected a DID document for did:web:credentials.corp.com"), "{}", error);
+
+        // ..or with the attacker's key as a method that isn't an assertionMethod
+        let mut document = valid.issuer_document.clone();
+        document["verificationMethod"].as_array_mut().expect("array").push(json!({
+            "id": "#key-2",
+            "type": "JsonWebKey2020",
+            "controller": "did:web:credentials.corp.com",
+            "publicKeyJwk": {"kty": "OKP", "crv": "Ed25519", "x": URL_SAFE_NO_PAD.encode(attacker.public.as_bytes())},
+        }));
+        let forged = sign_vc_jwt(&attacker, Some("#key-2"), "did:web:credentials.corp.com", 9);
+        let error = verify_jwt_vc(&forged, &document).expect_err("not an assertionMethod");
+        assert!(error.to_string().contains("did:web:credentials.corp.com has no assertionMethod verification method"), "{}", error);
+
+        // ..or with another DID's (resolved) document
+        let error = verify_jwt_vc(&valid.vc_jwt, &json!(crate::did::resolve_did(HOLDER).expect("failed to resolve")))
+            .expect_err("wrong DID");
+        assert!(error.to_string().contains("expected a DID document for did:web:credentials.corp.com"), "{}", error);
+    }
+}
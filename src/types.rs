@@ -11,6 +11,7 @@ use crate::restack::{Restack, RestackError};
 use crate::location::LineNo;
 use crate::elem_type::{ElemType, StackType};
 
+use std::cmp;
 use std::fmt::{Display, Formatter};
 use std::fmt;
 
@@ -150,9 +151,6 @@ impl Type {
     /// 2. collect the remainder and add them to the context
     /// 3. add the remainder to (self.i_type, other.o_type), with replaced variables
     pub fn compose(&self, other: Self) -> Result<Self, TypeError> {
-        println!("");
-        println!("composing:\n{0}\n\nAND\n{1}\n", self, other);
-
         let mut context = self.context.clone();
         // println!("context: {}", context);
         // println!("context.next_type_id: {:?}", context.next_type_id.type_id);
@@ -164,24 +162,31 @@ impl Type {
             .map_err(|e| TypeError::ComposeContextError(e))?;
         // println!("context union: {}", context);
 
-        let mut mut_offset_other = offset_other.clone();
-        let mut zip_len = 0;
-        let other_o_type = offset_other.o_type.iter().clone();
-        let self_i_type = self.i_type.iter().clone();
-        other_o_type.zip(self_i_type).try_for_each(|(&o_type, &i_type)| {
-            zip_len += 1;
-            context
-                .unify(o_type, i_type)
-                .map_err(|e| TypeError::ComposeContextError(e))?;
-            mut_offset_other
-                .update_type_id(o_type, i_type)?;
-            Ok(())
-        })?;
+        // A TypeId may appear more than once (e.g. after "dup"), so each
+        // unified pair is renamed everywhere before unifying the next
+        let mut other_i_type = offset_other.i_type.clone();
+        let mut other_o_type = offset_other.o_type.clone();
+        let mut self_i_type = self.i_type.clone();
+        let mut self_o_type = self.o_type.clone();
+        let zip_len = cmp::min(other_o_type.len(), self_i_type.len());
+        for ix in 0..zip_len {
+            let (o_type, i_type) = (other_o_type[ix], self_i_type[ix]);
+            if o_type != i_type {
+                context
+                    .unify(o_type, i_type)
+                    .map_err(|e| TypeError::ComposeContextError(e))?;
+                for type_ids in [&mut other_i_type, &mut other_o_type, &mut self_i_type, &mut self_o_type] {
+                    for type_id in type_ids.iter_mut() {
+                        *type_id = type_id.update_type_id(o_type, i_type);
+                    }
+                }
+            }
+        }
 
         Ok(Type {
             context: context,
-            i_type: mut_offset_other.i_type.iter().chain(self.i_type.iter().skip(zip_len)).copied().collect(),
-            o_type: self.o_type.iter().chain(mut_offset_other.o_type.iter().skip(zip_len)).copied().collect(),
+            i_type: other_i_type.iter().chain(self_i_type.iter().skip(zip_len)).copied().collect(),
+            o_type: self_o_type.iter().chain(other_o_type.iter().skip(zip_len)).copied().collect(),
         })
     }
 
@@ -189,7 +194,7 @@ impl Type {
     pub fn prepend_inputs(&mut self, num_copies: usize, elem_type: ElemType) -> () {
         if 0 < num_copies {
             let type_id = self.context.push(elem_type);
-            self.i_type = (1..num_copies).into_iter()
+            self.i_type = (0..num_copies).into_iter()
                 .map(|_| type_id)
                 .chain(self.i_type.clone().into_iter())
                 .collect()
@@ -262,7 +267,18 @@ impl Display for Type {
 #[cfg(test)]
 mod type_display_tests {
     use super::*;
+    use crate::elem::ElemSymbol;
+    use crate::elems_singleton::Singleton;
+    use crate::elems_input_output_singleton::ReturnSingleton;
+    use crate::elems_list_cons::Cons;
+    use crate::elems_list_input_output::IOList;
+    use crate::elems_list_input_output_cons::ConsOut;
+    use crate::elems_list_nil::Nil;
+
+    use std::marker::PhantomData;
+
     use enumset::EnumSet;
+    use generic_array::typenum::{U0, U2};
 
     #[test]
     fn test_empty() {
@@ -293,6 +309,33 @@ mod type_display_tests {
             assert_eq!(format!("\n∀ (t0 ∊ {}),\n[t0, t0] ->\n[t0]", elem_type), format!("{}", example_type));
         }
     }
+
+    // Type::prepend_inputs(n, _) used to prepend n - 1 inputs
+    #[test]
+    fn test_prepend_inputs() {
+        let string_type = ElemType {
+            type_set: EnumSet::only(ElemSymbol::String),
+            info: vec![],
+        };
+        let mut example_type = Type::id();
+        example_type.prepend_inputs(0, string_type.clone());
+        assert_eq!(0, example_type.i_type.len());
+        example_type.prepend_inputs(2, string_type);
+        assert_eq!(2, example_type.i_type.len());
+
+        let io_type = IOList::type_of(PhantomData::<Cons<Singleton<String, U2>, ConsOut<ReturnSingleton<bool, U0>, Nil>>>)
+            .expect("failed to type IOList");
+        assert_eq!((2, 1), (io_type.i_type.len(), io_type.o_type.len()));
+    }
+
+    #[test]
+    fn test_compose_repeated_type_id() {
+        let dup_type = Restack::dup().type_of(From::from(0)).expect("dup is well-typed");
+        let swap_type = Restack::swap().type_of(From::from(1)).expect("swap is well-typed");
+        let dup_swap_type = swap_type.compose(dup_type).expect("dup; swap is well-typed");
+        assert_eq!(1, dup_swap_type.i_type.len());
+        assert_eq!(vec![dup_swap_type.i_type[0]; 2], dup_swap_type.o_type);
+    }
 }
 
 /// Type trait errors
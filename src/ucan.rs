@@ -0,0 +1,530 @@
+use crate::did::{resolve_did_key, DidError};
+use crate::hash_function::HashFunction;
+use crate::jws::{Jws, JwsError};
+use crate::parse::{parse, ParseError};
+use crate::stack::Stack;
+use crate::untyped_instruction::InstructionError;
+use crate::untyped_instructions::Instructions;
+use crate::typed_instruction::StackInstructionError;
+
+use std::sync::Arc;
+
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+/// The caveat ("nb") member of a capability holding its embedded cryptoscript
+pub const CRYPTOSCRIPT_CAVEAT: &str = "cryptoscript";
+
+/// A capability, i.e. the ability "can" on the resource "with", restricted by
+/// the caveats "nb"
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capability {
+    /// The resource, e.g. a URL
+    pub with: String,
+
+    /// The ability, e.g. "crud/read"
+    pub can: String,
+
+    /// The caveats, empty if absent
+    pub caveats: Map<String, Value>,
+}
+
+impl Capability {
+    /// The embedded cryptoscript caveat, if any: either cryptoscript source
+    /// (as accepted by parse) or Instructions (as accepted by parse_json)
+    pub fn cryptoscript(&self) -> Result<Option<Instructions>, UcanError> {
+        match self.caveats.get(CRYPTOSCRIPT_CAVEAT) {
+            None => Ok(None),
+            Some(Value::String(source)) => Ok(Some(parse(source)
+                .map_err(|e| UcanError::CaveatParseError(Arc::new(e)))?)),
+            Some(instructions) => Ok(Some(serde_json::from_value(instructions.clone())
+                .map_err(|e| UcanError::CaveatJsonError(Arc::new(e)))?)),
+        }
+    }
+
+    fn new(with: &str, can: &str, caveats: &Value) -> Result<Self, UcanError> {
+        Ok(Capability {
+            with: with.to_string(),
+            can: can.to_string(),
+            caveats: match caveats {
+                Value::Null => Map::new(),
+                Value::Object(caveats) => caveats.clone(),
+                _ => return Err(UcanError::InvalidCaveats {
+                    caveats: caveats.clone(),
+                }),
+            },
+        })
+    }
+}
+
+/// A JWT-encoded UCAN, with its proofs ("prf") parsed in turn
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ucan {
+    /// The decoded JWT
+    pub jws: Jws,
+
+    /// The issuer ("iss"), which must be a did:key
+    pub issuer: String,
+
+    /// The audience ("aud"), i.e. the delegate
+    pub audience: String,
+
+    /// "nbf", if any
+    pub not_before: Option<i64>,
+
+    /// "exp", unless it's null
+    pub expiration: Option<i64>,
+
+    /// The delegated capabilities ("att")
+    pub capabilities: Vec<Capability>,
+
+    /// The UCAN's proofs, i.e. the UCAN's delegated to its issuer
+    pub proofs: Vec<Ucan>,
+}
+
+impl Ucan {
+    /// Parse a JWT-encoded UCAN and its proofs, where each proof is either
+    /// an encoded UCAN or the CID (see ucan_cid) of one of the given proofs.
+    ///
+    /// "att" may be an Array of {"with", "can", "nb"} (UCAN 0.9) or an Object
+    /// from "with" to "can" to an Array of "nb"'s (UCAN 0.10).
+    pub fn parse(encoded: &str, proofs: &[String]) -> Result<Self, UcanError> {
+        let jws = Jws::parse(encoded)?;
+        let claims = jws.payload.clone();
+        let string_claim = |claim: &'static str| match claims.get(claim) {
+            Some(Value::String(value)) => Ok(value.clone()),
+            value => Err(UcanError::InvalidClaim {
+                claim: claim,
+                value: value.cloned(),
+            }),
+        };
+        let date_claim = |claim: &'static str| match claims.get(claim) {
+            None | Some(Value::Null) => Ok(None),
+            Some(value) => value.as_i64()
+                .map(Some)
+                .ok_or_else(|| UcanError::InvalidClaim {
+                    claim: claim,
+                    value: Some(value.clone()),
+                }),
+        };
+        let issuer = string_claim("iss")?;
+        let audience = string_claim("aud")?;
+        let not_before = date_claim("nbf")?;
+        let expiration = date_claim("exp")?;
+
+        let invalid_att = || UcanError::InvalidClaim {
+            claim: "att",
+            value: claims.get("att").cloned(),
+        };
+        let mut capabilities = vec![];
+        match claims.get("att") {
+            Some(Value::Array(att)) => for capability in att {
+                match (capability.get("with").and_then(Value::as_str), capability.get("can").and_then(Value::as_str)) {
+                    (Some(with), Some(can)) =>
+                        capabilities.push(Capability::new(with, can, capability.get("nb").unwrap_or(&Value::Null))?),
+                    _ => return Err(invalid_att()),
+                }
+            },
+            Some(Value::Object(att)) => for (with, abilities) in att {
+                for (can, caveats) in abilities.as_object().ok_or_else(invalid_att)? {
+                    for caveats in caveats.as_array().ok_or_else(invalid_att)? {
+                        capabilities.push(Capability::new(with, can, caveats)?);
+                    }
+                }
+            },
+            _ => return Err(invalid_att()),
+        }
+
+        let parsed_proofs = match claims.get("prf") {
+            None => vec![],
+            Some(Value::Array(prf)) => prf.iter().map(|proof| {
+                let proof = proof.as_str()
+                    .ok_or_else(|| UcanError::InvalidClaim {
+                        claim: "prf",
+                        value: Some(proof.clone()),
+                    })?;
+                if proof.contains('.') {
+                    Self::parse(proof, proofs)
+                } else {
+                    let encoded_proof = proofs.iter().find(|encoded_proof| ucan_cid(encoded_proof) == proof)
+                        .ok_or_else(|| UcanError::UnknownProof {
+                            cid: proof.to_string(),
+                        })?;
+                    Self::parse(encoded_proof, proofs)
+                }
+            }).collect::<Result<Vec<Self>, UcanError>>()?,
+            prf => return Err(UcanError::InvalidClaim {
+                claim: "prf",
+                value: prf.cloned(),
+            }),
+        };
+
+        Ok(Ucan {
+            jws: jws,
+            issuer: issuer,
+            audience: audience,
+            not_before: not_before,
+            expiration: expiration,
+            capabilities: capabilities,
+            proofs: parsed_proofs,
+        })
+    }
+
+    /// Verify the delegation chain at the given time (in seconds since the
+    /// epoch): each UCAN must be signed by its issuer's did:key and valid at
+    /// "now", and each proof's audience must be the issuer it delegates to
+    pub fn verify(&self, now: i64) -> Result<(), UcanError> {
+        let document = resolve_did_key(&self.issuer)?;
+        let jwk = document["verificationMethod"][0]["publicKeyJwk"].as_object()
+            .ok_or_else(|| UcanError::UnsupportedIssuer {
+                issuer: self.issuer.clone(),
+            })?;
+        if !self.jws.verify(jwk)? {
+            return Err(UcanError::InvalidSignature {
+                issuer: self.issuer.clone(),
+            })
+        }
+        if matches!(self.not_before, Some(not_before) if now < not_before) ||
+            matches!(self.expiration, Some(expiration) if expiration <= now) {
+            return Err(UcanError::NotValidAt {
+                issuer: self.issuer.clone(),
+                not_before: self.not_before,
+                expiration: self.expiration,
+                now: now,
+            })
+        }
+        for proof in &self.proofs {
+            if proof.audience != self.issuer {
+                return Err(UcanError::ProofAudienceMismatch {
+                    issuer: self.issuer.clone(),
+                    audience: proof.audience.clone(),
+                })
+            }
+            proof.verify(now)?;
+        }
+        Ok(())
+    }
+
+    /// The cryptoscript caveats of the capability ("with", "can"), which
+    /// must be delegated by the resource's owner through every UCAN in a
+    /// chain of proofs.
+    ///
+    /// The caveats of every matching capability, from every chain of proofs
+    /// that delegates it, are included.
+    pub fn caveats(&self, owner: &str, with: &str, can: &str) -> Result<Vec<Instructions>, UcanError> {
+        let not_delegated = || UcanError::CapabilityNotDelegated {
+            issuer: self.issuer.clone(),
+            with: with.to_string(),
+            can: can.to_string(),
+        };
+        let matching: Vec<&Capability> = self.capabilities.iter()
+            .filter(|capability| capability.with == with && capability.can == can)
+            .collect();
+        if matching.is_empty() {
+            return Err(not_delegated())
+        }
+        let mut caveats = vec![];
+        for capability in matching {
+            caveats.extend(capability.cryptoscript()?);
+        }
+        if self.issuer != owner {
+            let mut is_delegated = false;
+            for proof in &self.proofs {
+                match proof.caveats(owner, with, can) {
+                    Ok(proof_caveats) => {
+                        is_delegated = true;
+                        caveats.extend(proof_caveats);
+                    },
+                    Err(UcanError::CapabilityNotDelegated { .. }) => (),
+                    Err(e) => return Err(e),
+                }
+            }
+            if !is_delegated {
+                return Err(not_delegated())
+            }
+        }
+        Ok(caveats)
+    }
+
+    /// Verify the delegation chain at the given time and run each of the
+    /// invoked capability's cryptoscript caveats on a Stack containing only
+    /// the invocation, failing if any caveat fails.
+    ///
+    /// The invocation is an Object whose "with" and "can" select the
+    /// capability, and owner is the DID trusted to own its resource.
+    pub fn invoke(&self, invocation: &Map<String, Value>, owner: &str, now: i64) -> Result<(), UcanError> {
+        self.verify(now)?;
+        let (with, can) = match (invocation.get("with").and_then(Value::as_str), invocation.get("can").and_then(Value::as_str)) {
+            (Some(with), Some(can)) => (with, can),
+            _ => return Err(UcanError::InvalidInvocation {
+                invocation: invocation.clone(),
+            }),
+        };
+        for (index, caveat) in self.caveats(owner, with, can)?.into_iter().enumerate() {
+            let mut stack = Stack::new();
+            stack.push_elem(invocation.clone());
+            caveat.to_instrs()
+                .map_err(|e| UcanError::CaveatInstructionError {
+                    index: index,
+                    error: e,
+                })?
+                .run(&mut stack)
+                .map_err(|e| UcanError::CaveatFailed {
+                    index: index,
+                    error: e,
+                })?;
+        }
+        Ok(())
+    }
+}
+
+/// The CIDv1 (raw, sha2-256, base32) of a JWT-encoded UCAN, used to refer to
+/// it from "prf"
+pub fn ucan_cid(encoded: &str) -> String {
+    let digest = HashFunction::Sha256.digest(encoded.as_bytes());
+    multibase::encode(multibase::Base::Base32Lower, [&[0x01, 0x55, 0x12, 0x20], digest.as_slice()].concat())
+}
+
+/// UCAN parsing, verification and invocation errors
+#[derive(Clone, Debug, Error)]
+pub enum UcanError {
+    /// A claim is missing or malformed
+    #[error("UCAN claim {claim:?} is missing or malformed: {value:?}")]
+    InvalidClaim {
+        /// The claim's name
+        claim: &'static str,
+        /// The claim, if present
+        value: Option<Value>,
+    },
+
+    /// A capability's caveats ("nb") aren't an Object
+    #[error("UCAN capability caveats must be an Object: {caveats}")]
+    InvalidCaveats {
+        /// The caveats
+        caveats: Value,
+    },
+
+    /// A proof CID doesn't match any of the given proofs
+    #[error("UCAN proof not found: {cid}")]
+    UnknownProof {
+        /// The proof's CID
+        cid: String,
+    },
+
+    /// The issuer's did:key has no JWK, e.g. a BLS12-381 key
+    #[error("UCAN issuer's key type is unsupported: {issuer}")]
+    UnsupportedIssuer {
+        /// The issuer
+        issuer: String,
+    },
+
+    /// The signature is invalid
+    #[error("UCAN signature is invalid: {issuer}")]
+    InvalidSignature {
+        /// The issuer
+        issuer: String,
+    },
+
+    /// "now" is before "nbf" or not before "exp"
+    #[error("UCAN from {issuer} is not valid at {now}: nbf: {not_before:?}, exp: {expiration:?}")]
+    NotValidAt {
+        /// The issuer
+        issuer: String,
+        /// "nbf"
+        not_before: Option<i64>,
+        /// "exp"
+        expiration: Option<i64>,
+        /// The time of verification
+        now: i64,
+    },
+
+    /// A proof wasn't delegated to the UCAN's issuer
+    #[error("UCAN issuer is {issuer}, but its proof's audience is {audience}")]
+    ProofAudienceMismatch {
+        /// The UCAN's issuer
+        issuer: String,
+        /// The proof's audience
+        audience: String,
+    },
+
+    /// The capability isn't delegated by the owner to the issuer
+    #[error("UCAN capability {can:?} on {with:?} is not delegated to {issuer}")]
+    CapabilityNotDelegated {
+        /// The issuer
+        issuer: String,
+        /// The resource
+        with: String,
+        /// The ability
+        can: String,
+    },
+
+    /// The invocation has no "with" or "can"
+    #[error("UCAN invocation must have \"with\" and \"can\" Strings: {invocation:?}")]
+    InvalidInvocation {
+        /// The invocation
+        invocation: Map<String, Value>,
+    },
+
+    /// A cryptoscript caveat's source is invalid
+    #[error("UCAN cryptoscript caveat is invalid:\n{0}")]
+    CaveatParseError(Arc<ParseError>),
+
+    /// A cryptoscript caveat's Instructions are invalid
+    #[error("UCAN cryptoscript caveat is invalid:\n{0}")]
+    CaveatJsonError(Arc<serde_json::Error>),
+
+    /// A cryptoscript caveat can't be converted to typed instructions
+    #[error("UCAN cryptoscript caveat {index} is invalid:\n{error}")]
+    CaveatInstructionError {
+        /// Index of the caveat
+        index: usize,
+        /// The error
+        error: InstructionError,
+    },
+
+    /// A cryptoscript caveat failed
+    #[error("UCAN cryptoscript caveat {index} failed:\n{error}")]
+    CaveatFailed {
+        /// Index of the caveat
+        index: usize,
+        /// The error
+        error: StackInstructionError,
+    },
+
+    /// The issuer isn't a supported did:key
+    #[error("UCAN issuer:\n{0}")]
+    DidError(#[from] DidError),
+
+    /// The JWT is malformed or its signature can't be verified
+    #[error("UCAN:\n{0}")]
+    JwsError(#[from] JwsError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use base64::Engine;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signer};
+    use serde_json::json;
+
+    const RESOURCE: &str = "https://api.corp.com/payments";
+    const NOW: i64 = 1650000000;
+
+    // owner (1) delegates payments of at most 100 to alice (2), who delegates
+    // USD payments to bob (3)
+    fn keypair(seed: u8) -> Keypair {
+        let secret = SecretKey::from_bytes(&[seed; 32]).expect("invalid secret key");
+        let public = PublicKey::from(&secret);
+        Keypair {
+            secret: secret,
+            public: public,
+        }
+    }
+
+    fn did_key(seed: u8) -> String {
+        let public_key = keypair(seed).public.to_bytes();
+        format!("did:key:{}", multibase::encode(multibase::Base::Base58Btc, [&[0xed, 0x01], public_key.as_slice()].concat()))
+    }
+
+    fn sign_ucan(issuer: u8, claims: Value) -> String {
+        let header = URL_SAFE_NO_PAD.encode(json!({"alg": "EdDSA", "typ": "JWT"}).to_string());
+        let payload = URL_SAFE_NO_PAD.encode(claims.to_string());
+        let signing_input = format!("{}.{}", header, payload);
+        let signature = keypair(issuer).sign(signing_input.as_bytes());
+        format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature.to_bytes()))
+    }
+
+    fn root() -> String {
+        let max_amount = parse("let invocation; invocation.amount<Number>; push 100; assert_le;").expect("failed to parse");
+        sign_ucan(1, json!({
+            "iss": did_key(1),
+            "aud": did_key(2),
+            "exp": 1700000000,
+            "att": [{"with": RESOURCE, "can": "payments/send", "nb": {"cryptoscript": max_amount}}],
+        }))
+    }
+
+    fn delegation(att: Value, prf: Value) -> String {
+        sign_ucan(2, json!({
+            "iss": did_key(2),
+            "aud": did_key(3),
+            "nbf": 1600000000,
+            "exp": null,
+            "att": att,
+            "prf": prf,
+        }))
+    }
+
+    fn usd_only() -> Value {
+        json!({RESOURCE: {"payments/send": [{"cryptoscript": r#"let invocation; invocation.currency<str>; push "USD"; assert_equal;"#}]}})
+    }
+
+    fn payment(can: &str, amount: u64, currency: &str) -> Map<String, Value> {
+        json!({"with": RESOURCE, "can": can, "amount": amount, "currency": currency})
+            .as_object().expect("not an Object").clone()
+    }
+
+    #[test]
+    fn test_invoke() {
+        let root = root();
+        let ucan = Ucan::parse(&delegation(usd_only(), json!([ucan_cid(&root)])), std::slice::from_ref(&root)).expect("failed to parse UCAN");
+        assert_eq!(Ucan::parse(&root, &[]).ok().as_ref(), ucan.proofs.first());
+        assert_eq!(2, ucan.caveats(&did_key(1), RESOURCE, "payments/send").expect("missing caveats").len());
+        ucan.invoke(&payment("payments/send", 50, "USD"), &did_key(1), NOW).expect("invocation failed");
+
+        let error = ucan.invoke(&payment("payments/send", 500, "USD"), &did_key(1), NOW).expect_err("amount is too large");
+        assert!(matches!(error, UcanError::CaveatFailed { index: 1, .. }), "{}", error);
+        let error = ucan.invoke(&payment("payments/send", 50, "EUR"), &did_key(1), NOW).expect_err("currency isn't USD");
+        assert!(matches!(error, UcanError::CaveatFailed { index: 0, .. }), "{}", error);
+        let error = ucan.invoke(&payment("payments/refund", 50, "USD"), &did_key(1), NOW).expect_err("ability isn't delegated");
+        assert!(matches!(error, UcanError::CapabilityNotDelegated { .. }), "{}", error);
+        let error = ucan.invoke(&payment("payments/send", 50, "USD"), &did_key(4), NOW).expect_err("wrong owner");
+        assert!(matches!(error, UcanError::CapabilityNotDelegated { .. }), "{}", error);
+        let error = ucan.invoke(&payment("payments/send", 50, "USD"), &did_key(1), 1750000000).expect_err("root has expired");
+        assert!(matches!(error, UcanError::NotValidAt { .. }), "{}", error);
+        let error = ucan.invoke(&payment("payments/send", 50, "USD"), &did_key(1), 1550000000).expect_err("delegation isn't valid yet");
+        assert!(matches!(error, UcanError::NotValidAt { .. }), "{}", error);
+
+        // proofs may be inlined, otherwise they must be given
+        let inlined = Ucan::parse(&delegation(usd_only(), json!([root])), &[]).expect("failed to parse UCAN");
+        assert_eq!(ucan.proofs, inlined.proofs);
+        assert!(matches!(Ucan::parse(&delegation(usd_only(), json!([ucan_cid(&root)])), &[]),
+                         Err(UcanError::UnknownProof { .. })));
+    }
+
+    #[test]
+    fn test_invalid_chain() {
+        // every UCAN in the chain must include the invoked capability
+        let refund_only = json!([{"with": RESOURCE, "can": "payments/refund"}]);
+        let ucan = Ucan::parse(&delegation(refund_only, json!([root()])), &[]).expect("failed to parse UCAN");
+        assert!(matches!(ucan.invoke(&payment("payments/send", 50, "USD"), &did_key(1), NOW),
+                         Err(UcanError::CapabilityNotDelegated { .. })));
+
+        // proofs must be delegated to the issuer
+        let forged = sign_ucan(5, json!({
+            "iss": did_key(5),
+            "aud": did_key(3),
+            "exp": null,
+            "att": [{"with": RESOURCE, "can": "payments/send"}],
+            "prf": [root()],
+        }));
+        let ucan = Ucan::parse(&forged, &[]).expect("failed to parse UCAN");
+        assert!(matches!(ucan.verify(NOW), Err(UcanError::ProofAudienceMismatch { .. })));
+
+        // signatures are checked throughout the chain
+        let root = root();
+        let parts: Vec<&str> = root.split('.').collect();
+        let mut claims = Jws::parse(&root).expect("invalid JWT").payload;
+        claims.insert("exp".to_string(), json!(1800000000));
+        let tampered_root = format!("{}.{}.{}", parts[0], URL_SAFE_NO_PAD.encode(Value::Object(claims).to_string()), parts[2]);
+        let ucan = Ucan::parse(&delegation(usd_only(), json!([tampered_root])), &[]).expect("failed to parse UCAN");
+        assert!(matches!(ucan.verify(NOW), Err(UcanError::InvalidSignature { .. })));
+
+        let mut invalid_caveat = payment("payments/send", 50, "USD");
+        invalid_caveat.remove("can");
+        assert!(matches!(Ucan::parse(&root, &[]).expect("failed to parse UCAN").invoke(&invalid_caveat, &did_key(1), NOW),
+                         Err(UcanError::InvalidInvocation { .. })));
+    }
+}
@@ -1,10 +1,15 @@
 #![allow(missing_docs)]
 
 use crate::elem::{Elem, ElemSymbol};
+use crate::hash_function::HashFunction;
+use crate::key_type::KeyType;
+use crate::merkle::MerklePairing;
 use crate::restack::Restack;
 
-use std::fmt::Debug;
+use std::fmt;
+use std::fmt::{Debug, Display, Formatter};
 
+use quickcheck::{Arbitrary, Gen};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -26,6 +31,196 @@ pub enum Instruction {
     ToJson,
     UnpackJson(ElemSymbol),
     StringToBytes,
+    AssertEq,
+    AssertNe,
+    AssertLt,
+    AssertLe,
+    AssertGt,
+    AssertGe,
+    AssertMember,
+    VerifySecp256k1,
+    EcRecover,
+    HashEip191,
+    VerifyEd25519,
+    VerifyP256,
+    WebAuthnPayload,
+    HashSha3_256,
+    HashKeccak256,
+    HashSha512,
+    HashBlake2b256,
+    HashBlake3,
+    HashRipemd160,
+    HmacSha256,
+    ConstantTimeBytesEq,
+    VerifyMerkleProof(HashFunction, MerklePairing),
+    VerifyBls12381,
+    FastAggregateVerifyBls12381,
+    CheckMultiSig(KeyType),
+    DecodeMultibase,
+    MulticodecCode,
+    StripMulticodec,
+    VerifyMultihash,
+    JwsHeader,
+    JwsPayload,
+    JwsSignature,
+    VerifyJws,
+    JwtExp,
+    JwtNbf,
+    ValidateVc,
+    ValidateVp,
+    VerifyJwtVc,
+    VerifyJwtVp,
+    VpSigner,
+    ParseSiwe,
+    ResolveDid,
+    CheckDidDocument,
+    VerificationMethod,
+}
+
+// Formatting uses the mnemonics accepted by parse, e.g.
+// ```
+// push 0x49206163;
+// unpack_json<Object>;
+// dig 2;
+// ```
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Self::Push(elem) => write!(f, "push {}", elem.literal()),
+            Self::Restack(restack) => write!(f, "{}", restack),
+            Self::HashSha256 => write!(f, "hash_sha256"),
+            Self::CheckLe => write!(f, "check_le"),
+            Self::CheckLt => write!(f, "check_lt"),
+            Self::CheckEq => write!(f, "check_equal"),
+            Self::StringEq => write!(f, "string_eq"),
+            Self::BytesEq => write!(f, "bytes_eq"),
+            Self::Concat => write!(f, "concat"),
+            Self::Slice => write!(f, "slice"),
+            Self::Index => write!(f, "index"),
+            Self::Lookup => write!(f, "lookup"),
+            Self::AssertTrue => write!(f, "assert_true"),
+            Self::ToJson => write!(f, "to_json"),
+            Self::UnpackJson(elem_symbol) => {
+                let elem_symbol_str: &'static str = From::from(*elem_symbol);
+                write!(f, "unpack_json<{}>", elem_symbol_str)
+            },
+            Self::StringToBytes => write!(f, "string_to_bytes"),
+            Self::AssertEq => write!(f, "assert_equal"),
+            Self::AssertNe => write!(f, "assert_not_equal"),
+            Self::AssertLt => write!(f, "assert_lt"),
+            Self::AssertLe => write!(f, "assert_le"),
+            Self::AssertGt => write!(f, "assert_gt"),
+            Self::AssertGe => write!(f, "assert_ge"),
+            Self::AssertMember => write!(f, "assert_member"),
+            Self::VerifySecp256k1 => write!(f, "verify_secp256k1"),
+            Self::EcRecover => write!(f, "ecrecover"),
+            Self::HashEip191 => write!(f, "hash_eip191"),
+            Self::VerifyEd25519 => write!(f, "verify_ed25519"),
+            Self::VerifyP256 => write!(f, "verify_p256"),
+            Self::WebAuthnPayload => write!(f, "webauthn_payload"),
+            Self::HashSha3_256 => write!(f, "hash_sha3_256"),
+            Self::HashKeccak256 => write!(f, "hash_keccak256"),
+            Self::HashSha512 => write!(f, "hash_sha512"),
+            Self::HashBlake2b256 => write!(f, "hash_blake2b_256"),
+            Self::HashBlake3 => write!(f, "hash_blake3"),
+            Self::HashRipemd160 => write!(f, "hash_ripemd160"),
+            Self::HmacSha256 => write!(f, "hmac_sha256"),
+            Self::ConstantTimeBytesEq => write!(f, "constant_time_bytes_eq"),
+            Self::VerifyMerkleProof(hash_function, pairing) =>
+                write!(f, "verify_merkle_proof<{}, {}>", hash_function, pairing),
+            Self::VerifyBls12381 => write!(f, "verify_bls12_381"),
+            Self::FastAggregateVerifyBls12381 => write!(f, "fast_aggregate_verify_bls12_381"),
+            Self::CheckMultiSig(key_type) => write!(f, "check_multisig<{}>", key_type),
+            Self::DecodeMultibase => write!(f, "decode_multibase"),
+            Self::MulticodecCode => write!(f, "multicodec_code"),
+            Self::StripMulticodec => write!(f, "strip_multicodec"),
+            Self::VerifyMultihash => write!(f, "verify_multihash"),
+            Self::JwsHeader => write!(f, "jws_header"),
+            Self::JwsPayload => write!(f, "jws_payload"),
+            Self::JwsSignature => write!(f, "jws_signature"),
+            Self::VerifyJws => write!(f, "verify_jws"),
+            Self::JwtExp => write!(f, "jwt_exp"),
+            Self::JwtNbf => write!(f, "jwt_nbf"),
+            Self::ValidateVc => write!(f, "validate_vc"),
+            Self::ValidateVp => write!(f, "validate_vp"),
+            Self::VerifyJwtVc => write!(f, "verify_jwt_vc"),
+            Self::VerifyJwtVp => write!(f, "verify_jwt_vp"),
+            Self::VpSigner => write!(f, "vp_signer"),
+            Self::ParseSiwe => write!(f, "parse_siwe"),
+            Self::ResolveDid => write!(f, "resolve_did"),
+            Self::CheckDidDocument => write!(f, "check_did_document"),
+            Self::VerificationMethod => write!(f, "verification_method"),
+        }
+    }
+}
+
+impl Arbitrary for Instruction {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let choices: [&dyn Fn(&mut Gen) -> Self; 60] = [
+            &|g| Self::Push(Arbitrary::arbitrary(g)),
+            &|g| Self::Restack(Arbitrary::arbitrary(g)),
+            &|_| Self::HashSha256,
+            &|_| Self::CheckLe,
+            &|_| Self::CheckLt,
+            &|_| Self::CheckEq,
+            &|_| Self::StringEq,
+            &|_| Self::BytesEq,
+            &|_| Self::Concat,
+            &|_| Self::Slice,
+            &|_| Self::Index,
+            &|_| Self::Lookup,
+            &|_| Self::AssertTrue,
+            &|_| Self::ToJson,
+            &|g| Self::UnpackJson(Arbitrary::arbitrary(g)),
+            &|_| Self::StringToBytes,
+            &|_| Self::AssertEq,
+            &|_| Self::AssertNe,
+            &|_| Self::AssertLt,
+            &|_| Self::AssertLe,
+            &|_| Self::AssertGt,
+            &|_| Self::AssertGe,
+            &|_| Self::AssertMember,
+            &|_| Self::VerifySecp256k1,
+            &|_| Self::EcRecover,
+            &|_| Self::HashEip191,
+            &|_| Self::VerifyEd25519,
+            &|_| Self::VerifyP256,
+            &|_| Self::WebAuthnPayload,
+            &|_| Self::HashSha3_256,
+            &|_| Self::HashKeccak256,
+            &|_| Self::HashSha512,
+            &|_| Self::HashBlake2b256,
+            &|_| Self::HashBlake3,
+            &|_| Self::HashRipemd160,
+            &|_| Self::HmacSha256,
+            &|_| Self::ConstantTimeBytesEq,
+            &|g| Self::VerifyMerkleProof(Arbitrary::arbitrary(g), Arbitrary::arbitrary(g)),
+            &|_| Self::VerifyBls12381,
+            &|_| Self::FastAggregateVerifyBls12381,
+            &|g| Self::CheckMultiSig(Arbitrary::arbitrary(g)),
+            &|_| Self::DecodeMultibase,
+            &|_| Self::MulticodecCode,
+            &|_| Self::StripMulticodec,
+            &|_| Self::VerifyMultihash,
+            &|_| Self::JwsHeader,
+            &|_| Self::JwsPayload,
+            &|_| Self::JwsSignature,
+            &|_| Self::VerifyJws,
+            &|_| Self::JwtExp,
+            &|_| Self::JwtNbf,
+            &|_| Self::ValidateVc,
+            &|_| Self::ValidateVp,
+            &|_| Self::VerifyJwtVc,
+            &|_| Self::VerifyJwtVp,
+            &|_| Self::VpSigner,
+            &|_| Self::ParseSiwe,
+            &|_| Self::ResolveDid,
+            &|_| Self::CheckDidDocument,
+            &|_| Self::VerificationMethod,
+        ];
+        let choice = usize::arbitrary(g) % choices.len();
+        choices[choice](g)
+    }
 }
 
 #[derive(Clone, Copy, Debug, Error)]
@@ -3,11 +3,19 @@ use crate::typed_instruction::StackInstructionError;
 use crate::typed_instr::Instr;
 use crate::typed_instrs::Instrs;
 
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use quickcheck::{Arbitrary, Gen};
 use serde::{Deserialize, Serialize};
 
 /// A list of untyped instructions
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Serialize, Deserialize)]
 pub struct Instructions {
+    /// Documentation for the program, one entry per line (from "///" comments)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub docs: Vec<String>,
+
     /// A list of untyped instructions
     pub instructions: Vec<Instruction>,
 }
@@ -21,6 +29,44 @@ impl IntoIterator for Instructions {
     }
 }
 
+// Canonical cryptoscript source: one "///" line per doc line, then one
+// instruction per line, each terminated by ";", so that
+// parse(&format!("{}", x)) == Ok(x)
+impl Display for Instructions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        for doc in &self.docs {
+            if doc.is_empty() {
+                writeln!(f, "///")?;
+            } else {
+                writeln!(f, "/// {}", doc)?;
+            }
+        }
+        for instruction in &self.instructions {
+            writeln!(f, "{};", instruction)?;
+        }
+        Ok(())
+    }
+}
+
+/// Arbitrary docs never contain line breaks
+impl Arbitrary for Instructions {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let docs: Vec<String> = Arbitrary::arbitrary(g);
+        Instructions {
+            docs: docs.into_iter().map(|doc| doc.replace(['\n', '\r'], "")).collect(),
+            instructions: Arbitrary::arbitrary(g),
+        }
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let docs = self.docs.clone();
+        Box::new(self.instructions.shrink().map(move |instructions| Instructions {
+            docs: docs.clone(),
+            instructions: instructions,
+        }))
+    }
+}
+
 impl Instructions {
     /// Convert to a list of typed instructions
     pub fn to_instrs(self) -> Result<Instrs, InstructionError> {
@@ -30,10 +76,20 @@ impl Instructions {
     }
 }
 
+// Formatted as the equivalent Instructions, failing if any Instr can't be
+// converted to an Instruction
+impl Display for Instrs {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        let instructions = self.clone().to_instructions().map_err(|_| fmt::Error)?;
+        write!(f, "{}", instructions)
+    }
+}
+
 impl Instrs {
     /// Convert to a list of untyped instructions
     pub fn to_instructions(self) -> Result<Instructions, StackInstructionError> {
         Ok(Instructions {
+            docs: vec![],
             instructions: self.instrs.into_iter().map(|x| x.to_instruction()).collect::<Result<Vec<Instruction>, StackInstructionError>>()?,
         })
     }
@@ -0,0 +1,450 @@
+use crate::did::{absolute_method_id, check_did_document, relationship_verification_methods, DidError};
+use crate::jws::{jwk_public_key, Jws, JwsAlgorithm, JwsError};
+
+use serde_json::{Map, Number, Value};
+use thiserror::Error;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+/// The base JSON-LD context of the VC Data Model v1.1
+pub const CREDENTIALS_CONTEXT_V1: &str = "https://www.w3.org/2018/credentials/v1";
+
+/// The base JSON-LD context of the VC Data Model v2.0
+pub const CREDENTIALS_CONTEXT_V2: &str = "https://www.w3.org/ns/credentials/v2";
+
+/// Check the structure of a W3C Verifiable Credential (v1.1 or v2.0) and
+/// that it's valid at the given time (in seconds since the epoch):
+/// - @context starts with CREDENTIALS_CONTEXT_V1 or CREDENTIALS_CONTEXT_V2
+/// - type includes "VerifiableCredential"
+/// - issuer is a URI or an Object with an "id"
+/// - credentialSubject is an Object or a non-empty Array of Objects
+/// - issuanceDate (required by v1.1) or validFrom is at or before now
+/// - expirationDate or validUntil, if present, is after now
+///
+/// Proofs are not checked: see verify_jwt_vc
+pub fn validate_credential(credential: &Map<String, Value>, now: i64) -> Result<(), VcError> {
+    let is_v1 = context_is_v1(credential)?;
+    check_type(credential, "VerifiableCredential")?;
+    match credential.get("issuer") {
+        Some(Value::String(issuer)) if !issuer.is_empty() => (),
+        Some(Value::Object(issuer)) if issuer.get("id").is_some_and(Value::is_string) => (),
+        issuer => return Err(VcError::InvalidIssuer {
+            issuer: issuer.cloned(),
+        }),
+    }
+    match credential.get("credentialSubject") {
+        Some(Value::Object(_)) => (),
+        Some(Value::Array(subjects)) if !subjects.is_empty() && subjects.iter().all(Value::is_object) => (),
+        subject => return Err(VcError::InvalidCredentialSubject {
+            subject: subject.cloned(),
+        }),
+    }
+    if is_v1 && !credential.contains_key("issuanceDate") {
+        return Err(VcError::MissingMember {
+            member: "issuanceDate",
+        })
+    }
+    for member in ["issuanceDate", "validFrom"] {
+        if let Some(valid_from) = date_member(credential, member)? {
+            if now < valid_from {
+                return Err(VcError::NotYetValid {
+                    member: member,
+                    date: valid_from,
+                    now: now,
+                })
+            }
+        }
+    }
+    for member in ["expirationDate", "validUntil"] {
+        if let Some(valid_until) = date_member(credential, member)? {
+            if valid_until <= now {
+                return Err(VcError::Expired {
+                    member: member,
+                    date: valid_until,
+                    now: now,
+                })
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check the structure of a W3C Verifiable Presentation, as for
+/// validate_credential, along with each of its verifiableCredential's
+/// (decoding JWT-VC's without verifying them)
+pub fn validate_presentation(presentation: &Map<String, Value>, now: i64) -> Result<(), VcError> {
+    context_is_v1(presentation)?;
+    check_type(presentation, "VerifiablePresentation")?;
+    let credentials = match presentation.get("verifiableCredential") {
+        None => vec![],
+        Some(Value::Array(credentials)) => credentials.clone(),
+        Some(credential) => vec![credential.clone()],
+    };
+    for credential in credentials {
+        match credential {
+            Value::Object(credential) => validate_credential(&credential, now)?,
+            Value::String(jwt) => validate_credential(&jwt_vc_credential(&Jws::parse(&jwt)?)?, now)?,
+            credential => return Err(VcError::InvalidEmbeddedCredential {
+                credential: credential,
+            }),
+        }
+    }
+    Ok(())
+}
+
+/// The credential of a JWT-VC (VC Data Model v1.1, section 6.3.1): the "vc"
+/// claim, with "iss", "sub", "jti", "nbf" and "exp" filled in as its issuer,
+/// credentialSubject.id, id, issuance and expiration dates.
+///
+/// Fails if a claim disagrees with the corresponding credential member.
+pub fn jwt_vc_credential(jws: &Jws) -> Result<Map<String, Value>, VcError> {
+    let mut credential = jwt_claim(jws, "vc")?;
+    let (valid_from, valid_until) = if context_is_v1(&credential)? {
+        ("issuanceDate", "expirationDate")
+    } else {
+        ("validFrom", "validUntil")
+    };
+    if let Some(iss) = jws.payload.get("iss") {
+        let issuer = match credential.get("issuer") {
+            Some(Value::Object(issuer)) => issuer.get("id"),
+            issuer => issuer,
+        }.cloned();
+        fill_member(&mut credential, "issuer", issuer, iss.clone())?;
+    }
+    if let (Some(sub), Some(Value::Object(subject))) = (jws.payload.get("sub"), credential.get_mut("credentialSubject")) {
+        let id = subject.get("id").cloned();
+        fill_member(subject, "id", id, sub.clone())?;
+    }
+    for (claim, member) in [("jti", "id"), ("nbf", valid_from), ("exp", valid_until)] {
+        if let Some(value) = jws.payload.get(claim) {
+            let value = if claim == "jti" { value.clone() } else { format_numeric_date(claim, value)? };
+            let existing = credential.get(member).cloned();
+            fill_member(&mut credential, member, existing, value)?;
+        }
+    }
+    Ok(credential)
+}
+
+/// The presentation of a JWT-VP: the "vp" claim, with "iss" filled in as its
+/// holder
+pub fn jwt_vp_presentation(jws: &Jws) -> Result<Map<String, Value>, VcError> {
+    let mut presentation = jwt_claim(jws, "vp")?;
+    if let Some(iss) = jws.payload.get("iss") {
+        let holder = presentation.get("holder").cloned();
+        fill_member(&mut presentation, "holder", holder, iss.clone())?;
+    }
+    Ok(presentation)
+}
+
+/// Verify a JWT (e.g. a JWT-VC or JWT-VP) with a key of its "iss", given the
+/// DID document of the "iss" (e.g. from resolve_did, or a did:web query
+/// checked with check_did_document):
+/// - the document's id must be the "iss" claim
+/// - the key is the publicKeyJwk of a verification method in the given
+///   verification relationship (e.g. "assertionMethod"), with the header's
+///   "kid" as its id (if any), and whose key type matches the header's "alg"
+///
+/// The document must come from a trusted source, not with the JWT.
+///
+/// Fails unless the signature is valid for one of these keys
+pub fn verify_jwt_signer(jws: &Jws, document: &Map<String, Value>, relationship: &'static str) -> Result<(), VcError> {
+    let iss = match jws.payload.get("iss") {
+        Some(Value::String(iss)) => iss,
+        iss => return Err(VcError::InvalidIss {
+            iss: iss.cloned(),
+        }),
+    };
+    check_did_document(iss, document)?;
+    let algorithm = jws.algorithm()?;
+    let kid = match jws.header.get("kid") {
+        None => None,
+        Some(Value::String(kid)) => Some(kid.clone()),
+        Some(kid) => return Err(VcError::InvalidKid {
+            kid: kid.clone(),
+        }),
+    };
+    let mut has_key = false;
+    for method in relationship_verification_methods(document, relationship)? {
+        let method_id = method.get("id").and_then(Value::as_str).map(|id| absolute_method_id(document, id));
+        if kid.as_ref().is_some_and(|kid| method_id != Some(absolute_method_id(document, kid))) {
+            continue
+        }
+        let jwk = match method.get("publicKeyJwk") {
+            Some(Value::Object(jwk)) => jwk,
+            _ => continue,
+        };
+        if !matches!(jwk_public_key(jwk), Ok((key_type, _)) if key_type == algorithm.key_type()) {
+            continue
+        }
+        has_key = true;
+        if jws.verify(jwk)? {
+            return Ok(())
+        }
+    }
+    if has_key {
+        Err(VcError::InvalidSignature)
+    } else {
+        Err(VcError::NoVerificationMethod {
+            iss: iss.clone(),
+            relationship: relationship,
+            algorithm: algorithm,
+            kid: kid,
+        })
+    }
+}
+
+/// The signer of a presentation, i.e. its holder (a URI, usually a DID)
+pub fn presentation_signer(presentation: &Map<String, Value>) -> Result<String, VcError> {
+    let holder = match presentation.get("holder") {
+        Some(Value::Object(holder)) => holder.get("id"),
+        holder => holder,
+    };
+    match holder {
+        Some(Value::String(holder)) => Ok(holder.clone()),
+        holder => Err(VcError::InvalidHolder {
+            holder: holder.cloned(),
+        }),
+    }
+}
+
+/// Whether the @context is v1.1, failing unless it starts with one of the
+/// supported base contexts
+fn context_is_v1(document: &Map<String, Value>) -> Result<bool, VcError> {
+    let context = document.get("@context");
+    match context.and_then(Value::as_array).and_then(|context| context.first()).and_then(Value::as_str) {
+        Some(CREDENTIALS_CONTEXT_V1) => Ok(true),
+        Some(CREDENTIALS_CONTEXT_V2) => Ok(false),
+        _ => Err(VcError::InvalidContext {
+            context: context.cloned(),
+        }),
+    }
+}
+
+fn check_type(document: &Map<String, Value>, expected: &'static str) -> Result<(), VcError> {
+    let type_ = document.get("type");
+    let has_type = match type_ {
+        Some(Value::String(type_)) => type_ == expected,
+        Some(Value::Array(types)) => types.iter().any(|type_| type_.as_str() == Some(expected)),
+        _ => false,
+    };
+    if has_type {
+        Ok(())
+    } else {
+        Err(VcError::MissingType {
+            expected: expected,
+            type_: type_.cloned(),
+        })
+    }
+}
+
+fn date_member(document: &Map<String, Value>, member: &'static str) -> Result<Option<i64>, VcError> {
+    match document.get(member) {
+        None => Ok(None),
+        Some(date) => date.as_str()
+            .and_then(|date| OffsetDateTime::parse(date, &Rfc3339).ok())
+            .map(|date| Some(date.unix_timestamp()))
+            .ok_or_else(|| VcError::InvalidDate {
+                member: member,
+                date: date.clone(),
+            }),
+    }
+}
+
+fn format_numeric_date(claim: &'static str, date: &Value) -> Result<Value, VcError> {
+    date.as_i64()
+        .and_then(|date| OffsetDateTime::from_unix_timestamp(date).ok())
+        .and_then(|date| date.format(&Rfc3339).ok())
+        .map(Value::String)
+        .ok_or_else(|| VcError::InvalidNumericDate {
+            claim: claim,
+            date: date.clone(),
+        })
+}
+
+fn jwt_claim(jws: &Jws, claim: &'static str) -> Result<Map<String, Value>, VcError> {
+    match jws.payload.get(claim) {
+        Some(Value::Object(object)) => Ok(object.clone()),
+        value => Err(VcError::InvalidJwtClaim {
+            claim: claim,
+            value: value.cloned(),
+        }),
+    }
+}
+
+/// Set member to value, unless it's already set (to existing), in which case
+/// the two must be equal
+fn fill_member(document: &mut Map<String, Value>, member: &'static str, existing: Option<Value>, value: Value) -> Result<(), VcError> {
+    match existing {
+        None => {
+            document.insert(member.to_string(), value);
+            Ok(())
+        },
+        Some(existing) if existing == value => Ok(()),
+        Some(existing) => Err(VcError::JwtClaimMismatch {
+            member: member,
+            member_value: existing,
+            claim_value: value,
+        }),
+    }
+}
+
+/// Verifiable Credential and Presentation errors
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum VcError {
+    /// @context must start with a supported base context
+    #[error("@context must be an Array starting with {} or {}: {context:?}", CREDENTIALS_CONTEXT_V1, CREDENTIALS_CONTEXT_V2)]
+    InvalidContext {
+        /// The @context, if present
+        context: Option<Value>,
+    },
+
+    /// type is missing the expected type
+    #[error("type must include {expected:?}: {type_:?}")]
+    MissingType {
+        /// The expected type, e.g. "VerifiableCredential"
+        expected: &'static str,
+        /// The type, if present
+        type_: Option<Value>,
+    },
+
+    /// A required member is missing
+    #[error("missing required member {member:?}")]
+    MissingMember {
+        /// The member's name
+        member: &'static str,
+    },
+
+    /// issuer isn't a URI or an Object with an "id"
+    #[error("issuer must be a URI or an Object with an \"id\": {issuer:?}")]
+    InvalidIssuer {
+        /// The issuer, if present
+        issuer: Option<Value>,
+    },
+
+    /// credentialSubject isn't an Object or non-empty Array of Objects
+    #[error("credentialSubject must be an Object or a non-empty Array of Objects: {subject:?}")]
+    InvalidCredentialSubject {
+        /// The credentialSubject, if present
+        subject: Option<Value>,
+    },
+
+    /// A presentation's verifiableCredential isn't an Object or JWT-VC
+    #[error("verifiableCredential's must be Objects or JWT-VC Strings: {credential}")]
+    InvalidEmbeddedCredential {
+        /// The embedded credential
+        credential: Value,
+    },
+
+    /// holder isn't a URI or an Object with an "id"
+    #[error("holder must be a URI or an Object with an \"id\": {holder:?}")]
+    InvalidHolder {
+        /// The holder, if present
+        holder: Option<Value>,
+    },
+
+    /// A date isn't an RFC 3339 date-time
+    #[error("{member} must be an RFC 3339 date-time: {date}")]
+    InvalidDate {
+        /// The member's name
+        member: &'static str,
+        /// The date
+        date: Value,
+    },
+
+    /// The credential isn't valid yet
+    #[error("not valid until {member} ({date}), but now is {now}")]
+    NotYetValid {
+        /// issuanceDate or validFrom
+        member: &'static str,
+        /// The date, in seconds since the epoch
+        date: i64,
+        /// The current time, in seconds since the epoch
+        now: i64,
+    },
+
+    /// The credential has expired
+    #[error("expired at {member} ({date}), but now is {now}")]
+    Expired {
+        /// expirationDate or validUntil
+        member: &'static str,
+        /// The date, in seconds since the epoch
+        date: i64,
+        /// The current time, in seconds since the epoch
+        now: i64,
+    },
+
+    /// The current time isn't an integer
+    #[error("the current time must be an integer number of seconds since the epoch: {now}")]
+    NowNotInteger {
+        /// The current time
+        now: Number,
+    },
+
+    /// The "vc" or "vp" claim of a JWT is missing or not an Object
+    #[error("JWT claim {claim:?} must be an Object: {value:?}")]
+    InvalidJwtClaim {
+        /// "vc" or "vp"
+        claim: &'static str,
+        /// The claim, if present
+        value: Option<Value>,
+    },
+
+    /// An "nbf" or "exp" claim isn't a valid NumericDate
+    #[error("JWT claim {claim:?} must be an integer NumericDate: {date}")]
+    InvalidNumericDate {
+        /// "nbf" or "exp"
+        claim: &'static str,
+        /// The claim
+        date: Value,
+    },
+
+    /// A JWT claim disagrees with the credential or presentation
+    #[error("JWT claim for {member:?} is {claim_value}, but the member is {member_value}")]
+    JwtClaimMismatch {
+        /// The member's name
+        member: &'static str,
+        /// The member's value
+        member_value: Value,
+        /// The claim's value
+        claim_value: Value,
+    },
+
+    /// The JWT's signature is invalid
+    #[error("invalid JWT signature")]
+    InvalidSignature,
+
+    /// The JWT's "iss" claim is missing or not a String
+    #[error("JWT claim \"iss\" must be a String (the signer's DID): {iss:?}")]
+    InvalidIss {
+        /// The claim, if present
+        iss: Option<Value>,
+    },
+
+    /// The JWT header's "kid" isn't a String
+    #[error("JWT header \"kid\" must be a String: {kid}")]
+    InvalidKid {
+        /// The "kid"
+        kid: Value,
+    },
+
+    /// The signer's DID document has no key that can verify the JWT
+    #[error("{iss} has no {relationship} verification method with a publicKeyJwk for {algorithm} (kid: {kid:?})")]
+    NoVerificationMethod {
+        /// The JWT's "iss"
+        iss: String,
+        /// The verification relationship, e.g. "assertionMethod"
+        relationship: &'static str,
+        /// The JWT header's "alg"
+        algorithm: JwsAlgorithm,
+        /// The JWT header's "kid", if any
+        kid: Option<String>,
+    },
+
+    /// The signer's DID document is for another DID, or is malformed
+    #[error("{0}")]
+    DidError(#[from] DidError),
+
+    /// The JWT or JWK is malformed
+    #[error("{0}")]
+    JwsError(#[from] JwsError),
+}